@@ -0,0 +1,222 @@
+use gtk4::glib::{self, WeakRef};
+use gtk4::prelude::*;
+use gtk4::{ApplicationWindow, EventControllerMotion};
+use gtk4_layer_shell::LayerShell;
+use std::cell::{Cell, RefCell};
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+use crate::config::PanelConfig;
+use crate::niri_ipc::NiriEvent;
+use crate::niri_state;
+use crate::widgets::Lifecycle;
+
+/// How long `niri-panel reveal` shows hidden panels
+const REVEAL_DURATION: Duration = Duration::from_secs(3);
+
+/// Shrinks a panel window to a thin hot zone at its screen edge, and slides
+/// it back in while the pointer is over it, a popover is open, niri's
+/// overview is open, or after `niri-panel reveal`
+pub struct AutoHide {
+    inner: Rc<AutoHideInner>,
+}
+
+struct AutoHideInner {
+    window: WeakRef<ApplicationWindow>,
+    config: PanelConfig,
+    active_popovers: Rc<RefCell<i32>>,
+    hovered: Cell<bool>,
+    overview_open: Cell<bool>,
+    revealed: Cell<bool>,
+    // Set by `reveal`, keeps the panel shown until then
+    reveal_until: Cell<Option<Instant>>,
+    // Whether the timer checking when to hide again is running
+    checking: Cell<bool>,
+    lifecycle: Lifecycle,
+}
+
+thread_local! {
+    // Every auto-hiding panel, for `reveal_all`
+    static INSTANCES: RefCell<Vec<Weak<AutoHideInner>>> = RefCell::new(Vec::new());
+}
+
+impl AutoHide {
+    /// Start auto-hiding the window, or return `None` if the config doesn't enable it
+    pub fn attach(
+        window: &ApplicationWindow,
+        config: &PanelConfig,
+        active_popovers: Rc<RefCell<i32>>,
+    ) -> Option<Self> {
+        if !config.auto_hide.enabled {
+            return None;
+        }
+
+        let lifecycle = Lifecycle::new();
+        let inner = Rc::new(AutoHideInner {
+            window: window.downgrade(),
+            config: config.clone(),
+            active_popovers,
+            hovered: Cell::new(false),
+            overview_open: Cell::new(niri_state::with(|niri| niri.is_overview_open())),
+            revealed: Cell::new(true),
+            reveal_until: Cell::new(None),
+            checking: Cell::new(false),
+            lifecycle: lifecycle.clone(),
+        });
+
+        // Reveal when the pointer reaches the hot zone
+        let motion = EventControllerMotion::new();
+        let inner_enter = Rc::downgrade(&inner);
+        motion.connect_enter(move |_, _, _| {
+            if let Some(inner) = inner_enter.upgrade() {
+                inner.hovered.set(true);
+                inner.reveal();
+            }
+        });
+        let inner_leave = Rc::downgrade(&inner);
+        motion.connect_leave(move |_| {
+            if let Some(inner) = inner_leave.upgrade() {
+                inner.hovered.set(false);
+            }
+        });
+        window.add_controller(motion.clone());
+
+        let window_weak = window.downgrade();
+        lifecycle.on_shutdown(move || {
+            if let Some(window) = window_weak.upgrade() {
+                window.remove_controller(&motion);
+                if let Some(child) = window.child() {
+                    child.set_visible(true);
+                }
+                window.remove_css_class("auto-hidden");
+            }
+        });
+
+        // Stay revealed while niri's overview is open
+        let inner_niri = Rc::downgrade(&inner);
+        niri_state::subscribe(&lifecycle, move |niri, event| {
+            if let NiriEvent::OverviewOpenedOrClosed { .. } | NiriEvent::ConnectionChanged { .. } =
+                event
+            {
+                if let Some(inner) = inner_niri.upgrade() {
+                    inner.overview_open.set(niri.is_overview_open());
+                    if niri.is_overview_open() {
+                        inner.reveal();
+                    }
+                }
+            }
+        });
+
+        INSTANCES.with(|instances| {
+            let mut instances = instances.borrow_mut();
+            instances.retain(|instance| instance.strong_count() > 0);
+            instances.push(Rc::downgrade(&inner));
+        });
+
+        // Start shown, then hide once nothing keeps the panel up
+        inner.schedule_check();
+        info!("Auto-hide enabled");
+
+        Some(Self { inner })
+    }
+
+    /// Stop auto-hiding and leave the panel content visible
+    pub fn shutdown(&self) {
+        self.inner.lifecycle.shutdown();
+    }
+}
+
+/// Show every auto-hidden panel for a few seconds, returning how many panels auto-hide
+pub fn reveal_all() -> usize {
+    let instances: Vec<Rc<AutoHideInner>> = INSTANCES.with(|instances| {
+        instances
+            .borrow()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .filter(|inner| !inner.lifecycle.is_stopped())
+            .collect()
+    });
+
+    for inner in &instances {
+        inner.reveal_until.set(Some(Instant::now() + REVEAL_DURATION));
+        inner.reveal();
+    }
+    instances.len()
+}
+
+impl AutoHideInner {
+    fn should_stay_revealed(&self) -> bool {
+        self.hovered.get()
+            || *self.active_popovers.borrow() > 0
+            || self.overview_open.get()
+            || self.reveal_until.get().is_some_and(|until| Instant::now() < until)
+    }
+
+    fn reveal(self: &Rc<Self>) {
+        if !self.revealed.replace(true) {
+            debug!("Revealing auto-hidden panel");
+            self.set_hidden(false);
+        }
+        self.schedule_check();
+    }
+
+    fn hide(&self) {
+        if self.revealed.replace(false) {
+            debug!("Auto-hiding panel");
+            self.set_hidden(true);
+        }
+    }
+
+    /// While revealed, check periodically whether the panel can hide again
+    fn schedule_check(self: &Rc<Self>) {
+        if self.checking.replace(true) {
+            return;
+        }
+
+        let inner = Rc::downgrade(self);
+        let delay = Duration::from_millis(self.config.auto_hide.delay_ms as u64);
+        self.lifecycle.timeout_add_local(delay, move || {
+            let Some(inner) = inner.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            if inner.should_stay_revealed() {
+                return glib::ControlFlow::Continue;
+            }
+            inner.hide();
+            inner.checking.set(false);
+            glib::ControlFlow::Break
+        });
+    }
+
+    /// Swap between the full panel and the hot zone
+    fn set_hidden(&self, hidden: bool) {
+        let Some(window) = self.window.upgrade() else {
+            return;
+        };
+
+        if let Some(child) = window.child() {
+            child.set_visible(!hidden);
+        }
+
+        let size = if hidden {
+            self.config.auto_hide.hot_zone
+        } else {
+            self.config.height
+        };
+        if self.config.position.is_vertical() {
+            window.set_size_request(size, -1);
+        } else {
+            window.set_size_request(-1, size);
+        }
+
+        if hidden {
+            window.add_css_class("auto-hidden");
+        } else {
+            window.remove_css_class("auto-hidden");
+        }
+
+        // An auto-hiding panel floats over windows instead of reserving space
+        window.set_exclusive_zone(0);
+    }
+}
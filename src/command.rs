@@ -0,0 +1,151 @@
+//! Runs external commands without blocking the GTK main loop.
+//!
+//! Commands run on a small tokio runtime and are killed when they exceed
+//! their timeout. GTK code awaits [`Cmd::run`] from a future spawned on the
+//! main context, or passes a callback to [`Cmd::spawn`]; either way the
+//! result arrives back on the main thread. Code that already runs on a
+//! worker thread, like a service fetch, can use [`Cmd::run_blocking`].
+//! Dropping a run, or aborting the handle from [`Cmd::spawn`], kills the
+//! command.
+
+use anyhow::{anyhow, bail, Context, Result};
+use gtk4::glib;
+use once_cell::sync::Lazy;
+use std::ffi::{OsStr, OsString};
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+
+/// How long a command may run unless the call sets its own timeout
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+static RUNTIME: Lazy<Runtime> = Lazy::new(|| {
+    tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .thread_name("niri-panel-command")
+        .enable_all()
+        .build()
+        .expect("Failed to start the command runtime")
+});
+
+/// A command line to run with a time limit
+#[derive(Debug, Clone)]
+pub struct Cmd {
+    program: String,
+    args: Vec<OsString>,
+    current_dir: Option<PathBuf>,
+    timeout: Duration,
+}
+
+impl Cmd {
+    pub fn new(program: &str) -> Self {
+        Self {
+            program: program.to_string(),
+            args: Vec::new(),
+            current_dir: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    pub fn arg(mut self, arg: impl AsRef<OsStr>) -> Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    pub fn args<I, S>(mut self, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        self.args
+            .extend(args.into_iter().map(|arg| arg.as_ref().to_os_string()));
+        self
+    }
+
+    pub fn current_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(dir.into());
+        self
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run the command and collect its output. A non-zero exit status is
+    /// not an error; check `status` where it matters.
+    pub async fn run(self) -> Result<Output> {
+        let mut task = AbortOnDrop(RUNTIME.spawn(self.execute()));
+        (&mut task.0)
+            .await
+            .map_err(|e| anyhow!("Command task failed: {}", e))?
+    }
+
+    /// Run the command and call back on the main loop with its output.
+    /// Aborting the returned handle kills the command without calling back.
+    pub fn spawn(self, callback: impl FnOnce(Result<Output>) + 'static) -> glib::JoinHandle<()> {
+        let task = self.run();
+        glib::MainContext::default().spawn_local(async move {
+            callback(task.await);
+        })
+    }
+
+    /// Run the command and wait for it; never call this on the main thread
+    pub fn run_blocking(self) -> Result<Output> {
+        RUNTIME.block_on(self.execute())
+    }
+
+    async fn execute(self) -> Result<Output> {
+        let mut command = tokio::process::Command::new(&self.program);
+        command
+            .args(&self.args)
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        if let Some(dir) = &self.current_dir {
+            command.current_dir(dir);
+        }
+
+        match tokio::time::timeout(self.timeout, command.output()).await {
+            Ok(output) => output.with_context(|| format!("Failed to run {}", self.program)),
+            Err(_) => bail!("{} timed out after {:?}", self.program, self.timeout),
+        }
+    }
+}
+
+/// Stops a command task, and so kills its process, once nobody waits for it
+struct AbortOnDrop(tokio::task::JoinHandle<Result<Output>>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Standard output of a command that exited successfully
+pub fn stdout(output: Result<Output>) -> Option<String> {
+    match output {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        _ => None,
+    }
+}
+
+/// Whether `program` is an executable file on `$PATH`, found the way a
+/// shell would without starting one
+pub fn in_path(program: &str) -> bool {
+    find_in_path(program).is_some()
+}
+
+/// The first executable file named `program` on `$PATH`
+pub fn find_in_path(program: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(program))
+        .find(|candidate| {
+            std::fs::metadata(candidate)
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        })
+}
@@ -1,14 +1,36 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use chrono::format::{Item, StrftimeItems};
 use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::time::Duration;
+use tracing::warn;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PanelConfig {
+    /// Thickness of the panel: its height, or its width for a vertical panel
     pub height: i32,
+    /// Screen edge the panel is anchored to
+    #[serde(default)]
+    pub position: PanelPosition,
+    /// Layer-shell layer the panel is placed on
+    #[serde(default)]
+    pub layer: PanelLayer,
+    /// Gaps between the panel and the screen edges, for a floating panel
+    #[serde(default)]
+    pub margins: PanelMargins,
+    /// Whether the panel reserves space so windows aren't placed under it
+    #[serde(default = "default_true")]
+    pub exclusive_zone: bool,
+    /// Hide the panel until the pointer reaches its screen edge
+    #[serde(default)]
+    pub auto_hide: AutoHideConfig,
+    /// Move the panel below windows while one fills its monitor, e.g. fullscreen video
+    #[serde(default = "default_true")]
+    pub hide_on_fullscreen: bool,
     pub show_launcher: bool,
     pub show_places: bool,
     pub show_servers: bool,
@@ -22,9 +44,190 @@ pub struct PanelConfig {
     pub show_power: bool,
     pub show_git: bool,
     pub show_secrets: bool,
+    /// Show the focused window's title in the center of the panel
+    #[serde(default)]
+    pub show_window_title: bool,
+    /// Show a button for every open window
+    #[serde(default)]
+    pub show_taskbar: bool,
+    /// Show the active keyboard layout
+    #[serde(default)]
+    pub show_keyboard_layout: bool,
     pub clock_format: String,
     pub launcher_icon: String,
     pub git: GitConfig,
+    #[serde(default)]
+    pub taskbar: TaskbarConfig,
+    #[serde(default)]
+    pub keyboard_layout: KeyboardLayoutConfig,
+    /// Widgets showing the output of user commands, placed as `custom/<name>`
+    #[serde(default)]
+    pub custom: Vec<CustomWidgetConfig>,
+    /// Plugin processes drawing their own widgets, placed as `plugin/<name>`
+    #[serde(default)]
+    pub plugin: Vec<PluginConfig>,
+    /// Widget order per panel section; falls back to the `show_*` flags when absent
+    #[serde(default)]
+    pub layout: Option<LayoutConfig>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PanelPosition {
+    #[default]
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl PanelPosition {
+    /// Left and right panels lay out their widgets vertically
+    pub fn is_vertical(&self) -> bool {
+        matches!(self, PanelPosition::Left | PanelPosition::Right)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PanelLayer {
+    #[default]
+    Top,
+    Overlay,
+    Bottom,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PanelMargins {
+    #[serde(default)]
+    pub top: i32,
+    #[serde(default)]
+    pub bottom: i32,
+    #[serde(default)]
+    pub left: i32,
+    #[serde(default)]
+    pub right: i32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AutoHideConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Thickness of the strip left at the screen edge while hidden
+    #[serde(default = "default_hot_zone")]
+    pub hot_zone: i32,
+    /// Milliseconds to wait before hiding once nothing keeps the panel shown
+    #[serde(default = "default_hide_delay_ms")]
+    pub delay_ms: u32,
+}
+
+impl Default for AutoHideConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            hot_zone: default_hot_zone(),
+            delay_ms: default_hide_delay_ms(),
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_hot_zone() -> i32 {
+    2
+}
+
+fn default_hide_delay_ms() -> u32 {
+    500
+}
+
+/// Widget names that can be used in `[layout]`
+pub const WIDGET_NAMES: &[&str] = &[
+    "overview",
+    "workspaces",
+    "launcher",
+    "places",
+    "servers",
+    "search",
+    "git",
+    "secrets",
+    "sound",
+    "bluetooth",
+    "network",
+    "battery",
+    "clock",
+    "power",
+    "window-title",
+    "taskbar",
+    "keyboard-layout",
+];
+
+/// Widget names, in order, for each section of the panel
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LayoutConfig {
+    #[serde(default)]
+    pub left: Vec<String>,
+    #[serde(default)]
+    pub center: Vec<String>,
+    #[serde(default)]
+    pub right: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TaskbarConfig {
+    /// One button per app instead of one per window
+    #[serde(default)]
+    pub group_by_app: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KeyboardLayoutConfig {
+    /// Codes shown for layout names, e.g. `"English (Dvorak)" = "DV"`
+    #[serde(default)]
+    pub short_names: HashMap<String, String>,
+}
+
+/// A `[[custom]]` widget. The command prints plain text, or a JSON object
+/// with `text`, `tooltip`, `class` and `icon` fields.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CustomWidgetConfig {
+    pub name: String,
+    /// Shell command producing the widget's output
+    pub exec: String,
+    /// Seconds between runs; without it the command runs continuously and
+    /// each line it prints replaces the output
+    #[serde(default)]
+    pub interval: Option<u32>,
+    #[serde(default)]
+    pub on_click: Option<String>,
+    #[serde(default)]
+    pub on_scroll_up: Option<String>,
+    #[serde(default)]
+    pub on_scroll_down: Option<String>,
+}
+
+impl CustomWidgetConfig {
+    /// Name used for the widget in `[layout]`
+    pub fn layout_name(&self) -> String {
+        format!("custom/{}", self.name)
+    }
+}
+
+/// A `[[plugin]]` process speaking the protocol in `crate::plugin`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PluginConfig {
+    pub name: String,
+    /// Shell command starting the plugin
+    pub exec: String,
+}
+
+impl PluginConfig {
+    /// Name used for the widget in `[layout]` and on the command line
+    pub fn layout_name(&self) -> String {
+        format!("plugin/{}", self.name)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +255,12 @@ impl Default for PanelConfig {
     fn default() -> Self {
         Self {
             height: 32,
+            position: PanelPosition::default(),
+            layer: PanelLayer::default(),
+            margins: PanelMargins::default(),
+            exclusive_zone: true,
+            auto_hide: AutoHideConfig::default(),
+            hide_on_fullscreen: true,
             show_launcher: true,
             show_places: true,
             show_servers: true,
@@ -65,13 +274,32 @@ impl Default for PanelConfig {
             show_power: true,
             show_git: true,
             show_secrets: true,
+            show_window_title: false,
+            show_taskbar: false,
+            show_keyboard_layout: false,
             clock_format: "%a %b %e %l:%M %p".to_string(),
             launcher_icon: "view-app-grid-symbolic".to_string(),
             git: GitConfig::default(),
+            taskbar: TaskbarConfig::default(),
+            keyboard_layout: KeyboardLayoutConfig::default(),
+            custom: Vec::new(),
+            plugin: Vec::new(),
+            layout: None,
         }
     }
 }
 
+/// 1-based line and column of a byte offset
+fn line_and_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset.min(content.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rfind('\n')
+        .map_or(before.chars().count(), |newline| before[newline + 1..].chars().count())
+        + 1;
+    (line, column)
+}
+
 impl Default for GitConfig {
     fn default() -> Self {
         Self {
@@ -97,14 +325,23 @@ impl Default for GitConfig {
     }
 }
 
+/// Outcome of checking a config file
+#[derive(Debug, Default)]
+pub struct ConfigCheck {
+    /// The parsed config, if the file could be deserialized
+    pub config: Option<PanelConfig>,
+    /// Problems that make the config unusable
+    pub errors: Vec<String>,
+    /// Problems that are tolerated, such as unknown keys
+    pub warnings: Vec<String>,
+}
+
 impl PanelConfig {
     pub fn load() -> Result<Self> {
         let config_path = Self::config_path()?;
 
         if config_path.exists() {
-            let content = fs::read_to_string(&config_path)?;
-            let config: Self = toml::from_str(&content)?;
-            Ok(config)
+            Self::load_from(&config_path)
         } else {
             // Create default config
             let config = Self::default();
@@ -113,6 +350,199 @@ impl PanelConfig {
         }
     }
 
+    /// The widget layout to build, either from `[layout]` or derived from the
+    /// `show_*` flags in the traditional order
+    pub fn layout(&self) -> LayoutConfig {
+        if let Some(layout) = &self.layout {
+            return layout.clone();
+        }
+
+        let enabled = |show: bool, name: &str| show.then(|| name.to_string());
+
+        LayoutConfig {
+            left: [
+                Some("overview".to_string()),
+                enabled(self.show_workspaces, "workspaces"),
+                enabled(self.show_taskbar, "taskbar"),
+                enabled(self.show_launcher, "launcher"),
+                enabled(self.show_places, "places"),
+                enabled(self.show_servers, "servers"),
+                enabled(self.show_search, "search"),
+                enabled(self.show_git, "git"),
+                enabled(self.show_secrets, "secrets"),
+            ]
+            .into_iter()
+            .flatten()
+            .collect(),
+            center: enabled(self.show_window_title, "window-title")
+                .into_iter()
+                .collect(),
+            // Custom and plugin widgets go first on the right, in config order
+            right: self
+                .custom
+                .iter()
+                .map(|custom| Some(custom.layout_name()))
+                .chain(self.plugin.iter().map(|plugin| Some(plugin.layout_name())))
+                .chain([
+                    enabled(self.show_keyboard_layout, "keyboard-layout"),
+                    enabled(self.show_sound, "sound"),
+                    enabled(self.show_bluetooth, "bluetooth"),
+                    enabled(self.show_network, "network"),
+                    enabled(self.show_battery, "battery"),
+                    enabled(self.show_clock, "clock"),
+                    enabled(self.show_power, "power"),
+                ])
+                .flatten()
+                .collect(),
+        }
+    }
+
+    /// Load and validate a config file, failing on any error
+    pub fn load_from(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let check = Self::check(&content);
+
+        for warning in &check.warnings {
+            warn!("{}: {}", path.display(), warning);
+        }
+
+        match check.config {
+            Some(config) if check.errors.is_empty() => Ok(config),
+            _ => Err(anyhow!("{}", check.errors.join("; "))),
+        }
+    }
+
+    /// Parse config file contents, collecting unknown keys, type errors and bad values
+    pub fn check(content: &str) -> ConfigCheck {
+        let mut check = ConfigCheck::default();
+        let mut unknown_keys = Vec::new();
+
+        let deserializer = toml::Deserializer::new(content);
+        let result: Result<Self, toml::de::Error> =
+            serde_ignored::deserialize(deserializer, |path| unknown_keys.push(path.to_string()));
+
+        check.warnings.extend(
+            unknown_keys
+                .into_iter()
+                .map(|key| format!("unknown key `{}`", key)),
+        );
+
+        match result {
+            Ok(config) => {
+                check.errors.extend(config.validate());
+                check.warnings.extend(config.validate_layout());
+                check.config = Some(config);
+            }
+            Err(e) => {
+                let location = e.span().map(|span| line_and_column(content, span.start));
+                check.errors.push(match location {
+                    Some((line, column)) => {
+                        format!("line {}, column {}: {}", line, column, e.message())
+                    }
+                    None => e.message().to_string(),
+                });
+            }
+        }
+
+        check
+    }
+
+    /// Check values that deserialize fine but can't be used
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.height <= 0 {
+            errors.push(format!("height must be positive, got {}", self.height));
+        }
+
+        if self.auto_hide.hot_zone <= 0 {
+            errors.push(format!(
+                "auto_hide.hot_zone must be positive, got {}",
+                self.auto_hide.hot_zone
+            ));
+        }
+
+        if StrftimeItems::new(&self.clock_format).any(|item| matches!(item, Item::Error)) {
+            errors.push(format!(
+                "clock_format `{}` is not a valid strftime format",
+                self.clock_format
+            ));
+        }
+
+        for (idx, custom) in self.custom.iter().enumerate() {
+            if custom.name.is_empty() {
+                errors.push("custom widget has an empty name".to_string());
+            } else if self.custom[..idx].iter().any(|other| other.name == custom.name) {
+                errors.push(format!("custom widget `{}` is defined twice", custom.name));
+            }
+            if custom.exec.trim().is_empty() {
+                errors.push(format!("custom widget `{}` has an empty exec", custom.name));
+            }
+            if custom.interval == Some(0) {
+                errors.push(format!("custom widget `{}` has an interval of 0", custom.name));
+            }
+        }
+
+        for (idx, plugin) in self.plugin.iter().enumerate() {
+            if plugin.name.is_empty() {
+                errors.push("plugin has an empty name".to_string());
+            } else if self.plugin[..idx].iter().any(|other| other.name == plugin.name) {
+                errors.push(format!("plugin `{}` is defined twice", plugin.name));
+            }
+            if plugin.exec.trim().is_empty() {
+                errors.push(format!("plugin `{}` has an empty exec", plugin.name));
+            }
+        }
+
+        for service in &self.git.services {
+            if !service.url_pattern.contains("{owner}") {
+                errors.push(format!(
+                    "git service `{}` has a url_pattern without an {{owner}} placeholder",
+                    service.name
+                ));
+            }
+        }
+
+        errors
+    }
+
+    /// Unknown widget names are skipped when the panel is built
+    fn validate_layout(&self) -> Vec<String> {
+        let Some(layout) = &self.layout else {
+            return Vec::new();
+        };
+
+        [
+            ("left", &layout.left),
+            ("center", &layout.center),
+            ("right", &layout.right),
+        ]
+        .into_iter()
+        .flat_map(|(section, names)| {
+            names
+                .iter()
+                .filter(|name| {
+                    !WIDGET_NAMES.contains(&name.as_str())
+                        && self.custom_widget(name).is_none()
+                        && self.plugin_widget(name).is_none()
+                })
+                .map(move |name| format!("unknown widget `{}` in layout.{}", name, section))
+        })
+        .collect()
+    }
+
+    /// The `[[custom]]` widget with this layout name
+    pub fn custom_widget(&self, layout_name: &str) -> Option<&CustomWidgetConfig> {
+        let name = layout_name.strip_prefix("custom/")?;
+        self.custom.iter().find(|custom| custom.name == name)
+    }
+
+    /// The `[[plugin]]` with this layout name
+    pub fn plugin_widget(&self, layout_name: &str) -> Option<&PluginConfig> {
+        let name = layout_name.strip_prefix("plugin/")?;
+        self.plugin.iter().find(|plugin| plugin.name == name)
+    }
+
     pub fn save(&self) -> Result<()> {
         let config_path = Self::config_path()?;
         let config_dir = config_path.parent().unwrap();
@@ -130,6 +560,11 @@ impl PanelConfig {
         Ok(config_dir.join("niri-panel").join("config.toml"))
     }
 
+    /// Path of the user stylesheet loaded on top of the built-in styles
+    pub fn style_path() -> Result<PathBuf> {
+        Ok(Self::config_path()?.with_file_name("style.css"))
+    }
+
     pub fn watch_config_changes() -> Result<mpsc::Receiver<Event>> {
         let (tx, rx) = mpsc::channel();
 
@@ -0,0 +1,109 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Mutex;
+use tracing::{debug, warn};
+
+/// A change in panel state, streamed to `subscribe` clients as a single
+/// line of JSON, e.g. `{"event":"volume-changed","volume":40,"muted":false}`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum PanelEvent {
+    /// A widget's popover was shown
+    PopoverOpened { widget: String },
+    /// A widget's popover was hidden
+    PopoverClosed { widget: String },
+    /// The default audio output changed volume, mute state or device
+    VolumeChanged {
+        volume: u32,
+        muted: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        device: Option<String>,
+    },
+    /// The battery level or charging state changed
+    BatteryChanged { percentage: u32, charging: bool },
+    /// The network connection changed
+    NetworkChanged {
+        connected: bool,
+        connection_type: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        ssid: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        signal_strength: Option<u8>,
+        vpn_active: bool,
+    },
+    /// The config file was reloaded, or failed to load
+    ConfigReloaded {
+        ok: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+}
+
+impl PanelEvent {
+    /// Key for events that describe the current value of some state, so a
+    /// repeat of the last value can be dropped. Popover and config events
+    /// are always delivered.
+    fn state_key(&self) -> Option<&'static str> {
+        match self {
+            PanelEvent::VolumeChanged { .. } => Some("volume"),
+            PanelEvent::BatteryChanged { .. } => Some("battery"),
+            PanelEvent::NetworkChanged { .. } => Some("network"),
+            _ => None,
+        }
+    }
+}
+
+/// Fans panel events out to every connected `subscribe` client
+#[derive(Default)]
+pub struct EventBus {
+    subscribers: Mutex<Vec<mpsc::Sender<String>>>,
+    // Last serialized value of each state event, to skip repeats
+    last_state: Mutex<HashMap<&'static str, String>>,
+}
+
+static INSTANCE: Lazy<EventBus> = Lazy::new(EventBus::default);
+
+impl EventBus {
+    /// Get the global event bus
+    pub fn global() -> &'static EventBus {
+        &INSTANCE
+    }
+
+    /// Send an event to all subscribers. Widgets call this on every update;
+    /// state events that repeat the last value are dropped.
+    pub fn emit(&self, event: PanelEvent) {
+        let line = match serde_json::to_string(&event) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("Failed to serialize panel event {:?}: {}", event, e);
+                return;
+            }
+        };
+
+        if let Some(key) = event.state_key() {
+            let mut last_state = self.last_state.lock().unwrap();
+            if last_state.get(key) == Some(&line) {
+                return;
+            }
+            last_state.insert(key, line.clone());
+        }
+
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            return;
+        }
+
+        debug!("Emitting panel event: {}", line);
+        // Drop subscribers whose connection has gone away
+        subscribers.retain(|subscriber| subscriber.send(line.clone()).is_ok());
+    }
+
+    /// Receive every event emitted from now on, as lines of JSON
+    pub fn subscribe(&self) -> mpsc::Receiver<String> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+}
@@ -0,0 +1,165 @@
+use gtk4::glib::WeakRef;
+use gtk4::prelude::*;
+use gtk4::{gdk, ApplicationWindow};
+use gtk4_layer_shell::{Layer, LayerShell};
+use std::cell::Cell;
+use std::rc::Rc;
+use tracing::debug;
+
+use crate::config::PanelConfig;
+use crate::niri_state::{self, NiriState};
+use crate::widgets::Lifecycle;
+
+/// Tile sizes within this many logical pixels of the working area count as covering it
+const SIZE_TOLERANCE: f64 = 1.0;
+
+/// Moves a panel window to the bottom layer without an exclusive zone while
+/// the active window on its output covers the whole working area, as a
+/// fullscreen or maximized-to-edges window does, and puts it back afterwards
+pub struct FullscreenWatch {
+    inner: Rc<FullscreenWatchInner>,
+}
+
+struct FullscreenWatchInner {
+    window: WeakRef<ApplicationWindow>,
+    output: String,
+    // Whether the panel sits on the left or right edge, so its exclusive
+    // zone narrows the working area rather than shortening it
+    vertical: bool,
+    // Layer and exclusive zone to restore, set while stepped back
+    saved: Cell<Option<(Layer, i32)>>,
+    lifecycle: Lifecycle,
+}
+
+impl FullscreenWatch {
+    /// Start watching the output's windows, or return `None` if the config
+    /// doesn't enable it or the panel's output is unknown
+    pub fn attach(
+        window: &ApplicationWindow,
+        output: Option<&str>,
+        config: &PanelConfig,
+    ) -> Option<Self> {
+        if !config.hide_on_fullscreen {
+            return None;
+        }
+        let output = output?;
+
+        let lifecycle = Lifecycle::new();
+        let inner = Rc::new(FullscreenWatchInner {
+            window: window.downgrade(),
+            output: output.to_string(),
+            vertical: config.position.is_vertical(),
+            saved: Cell::new(None),
+            lifecycle: lifecycle.clone(),
+        });
+
+        // Always come back when the watch stops, e.g. on a config reload
+        let inner_shutdown = Rc::downgrade(&inner);
+        lifecycle.on_shutdown(move || {
+            if let Some(inner) = inner_shutdown.upgrade() {
+                inner.set_stepped_back(false);
+            }
+        });
+
+        let inner_niri = Rc::downgrade(&inner);
+        niri_state::subscribe(&lifecycle, move |niri, _| {
+            if let Some(inner) = inner_niri.upgrade() {
+                inner.update(niri);
+            }
+        });
+
+        // niri sends no event when the monitor changes mode or scale, but GDK does
+        if let Some(monitor) = find_monitor(output) {
+            let handler = monitor.connect_geometry_notify(|_| niri_state::refresh_outputs());
+            lifecycle.on_shutdown(move || monitor.disconnect(handler));
+        }
+
+        // When niri is already connected no `ConnectionChanged` is coming
+        niri_state::with(|niri| inner.update(niri));
+
+        Some(Self { inner })
+    }
+
+    /// Stop watching and restore the panel's layer and exclusive zone
+    pub fn shutdown(&self) {
+        self.inner.lifecycle.shutdown();
+    }
+}
+
+impl FullscreenWatchInner {
+    /// Logical size of the area windows can fill: the output less the space
+    /// the panel reserves, which niri takes off maximized windows. Zones
+    /// reserved by other layer-shell surfaces aren't known here.
+    fn working_area(&self, niri: &NiriState) -> Option<(f64, f64)> {
+        let logical = niri.output(&self.output)?.logical?;
+        // While stepped back nothing is reserved, but the saved zone decides
+        // whether the window would still fill the area once the panel returns
+        let reserved = match self.saved.get() {
+            Some((_, exclusive_zone)) => exclusive_zone,
+            None => self.window.upgrade()?.exclusive_zone(),
+        }
+        .max(0) as f64;
+
+        let (width, height) = (logical.width as f64, logical.height as f64);
+        Some(if self.vertical {
+            (width - reserved, height)
+        } else {
+            (width, height - reserved)
+        })
+    }
+
+    /// Whether the active window on the panel's output fills the working area
+    fn is_covered(&self, niri: &NiriState) -> bool {
+        let Some((width, height)) = self.working_area(niri) else {
+            return false;
+        };
+
+        niri.workspaces_on_output(Some(&self.output))
+            .iter()
+            .find(|workspace| workspace.is_active)
+            .and_then(|workspace| workspace.active_window_id)
+            .and_then(|id| niri.window(id))
+            .and_then(|window| window.tile_size)
+            .is_some_and(|(tile_width, tile_height)| {
+                tile_width + SIZE_TOLERANCE >= width && tile_height + SIZE_TOLERANCE >= height
+            })
+    }
+
+    fn update(&self, niri: &NiriState) {
+        if self.lifecycle.is_stopped() {
+            return;
+        }
+        self.set_stepped_back(niri.is_connected() && self.is_covered(niri));
+    }
+
+    fn set_stepped_back(&self, stepped_back: bool) {
+        let Some(window) = self.window.upgrade() else {
+            return;
+        };
+
+        match (stepped_back, self.saved.get()) {
+            (true, None) => {
+                debug!("Window covers output {}, moving the panel back", self.output);
+                self.saved.set(Some((window.layer(), window.exclusive_zone())));
+                window.set_layer(Layer::Bottom);
+                window.set_exclusive_zone(0);
+            }
+            (false, Some((layer, exclusive_zone))) => {
+                debug!("Output {} uncovered, restoring the panel", self.output);
+                self.saved.set(None);
+                window.set_layer(layer);
+                window.set_exclusive_zone(exclusive_zone);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The GDK monitor with the given connector name
+fn find_monitor(connector: &str) -> Option<gdk::Monitor> {
+    let monitors = gdk::Display::default()?.monitors();
+    (0..monitors.n_items())
+        .filter_map(|position| monitors.item(position))
+        .filter_map(|item| item.downcast::<gdk::Monitor>().ok())
+        .find(|monitor| monitor.connector().as_deref() == Some(connector))
+}
@@ -1,15 +1,266 @@
-use anyhow::{Context, Result};
-use gtk4::glib;
+use anyhow::{anyhow, bail, Context, Result};
+use gtk4::gio::prelude::*;
+use gtk4::{gio, glib};
+use serde::de::IntoDeserializer;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::cell::RefCell;
+use std::fmt;
 use std::fs;
 use std::io::{BufRead, BufReader, Write};
 use std::os::unix::net::{UnixListener, UnixStream};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::mpsc;
 use std::thread;
-use tracing::{error, info};
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, warn};
 
+use crate::autohide;
+use crate::config::{PanelConfig, WIDGET_NAMES};
+use crate::events::EventBus;
 use crate::popover_registry::PopoverRegistry;
+use crate::state_registry::StateRegistry;
 use crate::Widget;
 
+/// Version of the JSON protocol spoken over the socket
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// How long a connection waits for the main loop to process its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long `--replace` waits for the running panel to exit
+const QUIT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request, sent as a single line of JSON, e.g.
+/// `{"version":1,"command":"show","widget":"sound"}`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    pub version: u32,
+    #[serde(flatten)]
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Command {
+    /// Show a widget's popover
+    Show { widget: String },
+    /// Hide a widget's popover
+    Hide { widget: String },
+    /// Show a widget's popover if hidden, or hide it if shown
+    Toggle { widget: String },
+    /// Hide every open popover
+    HideAll,
+    /// Report which popovers are open
+    Status,
+    /// List widgets whose popovers can be shown
+    List,
+    /// Report a widget's current state, e.g. the battery level
+    Get { widget: String },
+    /// Keep the connection open and stream panel events, one JSON object per line
+    Subscribe,
+    /// Exit the panel, e.g. to hand over to a new instance
+    Quit,
+    /// Briefly show auto-hidden panels
+    Reveal,
+}
+
+/// The names in `Command`'s `command` tag, to tell an unknown command
+/// apart from a known one with bad arguments
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum CommandName {
+    Show,
+    Hide,
+    Toggle,
+    HideAll,
+    Status,
+    List,
+    Get,
+    Subscribe,
+    Quit,
+    Reveal,
+}
+
+/// The reply to a request, sent as a single line of JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Response {
+    pub version: u32,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<IpcError>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorCode {
+    /// The request was not valid JSON or was missing fields
+    InvalidRequest,
+    /// The request used a protocol version the panel doesn't speak
+    UnsupportedVersion,
+    /// The command is not known
+    UnknownCommand,
+    /// No widget has this name
+    UnknownWidget,
+    /// The widget exists but isn't part of the panel layout, or has no popover
+    WidgetDisabled,
+    /// The panel failed to process the request
+    Internal,
+}
+
+/// An error reported by the panel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpcError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl IpcError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for IpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for IpcError {}
+
+impl Request {
+    pub fn new(command: Command) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            command,
+        }
+    }
+
+    /// Parse a request line. Plain-text commands such as `show sound` from
+    /// older clients are accepted as well.
+    pub fn parse(line: &str) -> Result<Self, IpcError> {
+        let line = line.trim();
+        if !line.starts_with('{') {
+            return Self::parse_legacy(line);
+        }
+
+        let value: Value = serde_json::from_str(line)
+            .map_err(|e| IpcError::new(ErrorCode::InvalidRequest, format!("invalid JSON: {}", e)))?;
+
+        let version = value
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| IpcError::new(ErrorCode::InvalidRequest, "missing protocol version"))?;
+        if version != PROTOCOL_VERSION as u64 {
+            return Err(IpcError::new(
+                ErrorCode::UnsupportedVersion,
+                format!(
+                    "unsupported protocol version {} (expected {})",
+                    version, PROTOCOL_VERSION
+                ),
+            ));
+        }
+
+        let name = value
+            .get("command")
+            .and_then(Value::as_str)
+            .ok_or_else(|| IpcError::new(ErrorCode::InvalidRequest, "missing command"))?;
+        let known: Result<CommandName, serde::de::value::Error> =
+            CommandName::deserialize(name.into_deserializer());
+        if known.is_err() {
+            return Err(IpcError::new(
+                ErrorCode::UnknownCommand,
+                format!("unknown command '{}'", name),
+            ));
+        }
+
+        // The command is known, so anything left is a problem with its arguments
+        serde_json::from_value(value)
+            .map_err(|e| IpcError::new(ErrorCode::InvalidRequest, e.to_string()))
+    }
+
+    fn parse_legacy(line: &str) -> Result<Self, IpcError> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let widget = |command: &str| {
+            parts.get(1).map(|name| name.to_string()).ok_or_else(|| {
+                IpcError::new(
+                    ErrorCode::InvalidRequest,
+                    format!("missing widget name in '{}' command", command),
+                )
+            })
+        };
+
+        let command = match parts.first() {
+            Some(&"show") => Command::Show {
+                widget: widget("show")?,
+            },
+            Some(&"hide") => Command::Hide {
+                widget: widget("hide")?,
+            },
+            Some(&"toggle") => Command::Toggle {
+                widget: widget("toggle")?,
+            },
+            Some(&"hide-all") => Command::HideAll,
+            Some(&"status") => Command::Status,
+            Some(&"list") => Command::List,
+            Some(&"get") => Command::Get {
+                widget: widget("get")?,
+            },
+            Some(&"subscribe") => Command::Subscribe,
+            Some(&"quit") => Command::Quit,
+            Some(&"reveal") => Command::Reveal,
+            Some(other) => {
+                return Err(IpcError::new(
+                    ErrorCode::UnknownCommand,
+                    format!("unknown command '{}'", other),
+                ))
+            }
+            None => return Err(IpcError::new(ErrorCode::InvalidRequest, "empty request")),
+        };
+
+        Ok(Self::new(command))
+    }
+}
+
+impl Response {
+    pub fn success(data: Option<Value>) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            ok: true,
+            data,
+            error: None,
+        }
+    }
+
+    pub fn failure(error: IpcError) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            ok: false,
+            data: None,
+            error: Some(error),
+        }
+    }
+
+    /// The response data, or the panel's error
+    pub fn into_result(self) -> Result<Option<Value>, IpcError> {
+        match (self.ok, self.error) {
+            (true, _) => Ok(self.data),
+            (false, Some(error)) => Err(error),
+            (false, None) => Err(IpcError::new(ErrorCode::Internal, "request failed")),
+        }
+    }
+}
+
+/// A parsed request waiting for the main loop, with the channel to reply on
+type PendingRequest = (Command, mpsc::Sender<Response>);
+
 /// IPC server for niri-panel
 pub struct IpcServer {
     socket_path: PathBuf,
@@ -22,14 +273,19 @@ impl IpcServer {
         Ok(Self { socket_path })
     }
 
-    /// Start the IPC server
-    pub fn start(&self) -> Result<glib::SourceId> {
+    /// Start the IPC server. `config` is the panel's current configuration,
+    /// used to tell configured widgets from unknown ones.
+    pub fn start(&self, config: Rc<RefCell<PanelConfig>>) -> Result<glib::SourceId> {
         // Make sure the directory exists
         if let Some(parent) = self.socket_path.parent() {
             fs::create_dir_all(parent)?;
         }
 
-        // Remove the socket if it already exists
+        // Never take the socket from a live panel; a socket nobody listens
+        // on is left over from a panel that crashed
+        if UnixStream::connect(&self.socket_path).is_ok() {
+            bail!("another niri-panel is already listening on {:?}", self.socket_path);
+        }
         if self.socket_path.exists() {
             fs::remove_file(&self.socket_path)?;
         }
@@ -61,81 +317,174 @@ impl IpcServer {
         });
 
         // Attach the receiver to the GTK main loop
-        let source_id = receiver.attach(None, move |command| {
-            if let Err(e) = Self::process_command(&command) {
-                error!("Error processing IPC command: {}", e);
-            }
+        let source_id = receiver.attach(None, move |(command, reply): PendingRequest| {
+            let response = match Self::process_command(&command, &config.borrow()) {
+                Ok(data) => Response::success(data),
+                Err(e) => {
+                    warn!("IPC command {:?} failed: {}", command, e);
+                    Response::failure(e)
+                }
+            };
+            let _ = reply.send(response);
             glib::ControlFlow::Continue
         });
 
         Ok(source_id)
     }
 
-    /// Handle a connection
-    fn handle_connection(
-        stream: UnixStream,
-        sender: glib::Sender<String>,
-    ) -> Result<()> {
+    /// Handle a connection: read one request, wait for the main loop to
+    /// process it and write back the response
+    fn handle_connection(stream: UnixStream, sender: glib::Sender<PendingRequest>) -> Result<()> {
         let mut reader = BufReader::new(stream.try_clone()?);
         let mut line = String::new();
-        
-        // Read a single line from the stream
-        reader.read_line(&mut line)?;
-        
-        // Trim whitespace and send to main thread
-        let command = line.trim().to_string();
-        if !command.is_empty() {
-            sender.send(command)?;
+
+        // Read a single line from the stream; clients checking whether the
+        // panel is running connect and close without sending anything
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
         }
 
-        // Send an acknowledgment
+        let response = match Request::parse(&line) {
+            Ok(Request {
+                command: Command::Subscribe,
+                ..
+            }) => return Self::stream_events(stream),
+            Ok(request) => {
+                let (reply_tx, reply_rx) = mpsc::channel();
+                sender.send((request.command, reply_tx))?;
+                reply_rx.recv_timeout(REQUEST_TIMEOUT).unwrap_or_else(|_| {
+                    Response::failure(IpcError::new(
+                        ErrorCode::Internal,
+                        "the panel did not process the request in time",
+                    ))
+                })
+            }
+            Err(e) => Response::failure(e),
+        };
+
         let mut writer = stream;
-        writer.write_all(b"OK\n")?;
-        
+        writeln!(writer, "{}", serde_json::to_string(&response)?)?;
+
         Ok(())
     }
 
-    /// Process a command in the main thread
-    fn process_command(command: &str) -> Result<()> {
-        info!("Processing IPC command: {}", command);
-        
-        let parts: Vec<&str> = command.split_whitespace().collect();
-        if parts.is_empty() {
-            return Ok(());
+    /// Acknowledge a `subscribe` request, then write each panel event as a
+    /// line of JSON until the client disconnects
+    fn stream_events(mut stream: UnixStream) -> Result<()> {
+        let events = EventBus::global().subscribe();
+        writeln!(stream, "{}", serde_json::to_string(&Response::success(None))?)?;
+        info!("IPC client subscribed to panel events");
+
+        for event in events {
+            if writeln!(stream, "{}", event).is_err() {
+                break;
+            }
         }
 
-        match parts[0] {
-            "show" => {
-                if parts.len() < 2 {
-                    error!("Missing widget name in 'show' command");
-                    return Ok(());
+        info!("IPC event subscriber disconnected");
+        Ok(())
+    }
+
+    /// Process a command in the main thread
+    fn process_command(
+        command: &Command,
+        config: &PanelConfig,
+    ) -> Result<Option<Value>, IpcError> {
+        info!("Processing IPC command: {:?}", command);
+
+        let registry = PopoverRegistry::global();
+        match command {
+            Command::Show { widget } => {
+                if !registry.show(widget).map_err(Self::internal)? {
+                    return Err(Self::missing_widget(widget, config));
                 }
-                
-                let widget_name = parts[1];
-                let registry = PopoverRegistry::global();
-                registry.show(widget_name)?;
+                Ok(None)
             }
-            "hide" => {
-                if parts.len() < 2 {
-                    error!("Missing widget name in 'hide' command");
-                    return Ok(());
+            Command::Hide { widget } => {
+                if !registry.hide(widget).map_err(Self::internal)? {
+                    return Err(Self::missing_widget(widget, config));
                 }
-                
-                let widget_name = parts[1];
-                let registry = PopoverRegistry::global();
-                registry.hide(widget_name)?;
+                Ok(None)
+            }
+            Command::Toggle { widget } => match registry.toggle(widget).map_err(Self::internal)? {
+                Some(visible) => Ok(Some(json!({ "visible": visible }))),
+                None => Err(Self::missing_widget(widget, config)),
+            },
+            Command::HideAll => {
+                let hidden = registry.hide_all();
+                Ok(Some(json!({ "hidden": hidden })))
             }
-            "list" => {
-                let registry = PopoverRegistry::global();
-                let names = registry.get_names();
-                info!("Available widgets: {:?}", names);
+            Command::Status => {
+                let visible = registry.visible_names();
+                Ok(Some(json!({
+                    "open": visible.first(),
+                    "visible": visible,
+                })))
             }
-            _ => {
-                error!("Unknown IPC command: {}", parts[0]);
+            Command::List => {
+                let mut names = registry.get_names();
+                names.sort();
+                Ok(Some(json!({ "widgets": names })))
             }
+            Command::Get { widget } => match StateRegistry::get(widget) {
+                Some(state) => Ok(Some(state)),
+                None if Self::is_configurable(widget, config) => Err(IpcError::new(
+                    ErrorCode::WidgetDisabled,
+                    format!(
+                        "widget '{}' is not in the panel layout or has no state to report (available: {})",
+                        widget,
+                        StateRegistry::names().join(", ")
+                    ),
+                )),
+                None => Err(IpcError::new(
+                    ErrorCode::UnknownWidget,
+                    format!("unknown widget '{}'", widget),
+                )),
+            },
+            Command::Reveal => {
+                let revealed = autohide::reveal_all();
+                Ok(Some(json!({ "revealed": revealed })))
+            }
+            Command::Quit => {
+                info!("Quitting on IPC request");
+                // Let the response go out before the main loop stops
+                glib::idle_add_local_once(|| {
+                    if let Some(app) = gio::Application::default() {
+                        app.quit();
+                    }
+                });
+                Ok(None)
+            }
+            // Handled by the connection thread, which keeps the socket open
+            Command::Subscribe => Err(IpcError::new(
+                ErrorCode::Internal,
+                "subscribe is not processed by the main loop",
+            )),
         }
-        
-        Ok(())
+    }
+
+    /// Whether `name` is a built-in widget, or a custom or plugin widget in
+    /// the config, whether or not it is in the layout
+    fn is_configurable(name: &str, config: &PanelConfig) -> bool {
+        WIDGET_NAMES.contains(&name)
+            || config.custom_widget(name).is_some()
+            || config.plugin_widget(name).is_some()
+    }
+
+    /// Error for a widget without a registered popover
+    fn missing_widget(name: &str, config: &PanelConfig) -> IpcError {
+        if Self::is_configurable(name, config) {
+            IpcError::new(
+                ErrorCode::WidgetDisabled,
+                format!("widget '{}' is not in the panel layout or has no popover", name),
+            )
+        } else {
+            IpcError::new(ErrorCode::UnknownWidget, format!("unknown widget '{}'", name))
+        }
+    }
+
+    fn internal(e: anyhow::Error) -> IpcError {
+        IpcError::new(ErrorCode::Internal, e.to_string())
     }
 
     /// Get the socket path
@@ -151,29 +500,92 @@ impl IpcServer {
 pub struct IpcClient;
 
 impl IpcClient {
-    /// Send a command to niri-panel
-    pub fn send_command(command: &str) -> Result<String> {
+    /// Connect to the running panel's socket
+    fn connect() -> Result<UnixStream> {
         let socket_path = IpcServer::socket_path()?;
-        
-        // Connect to the socket
-        let mut stream = UnixStream::connect(&socket_path)
-            .context(format!("Failed to connect to socket: {:?}", socket_path))?;
-            
-        // Send the command
-        writeln!(stream, "{}", command)?;
-        
+        UnixStream::connect(&socket_path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound | std::io::ErrorKind::ConnectionRefused => anyhow!(
+                "niri-panel is not running (nothing is listening on {})",
+                socket_path.display()
+            ),
+            _ => anyhow!(e).context(format!("Failed to connect to socket: {:?}", socket_path)),
+        })
+    }
+
+    /// Whether a panel is listening on the socket
+    pub fn is_running() -> bool {
+        Self::connect().is_ok()
+    }
+
+    /// Ask the running panel to exit and wait until it has
+    pub fn quit_running() -> Result<()> {
+        // The panel may exit before its reply arrives
+        if let Err(e) = Self::command(Command::Quit) {
+            debug!("Quit request to the running panel failed: {:#}", e);
+        }
+
+        let started = Instant::now();
+        while Self::is_running() {
+            if started.elapsed() > QUIT_TIMEOUT {
+                bail!(
+                    "the running niri-panel did not exit within {} seconds",
+                    QUIT_TIMEOUT.as_secs()
+                );
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        Ok(())
+    }
+
+    /// Send a request to niri-panel and wait for its response
+    pub fn send(request: &Request) -> Result<Response> {
+        let mut stream = Self::connect()?;
+
+        // Send the request
+        writeln!(stream, "{}", serde_json::to_string(request)?)?;
+
         // Read the response
         let mut reader = BufReader::new(stream);
         let mut response = String::new();
         reader.read_line(&mut response)?;
-        
-        Ok(response.trim().to_string())
+
+        serde_json::from_str(&response)
+            .context(format!("Invalid response from niri-panel: {:?}", response.trim()))
     }
-    
+
+    /// Subscribe to panel events, calling `on_event` with each line of JSON
+    /// until the panel closes the connection
+    pub fn subscribe(mut on_event: impl FnMut(&str)) -> Result<()> {
+        let mut stream = Self::connect()?;
+
+        writeln!(stream, "{}", serde_json::to_string(&Request::new(Command::Subscribe))?)?;
+
+        // The first line acknowledges the subscription
+        let mut reader = BufReader::new(stream);
+        let mut response = String::new();
+        reader.read_line(&mut response)?;
+        let response: Response = serde_json::from_str(&response)
+            .context(format!("Invalid response from niri-panel: {:?}", response.trim()))?;
+        response.into_result()?;
+
+        for line in reader.lines() {
+            on_event(&line?);
+        }
+
+        Ok(())
+    }
+
+    /// Run a command, returning its data or the error reported by the panel
+    pub fn command(command: Command) -> Result<Option<Value>> {
+        Ok(Self::send(&Request::new(command))?.into_result()?)
+    }
+
     /// Show a widget popover
     pub fn show_widget(widget: Widget) -> Result<()> {
         let widget_name = PopoverRegistry::widget_to_name(&widget);
-        Self::send_command(&format!("show {}", widget_name))?;
+        Self::command(Command::Show {
+            widget: widget_name.to_string(),
+        })?;
         Ok(())
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,19 @@
 use clap::ValueEnum;
 
+pub mod autohide;
+pub mod command;
 pub mod config;
+pub mod events;
+pub mod fullscreen;
 pub mod ipc;
+pub mod network_manager;
 pub mod niri_ipc;
+pub mod niri_state;
 pub mod panel;
+pub mod plugin;
 pub mod popover_registry;
+pub mod services;
+pub mod state_registry;
 pub mod widgets;
 
 /// Available panel widgets that can be controlled
@@ -22,6 +31,8 @@ pub enum Widget {
     Battery,
     Clock,
     Power,
+    WindowTitle,
+    KeyboardLayout,
 }
 
 impl ToString for Widget {
@@ -39,6 +50,17 @@ impl ToString for Widget {
             Widget::Battery => "battery",
             Widget::Clock => "clock",
             Widget::Power => "power",
+            Widget::WindowTitle => "window-title",
+            Widget::KeyboardLayout => "keyboard-layout",
         }.to_string()
     }
-}
\ No newline at end of file
+}
+
+/// Parse a widget name given on the command line: a built-in widget, or
+/// `plugin/<name>` for a `[[plugin]]` widget
+pub fn parse_widget_name(name: &str) -> Result<String, String> {
+    match name.strip_prefix("plugin/") {
+        Some(plugin) if !plugin.is_empty() => Ok(name.to_string()),
+        _ => Widget::from_str(name, true).map(|widget| widget.to_string()),
+    }
+}
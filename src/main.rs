@@ -1,22 +1,27 @@
 use gtk4::prelude::*;
-use gtk4::{Application, ApplicationWindow};
+use gtk4::{gdk, Application, ApplicationWindow};
 use gtk4_layer_shell::{Layer, LayerShell};
 use notify::{Event, EventKind};
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 use tracing::{error, info, warn};
 use tracing_subscriber;
 use clap::{Parser, Subcommand};
 
+use niri_panel::autohide::AutoHide;
+use niri_panel::fullscreen::FullscreenWatch;
 use niri_panel::config;
+use niri_panel::events;
 use niri_panel::ipc;
 use niri_panel::panel;
 use niri_panel::popover_registry;
 use niri_panel::widgets;
-use niri_panel::Widget;
+use niri_panel::{parse_widget_name, Widget};
 
-use config::PanelConfig;
-use ipc::{IpcClient, IpcServer};
+use config::{PanelConfig, PanelLayer, PanelPosition};
+use events::{EventBus, PanelEvent};
+use ipc::{Command as IpcCommand, IpcClient, IpcServer};
 use panel::Panel;
 use popover_registry::{PopoverRegistry};
 
@@ -24,6 +29,10 @@ use popover_registry::{PopoverRegistry};
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Replace an already running panel instead of exiting
+    #[arg(long, global = true)]
+    replace: bool,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -32,18 +41,45 @@ struct Args {
 enum Commands {
     /// Show a specific widget popover
     Show {
-        /// Widget to show
-        #[arg(value_enum)]
-        widget: Widget,
+        /// Widget to show: a built-in widget, or plugin/<name>
+        #[arg(value_parser = parse_widget_name)]
+        widget: String,
     },
     /// Hide a specific widget popover
     Hide {
-        /// Widget to hide
-        #[arg(value_enum)]
-        widget: Widget,
+        /// Widget to hide: a built-in widget, or plugin/<name>
+        #[arg(value_parser = parse_widget_name)]
+        widget: String,
     },
+    /// Show a widget popover if it is hidden, or hide it if it is shown
+    Toggle {
+        /// Widget to toggle: a built-in widget, or plugin/<name>
+        #[arg(value_parser = parse_widget_name)]
+        widget: String,
+    },
+    /// Hide all open widget popovers
+    HideAll,
+    /// Print the open widget popover, if any
+    Status,
     /// List available widgets
     List,
+    /// Print a widget's current state as JSON
+    Get {
+        /// Widget to query
+        #[arg(value_enum)]
+        widget: Widget,
+    },
+    /// Print panel events as lines of JSON until the panel exits
+    Subscribe,
+    /// Exit the running panel
+    Quit,
+    /// Briefly show the panel when it is auto-hidden
+    Reveal,
+    /// Check a config file for errors without starting the panel
+    CheckConfig {
+        /// Config file to check (defaults to ~/.config/niri-panel/config.toml)
+        path: Option<PathBuf>,
+    },
     /// Run the panel (default when no subcommand is specified)
     Run
 }
@@ -64,6 +100,16 @@ fn main() -> anyhow::Result<()> {
         _ => return handle_command(command),
     }
 
+    // Only one panel runs at a time; the IPC socket tells whether one is up
+    if IpcClient::is_running() {
+        if !args.replace {
+            eprintln!("error: niri-panel is already running (use --replace to replace it)");
+            std::process::exit(1);
+        }
+        info!("Replacing the running panel");
+        IpcClient::quit_running()?;
+    }
+
     // Check if GSettings schemas are available
     match gtk4::gio::SettingsSchemaSource::default() {
         Some(_) => info!("GSettings schema source found."),
@@ -72,8 +118,11 @@ fn main() -> anyhow::Result<()> {
     // We continue anyway as the panel doesn't strictly require these schemas
 
     // Create GTK application
+    // Uniqueness is handled through the IPC socket above, so a replacing
+    // panel doesn't hand its activation to the exiting one over D-Bus
     let app = Application::builder()
         .application_id("org.niri.panel")
+        .flags(gtk4::gio::ApplicationFlags::NON_UNIQUE)
         .build();
 
     app.connect_activate(|app| {
@@ -94,23 +143,139 @@ fn handle_command(command: Commands) -> anyhow::Result<()> {
         Commands::Show { widget } => {
             info!("Showing widget: {:?}", widget);
             // Use the IPC client to show the widget
-            let widget_name = widget.to_string().to_lowercase();
-            let response = IpcClient::send_command(&format!("show {}", widget_name))?;
-            println!("{}", response);
+            send_ipc_command(IpcCommand::Show { widget });
+            println!("OK");
             Ok(())
         },
         Commands::Hide { widget } => {
             info!("Hiding widget: {:?}", widget);
-            let widget_name = widget.to_string().to_lowercase();
-            let response = IpcClient::send_command(&format!("hide {}", widget_name))?;
-            println!("{}", response);
+            send_ipc_command(IpcCommand::Hide { widget });
+            println!("OK");
+            Ok(())
+        },
+        Commands::Toggle { widget } => {
+            info!("Toggling widget: {:?}", widget);
+            let data = send_ipc_command(IpcCommand::Toggle { widget });
+            let visible = data
+                .as_ref()
+                .and_then(|data| data.get("visible"))
+                .and_then(|visible| visible.as_bool());
+            println!("{}", if visible == Some(true) { "shown" } else { "hidden" });
+            Ok(())
+        },
+        Commands::HideAll => {
+            // Print each popover that was hidden on its own line
+            let data = send_ipc_command(IpcCommand::HideAll);
+            let hidden = data
+                .as_ref()
+                .and_then(|data| data.get("hidden"))
+                .and_then(|hidden| hidden.as_array())
+                .cloned()
+                .unwrap_or_default();
+            for widget in hidden {
+                if let Some(name) = widget.as_str() {
+                    println!("{}", name);
+                }
+            }
+            Ok(())
+        },
+        Commands::Status => {
+            // Print each open popover on its own line; nothing when none is open
+            let data = send_ipc_command(IpcCommand::Status);
+            let visible = data
+                .as_ref()
+                .and_then(|data| data.get("visible"))
+                .and_then(|visible| visible.as_array())
+                .cloned()
+                .unwrap_or_default();
+            for widget in visible {
+                if let Some(name) = widget.as_str() {
+                    println!("{}", name);
+                }
+            }
             Ok(())
         },
         Commands::List => {
-            let response = IpcClient::send_command("list")?;
-            println!("Available widgets: {}", response);
+            let data = send_ipc_command(IpcCommand::List);
+            let widgets = data
+                .as_ref()
+                .and_then(|data| data.get("widgets"))
+                .and_then(|widgets| widgets.as_array())
+                .cloned()
+                .unwrap_or_default();
+            for widget in widgets {
+                if let Some(name) = widget.as_str() {
+                    println!("{}", name);
+                }
+            }
+            Ok(())
+        },
+        Commands::Get { widget } => {
+            let data = send_ipc_command(IpcCommand::Get {
+                widget: widget.to_string(),
+            });
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&data.unwrap_or_default())?
+            );
             Ok(())
         },
+        Commands::Subscribe => {
+            let result = IpcClient::subscribe(|event| println!("{}", event));
+            if let Err(e) = result {
+                eprintln!("error: {:#}", e);
+                std::process::exit(1);
+            }
+            Ok(())
+        },
+        Commands::Reveal => {
+            // Print how many panels were revealed; none means nothing auto-hides
+            let data = send_ipc_command(IpcCommand::Reveal);
+            let revealed = data
+                .as_ref()
+                .and_then(|data| data.get("revealed"))
+                .and_then(|revealed| revealed.as_u64())
+                .unwrap_or(0);
+            if revealed == 0 {
+                eprintln!("error: no panel has auto-hide enabled");
+                std::process::exit(1);
+            }
+            println!("{}", revealed);
+            Ok(())
+        },
+        Commands::Quit => {
+            send_ipc_command(IpcCommand::Quit);
+            println!("OK");
+            Ok(())
+        },
+        Commands::CheckConfig { path } => {
+            let path = match path {
+                Some(path) => path,
+                None => PanelConfig::config_path()?,
+            };
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+            let check = PanelConfig::check(&content);
+
+            for error in &check.errors {
+                println!("error: {}", error);
+            }
+            for warning in &check.warnings {
+                println!("warning: {}", warning);
+            }
+
+            if check.errors.is_empty() {
+                println!(
+                    "{}: OK ({} warning(s))",
+                    path.display(),
+                    check.warnings.len()
+                );
+                Ok(())
+            } else {
+                println!("{}: {} error(s)", path.display(), check.errors.len());
+                std::process::exit(1);
+            }
+        },
         Commands::Run => {
             // This should never happen as Run is handled in main()
             unreachable!()
@@ -118,50 +283,95 @@ fn handle_command(command: Commands) -> anyhow::Result<()> {
     }
 }
 
+/// Send a command to the running panel, exiting with an error status if it fails
+fn send_ipc_command(command: IpcCommand) -> Option<serde_json::Value> {
+    match IpcClient::command(command) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("error: {:#}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// A layer-shell panel window bound to a single monitor
+struct PanelWindow {
+    monitor: gdk::Monitor,
+    /// Connector name of the monitor (e.g. "DP-1"), used to filter workspaces
+    output: Option<String>,
+    window: ApplicationWindow,
+    panel: Panel,
+    /// Number of open popovers, shared by every panel built for this window
+    active_popovers: Rc<RefCell<i32>>,
+    /// Set when the config enables auto-hide
+    auto_hide: Option<AutoHide>,
+    /// Set when the config moves the panel back for fullscreen windows
+    fullscreen_watch: Option<FullscreenWatch>,
+}
+
+/// All panel windows, one per connected monitor
+struct PanelWindows {
+    windows: Vec<PanelWindow>,
+    /// Error from the last failed config load, shown on every panel
+    config_error: Option<String>,
+    // Keep the application alive while no monitor is connected
+    _hold: gtk4::gio::ApplicationHoldGuard,
+}
+
 fn build_ui(app: &Application) -> anyhow::Result<()> {
+    // Load configuration, falling back to the defaults so the panel still comes up
+    let (config, config_error) = match PanelConfig::load() {
+        Ok(config) => (config, None),
+        Err(e) => {
+            error!("Failed to load configuration, using defaults: {}", e);
+            (PanelConfig::default(), Some(e.to_string()))
+        }
+    };
+    let config = Rc::new(RefCell::new(config));
+
     // Start IPC server
     let ipc_server = IpcServer::new()?;
-    if let Err(e) = ipc_server.start() {
+    if let Err(e) = ipc_server.start(config.clone()) {
+        // Another panel started at the same time and won the socket
+        if IpcClient::is_running() {
+            error!("Not starting: {}", e);
+            app.quit();
+            return Ok(());
+        }
         warn!("Failed to start IPC server: {}", e);
         // Continue anyway as the panel can work without IPC
     }
 
-    // Load configuration
-    let config = PanelConfig::load()?;
-
-    // Create main window
-    let window = ApplicationWindow::builder()
-        .application(app)
-        .title("Niri Panel")
-        .build();
-
-    // Initialize layer shell
-    window.init_layer_shell();
-    window.set_layer(Layer::Top);
-    window.set_anchor(gtk4_layer_shell::Edge::Top, true);
-    window.set_anchor(gtk4_layer_shell::Edge::Left, true);
-    window.set_anchor(gtk4_layer_shell::Edge::Right, true);
-
-    // Set exclusive zone to reserve space
-    window.set_exclusive_zone(config.height);
-
-    // Set keyboard mode to None initially (panel doesn't capture keyboard)
-    window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::None);
-
-    // Set window properties
-    window.set_height_request(config.height);
-    window.set_margin(gtk4_layer_shell::Edge::Top, 0);
-    window.set_margin(gtk4_layer_shell::Edge::Bottom, 0);
-    window.set_margin(gtk4_layer_shell::Edge::Left, 0);
-    window.set_margin(gtk4_layer_shell::Edge::Right, 0);
-
-    // Create shared state for keyboard mode management
-    let window_weak = window.downgrade();
-    let active_popovers = Rc::new(RefCell::new(0));
+    let display = gdk::Display::default()
+        .ok_or_else(|| anyhow::anyhow!("Could not get default display"))?;
+
+    // Create one panel per monitor
+    let panels = Rc::new(RefCell::new(PanelWindows {
+        windows: Vec::new(),
+        config_error,
+        _hold: app.hold(),
+    }));
+    sync_panels(app, &config.borrow(), &panels);
+
+    // Stop widget child processes, such as plugins, when the panel exits
+    let panels_shutdown = panels.clone();
+    app.connect_shutdown(move |_| {
+        for panel_window in &panels_shutdown.borrow().windows {
+            panel_window.panel.shutdown();
+        }
+    });
 
-    // Create and setup panel with keyboard mode management
-    let panel = Panel::new(config.clone(), window_weak.clone(), active_popovers.clone())?;
-    window.set_child(Some(panel.container()));
+    // Add and remove panels as monitors are hotplugged
+    let monitors = display.monitors();
+    let app_weak = app.downgrade();
+    let config_monitors = config.clone();
+    let panels_monitors = panels.clone();
+    monitors.connect_items_changed(move |_, _, removed, added| {
+        if let Some(app) = app_weak.upgrade() {
+            info!("Monitors changed ({} removed, {} added), updating panels", removed, added);
+            sync_panels(&app, &config_monitors.borrow(), &panels_monitors);
+        }
+    });
 
     // Apply CSS styling
     let css_provider = gtk4::CssProvider::new();
@@ -171,25 +381,32 @@ fn build_ui(app: &Application) -> anyhow::Result<()> {
         Ok(css_data) => {
             css_provider.load_from_data(css_data);
 
-            if let Some(display) = gtk4::gdk::Display::default() {
-                gtk4::style_context_add_provider_for_display(
-                    &display,
-                    &css_provider,
-                    gtk4::STYLE_PROVIDER_PRIORITY_USER,
-                );
-                info!("CSS styles loaded successfully");
-            } else {
-                warn!("Could not get default display for CSS styling");
-            }
+            gtk4::style_context_add_provider_for_display(
+                &display,
+                &css_provider,
+                gtk4::STYLE_PROVIDER_PRIORITY_USER,
+            );
+            info!("CSS styles loaded successfully");
         }
         Err(e) => {
             error!("Failed to load CSS data: {}", e);
         }
     }
 
+    // Load user stylesheet overrides on top of the built-in styles
+    let user_css_provider = gtk4::CssProvider::new();
+    user_css_provider.connect_parsing_error(|_, section, error| {
+        warn!("Error in user stylesheet at {}: {}", section, error);
+    });
+    load_user_style(&user_css_provider);
+    gtk4::style_context_add_provider_for_display(
+        &display,
+        &user_css_provider,
+        gtk4::STYLE_PROVIDER_PRIORITY_USER + 1,
+    );
+
     // Set up config file watching
     if let Ok(rx) = PanelConfig::watch_config_changes() {
-        let app_weak = app.downgrade();
         let config_path = match PanelConfig::config_path() {
             Ok(path) => path.to_string_lossy().to_string(),
             Err(_) => String::from("config file"),
@@ -208,59 +425,47 @@ fn build_ui(app: &Application) -> anyhow::Result<()> {
 
         // Handle events in the main thread
         receiver.attach(None, move |event: Event| {
-            if let Some(app) = app_weak.upgrade() {
-                if let EventKind::Modify(_) | EventKind::Create(_) = event.kind {
-                    // Check if the event is for our config file
-                    for path in event.paths {
-                        let path_str = path.to_string_lossy();
-                        if path_str.ends_with("config.toml") {
-                            info!("Config file changed: {}", path_str);
-                            
-                            // Try to load the new configuration
-                            match PanelConfig::load() {
-                                Ok(new_config) => {
-                                    info!("Reloading configuration");
-                                    
-                                    // Get the current window
-                                    let windows = app.windows();
-                                    if !windows.is_empty() {
-                                        // Assuming the first window is the panel
-                                        let window = windows.first().unwrap();
-                                        
-                                        // Convert the window to ApplicationWindow
-                                        if let Some(app_window) = window.downcast_ref::<ApplicationWindow>() {
-                                            // Create window weak reference
-                                            let window_weak = app_window.downgrade();
-                                            let active_popovers = Rc::new(RefCell::new(0));
-                                            
-                                            // Create new panel with new config
-                                            if let Ok(panel) = Panel::new(
-                                                new_config.clone(),
-                                                window_weak,
-                                                active_popovers
-                                            ) {
-                                                // Update window height if changed
-                                                window.set_height_request(new_config.height);
-                                                app_window.set_exclusive_zone(new_config.height);
-                                                
-                                                // Replace the old panel with the new one
-                                                window.set_child(Some(panel.container()));
-                                                info!("Panel reloaded with new configuration");
-                                            } else {
-                                                error!("Failed to create new panel with updated config");
-                                            }
-                                        }
-                                    }
-                                },
-                                Err(e) => {
-                                    error!("Failed to load new configuration: {}", e);
+            let removed = matches!(event.kind, EventKind::Remove(_));
+            if let EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_) = event.kind {
+                // Check if the event is for our config file or stylesheet
+                for path in event.paths {
+                    let path_str = path.to_string_lossy();
+                    if path_str.ends_with("config.toml") && !removed {
+                        info!("Config file changed: {}", path_str);
+
+                        // Try to load the new configuration
+                        match PanelConfig::load() {
+                            Ok(new_config) => {
+                                info!("Reloading configuration");
+                                panels.borrow_mut().config_error = None;
+                                reload_panels(&new_config, &panels);
+                                *config.borrow_mut() = new_config;
+                                EventBus::global().emit(PanelEvent::ConfigReloaded {
+                                    ok: true,
+                                    error: None,
+                                });
+                            }
+                            Err(e) => {
+                                // Keep running with the last good config
+                                error!("Failed to load new configuration: {}", e);
+                                let mut panels = panels.borrow_mut();
+                                panels.config_error = Some(e.to_string());
+                                for panel_window in &panels.windows {
+                                    panel_window.panel.set_config_error(Some(&e.to_string()));
                                 }
+                                EventBus::global().emit(PanelEvent::ConfigReloaded {
+                                    ok: false,
+                                    error: Some(e.to_string()),
+                                });
                             }
                         }
+                    } else if path_str.ends_with("style.css") {
+                        info!("User stylesheet changed: {}", path_str);
+                        load_user_style(&user_css_provider);
                     }
                 }
             }
-            
+
             // Continue receiving events
             gtk4::glib::ControlFlow::Continue
         });
@@ -270,8 +475,229 @@ fn build_ui(app: &Application) -> anyhow::Result<()> {
         warn!("Failed to set up config file watcher");
     }
 
-    window.present();
-
     info!("Niri Panel initialized successfully");
     Ok(())
 }
+
+/// Load `~/.config/niri-panel/style.css` into the provider, or clear it if the file is gone
+fn load_user_style(provider: &gtk4::CssProvider) {
+    match PanelConfig::style_path() {
+        Ok(path) if path.exists() => {
+            provider.load_from_path(&path);
+            info!("User stylesheet loaded from {}", path.display());
+        }
+        Ok(_) => provider.load_from_data(""),
+        Err(e) => warn!("Could not locate user stylesheet: {}", e),
+    }
+}
+
+/// Create a panel for every connected monitor that lacks one, and destroy
+/// the panels of monitors that are gone. Panels on monitors that stay are
+/// left alone.
+fn sync_panels(app: &Application, config: &PanelConfig, panels: &Rc<RefCell<PanelWindows>>) {
+    let Some(display) = gdk::Display::default() else {
+        warn!("Could not get default display, no panels created");
+        return;
+    };
+
+    let monitors = display.monitors();
+    let monitors: Vec<gdk::Monitor> = (0..monitors.n_items())
+        .filter_map(|position| monitors.item(position))
+        .filter_map(|item| item.downcast::<gdk::Monitor>().ok())
+        .collect();
+
+    // Build the new panels before destroying the old ones, so the services
+    // and niri stream they share keep a subscriber and keep their values
+    for monitor in &monitors {
+        let has_panel = panels
+            .borrow()
+            .windows
+            .iter()
+            .any(|panel_window| panel_window.monitor == *monitor);
+        if has_panel {
+            continue;
+        }
+
+        match create_panel_window(app, config, monitor) {
+            Ok(panel_window) => {
+                let mut panels = panels.borrow_mut();
+                panel_window.panel.set_config_error(panels.config_error.as_deref());
+                panels.windows.push(panel_window);
+            }
+            Err(e) => error!(
+                "Failed to create panel for monitor {:?}: {}",
+                monitor.connector(),
+                e
+            ),
+        }
+    }
+
+    let removed: Vec<PanelWindow> = {
+        let mut panels = panels.borrow_mut();
+        let (kept, removed) = panels
+            .windows
+            .drain(..)
+            .partition(|panel_window| monitors.contains(&panel_window.monitor));
+        panels.windows = kept;
+        removed
+    };
+    for panel_window in removed {
+        if let Some(auto_hide) = &panel_window.auto_hide {
+            auto_hide.shutdown();
+        }
+        if let Some(fullscreen_watch) = &panel_window.fullscreen_watch {
+            fullscreen_watch.shutdown();
+        }
+        panel_window.panel.shutdown();
+        panel_window.window.destroy();
+        info!("Removed the panel on output {:?}", panel_window.output);
+    }
+
+    info!("Running {} panel(s)", panels.borrow().windows.len());
+}
+
+/// Create a layer-shell panel window on the given monitor
+fn create_panel_window(
+    app: &Application,
+    config: &PanelConfig,
+    monitor: &gdk::Monitor,
+) -> anyhow::Result<PanelWindow> {
+    let output = monitor.connector().map(|connector| connector.to_string());
+
+    // Create main window
+    let window = ApplicationWindow::builder()
+        .application(app)
+        .title("Niri Panel")
+        .build();
+
+    // Initialize layer shell
+    window.init_layer_shell();
+    window.set_monitor(monitor);
+    configure_layer_shell(&window, config);
+
+    // Set keyboard mode to None initially (panel doesn't capture keyboard)
+    window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::None);
+
+    // Create shared state for keyboard mode management
+    let window_weak = window.downgrade();
+    let active_popovers = Rc::new(RefCell::new(0));
+
+    // Create and setup panel with keyboard mode management
+    let panel = Panel::new(
+        config.clone(),
+        output.clone(),
+        window_weak,
+        active_popovers.clone(),
+    )?;
+    window.set_child(Some(panel.container()));
+
+    window.present();
+    info!("Panel created on output {:?}", output);
+
+    let auto_hide = AutoHide::attach(&window, config, active_popovers.clone());
+    let fullscreen_watch = FullscreenWatch::attach(&window, output.as_deref(), config);
+
+    Ok(PanelWindow {
+        monitor: monitor.clone(),
+        output,
+        window,
+        panel,
+        active_popovers,
+        auto_hide,
+        fullscreen_watch,
+    })
+}
+
+/// Apply the configured edge, layer, size, margins and exclusive zone to a panel window
+fn configure_layer_shell(window: &ApplicationWindow, config: &PanelConfig) {
+    use gtk4_layer_shell::Edge;
+
+    window.set_layer(match config.layer {
+        PanelLayer::Top => Layer::Top,
+        PanelLayer::Overlay => Layer::Overlay,
+        PanelLayer::Bottom => Layer::Bottom,
+    });
+
+    // Anchor to the configured edge and stretch along it
+    let (top, bottom, left, right) = match config.position {
+        PanelPosition::Top => (true, false, true, true),
+        PanelPosition::Bottom => (false, true, true, true),
+        PanelPosition::Left => (true, true, true, false),
+        PanelPosition::Right => (true, true, false, true),
+    };
+    window.set_anchor(Edge::Top, top);
+    window.set_anchor(Edge::Bottom, bottom);
+    window.set_anchor(Edge::Left, left);
+    window.set_anchor(Edge::Right, right);
+
+    // Set window size across the panel
+    if config.position.is_vertical() {
+        window.set_size_request(config.height, -1);
+    } else {
+        window.set_size_request(-1, config.height);
+    }
+
+    window.set_margin(Edge::Top, config.margins.top);
+    window.set_margin(Edge::Bottom, config.margins.bottom);
+    window.set_margin(Edge::Left, config.margins.left);
+    window.set_margin(Edge::Right, config.margins.right);
+
+    // Reserve space for the panel and its gap to the edge; an auto-hiding
+    // panel floats over windows instead
+    if config.exclusive_zone && !config.auto_hide.enabled {
+        let edge_margin = match config.position {
+            PanelPosition::Top => config.margins.top,
+            PanelPosition::Bottom => config.margins.bottom,
+            PanelPosition::Left => config.margins.left,
+            PanelPosition::Right => config.margins.right,
+        };
+        window.set_exclusive_zone(config.height + edge_margin);
+    } else {
+        window.set_exclusive_zone(0);
+    }
+}
+
+/// Replace the panel in every window with one built from the new configuration
+fn reload_panels(config: &PanelConfig, panels: &Rc<RefCell<PanelWindows>>) {
+    for panel_window in &mut panels.borrow_mut().windows {
+        let window = &panel_window.window;
+        let window_weak = window.downgrade();
+
+        // Create new panel with new config
+        match Panel::new(
+            config.clone(),
+            panel_window.output.clone(),
+            window_weak,
+            panel_window.active_popovers.clone(),
+        ) {
+            Ok(panel) => {
+                // Restore the full panel before applying the new config
+                if let Some(auto_hide) = panel_window.auto_hide.take() {
+                    auto_hide.shutdown();
+                }
+                if let Some(fullscreen_watch) = panel_window.fullscreen_watch.take() {
+                    fullscreen_watch.shutdown();
+                }
+
+                // Update edge, size and layer if changed
+                configure_layer_shell(window, config);
+
+                // Replace the old panel with the new one
+                window.set_child(Some(panel.container()));
+
+                // Stop the old panel's timers and processes, closing its popovers
+                let old_panel = std::mem::replace(&mut panel_window.panel, panel);
+                old_panel.shutdown();
+
+                panel_window.auto_hide =
+                    AutoHide::attach(window, config, panel_window.active_popovers.clone());
+                panel_window.fullscreen_watch =
+                    FullscreenWatch::attach(window, panel_window.output.as_deref(), config);
+                info!("Panel on output {:?} reloaded with new configuration", panel_window.output);
+            }
+            Err(e) => {
+                error!("Failed to create new panel with updated config: {}", e);
+            }
+        }
+    }
+}
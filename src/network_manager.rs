@@ -0,0 +1,530 @@
+//! A client for NetworkManager's D-Bus API
+//!
+//! Reads are blocking and meant for the network service's worker thread.
+//! Requests like activating a connection are async and run on the main
+//! loop. NetworkManager reports every change as a signal, so
+//! [`NetworkManager::subscribe`] is all a consumer needs to stay current.
+
+use anyhow::{anyhow, Context, Result};
+use gtk4::gio::{
+    self, BusType, DBusCallFlags, DBusConnection, DBusConnectionFlags, DBusSignalFlags,
+    SignalSubscriptionId,
+};
+use gtk4::glib::variant::{FromVariant, ObjectPath};
+use gtk4::glib::{ToVariant, Variant};
+use std::collections::HashMap;
+use std::rc::Rc;
+use tracing::debug;
+
+pub const BUS_NAME: &str = "org.freedesktop.NetworkManager";
+pub const MANAGER_PATH: &str = "/org/freedesktop/NetworkManager";
+pub const SETTINGS_PATH: &str = "/org/freedesktop/NetworkManager/Settings";
+
+pub const MANAGER_INTERFACE: &str = "org.freedesktop.NetworkManager";
+pub const SETTINGS_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings";
+pub const CONNECTION_INTERFACE: &str = "org.freedesktop.NetworkManager.Settings.Connection";
+pub const ACTIVE_INTERFACE: &str = "org.freedesktop.NetworkManager.Connection.Active";
+pub const DEVICE_INTERFACE: &str = "org.freedesktop.NetworkManager.Device";
+pub const WIRELESS_INTERFACE: &str = "org.freedesktop.NetworkManager.Device.Wireless";
+pub const ACCESS_POINT_INTERFACE: &str = "org.freedesktop.NetworkManager.AccessPoint";
+pub const IP4_CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.IP4Config";
+pub const IP6_CONFIG_INTERFACE: &str = "org.freedesktop.NetworkManager.IP6Config";
+
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// How long NetworkManager may take to answer a call
+const CALL_TIMEOUT_MS: i32 = 5_000;
+
+/// `NM_DEVICE_TYPE_WIFI`
+const DEVICE_TYPE_WIFI: u32 = 2;
+/// `NM_ACTIVE_CONNECTION_STATE_ACTIVATED`
+const ACTIVE_STATE_ACTIVATED: u32 = 2;
+/// `NM_802_11_AP_FLAGS_PRIVACY`, set by WEP access points
+const AP_FLAGS_PRIVACY: u32 = 0x1;
+
+/// NetworkManager's view of the machine, read in one go
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    pub wireless_enabled: bool,
+    /// The active connection holding the default route
+    pub primary_connection: Option<String>,
+    pub active_connections: Vec<ActiveConnection>,
+    pub devices: Vec<Device>,
+    /// Saved connection profiles
+    pub connections: Vec<Connection>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActiveConnection {
+    pub path: String,
+    pub id: String,
+    pub uuid: String,
+    /// Setting type, like `802-11-wireless`, `802-3-ethernet` or `vpn`
+    pub kind: String,
+    pub activated: bool,
+    pub vpn: bool,
+    /// Devices the connection runs on
+    pub devices: Vec<String>,
+    pub ip4_addresses: Vec<String>,
+    pub ip6_addresses: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Device {
+    pub path: String,
+    pub interface: String,
+    pub wifi: bool,
+    pub active_connection: Option<String>,
+    /// The rest is only set for wireless devices
+    pub active_access_point: Option<String>,
+    pub access_points: Vec<AccessPoint>,
+    /// `CLOCK_BOOTTIME` of the last finished scan in milliseconds, or -1
+    pub last_scan: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessPoint {
+    pub path: String,
+    pub ssid: String,
+    /// Signal quality in percent
+    pub strength: u8,
+    pub secured: bool,
+    pub bssid: String,
+}
+
+/// A saved connection profile
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Connection {
+    pub path: String,
+    pub id: String,
+    pub uuid: String,
+    pub kind: String,
+    /// Network name of wireless profiles
+    pub ssid: Option<String>,
+}
+
+/// A connection to the bus NetworkManager runs on
+#[derive(Debug, Clone)]
+pub struct NetworkManager {
+    connection: DBusConnection,
+}
+
+/// Signal subscriptions made by [`NetworkManager::subscribe`]
+pub struct Subscription {
+    connection: DBusConnection,
+    ids: Vec<SignalSubscriptionId>,
+}
+
+impl Subscription {
+    pub fn unsubscribe(self) {
+        for id in self.ids {
+            self.connection.signal_unsubscribe(id);
+        }
+    }
+}
+
+impl NetworkManager {
+    /// Use the system bus, where NetworkManager normally runs
+    pub fn system() -> Result<Self> {
+        let connection = gio::bus_get_sync(BusType::System, gio::Cancellable::NONE)
+            .context("Failed to connect to the system bus")?;
+        Ok(Self { connection })
+    }
+
+    /// Use the message bus at `address`, e.g. a private bus in tests
+    pub fn connect_to(address: &str) -> Result<Self> {
+        let connection = DBusConnection::for_address_sync(
+            address,
+            DBusConnectionFlags::AUTHENTICATION_CLIENT
+                | DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+            None,
+            gio::Cancellable::NONE,
+        )
+        .with_context(|| format!("Failed to connect to the bus at {}", address))?;
+        Ok(Self { connection })
+    }
+
+    /// Call `callback` on the current thread's main context whenever
+    /// NetworkManager reports a change the snapshot shows, starts or stops.
+    /// Access point property changes like `Strength` come several times a
+    /// second while scanning, so they are left out; the network service
+    /// still polls for them.
+    pub fn subscribe(&self, callback: impl Fn() + 'static) -> Subscription {
+        let callback = Rc::new(callback);
+        let subscribe = |interface: &str, member: Option<&str>, arg0: Option<&str>| {
+            let callback = callback.clone();
+            self.connection.signal_subscribe(
+                Some(BUS_NAME),
+                Some(interface),
+                member,
+                None,
+                arg0,
+                DBusSignalFlags::NONE,
+                move |_, _, _, _, _, _| callback(),
+            )
+        };
+
+        let mut ids = vec![
+            // Networking, device and connection state
+            subscribe(MANAGER_INTERFACE, Some("StateChanged"), None),
+            subscribe(DEVICE_INTERFACE, Some("StateChanged"), None),
+            subscribe(ACTIVE_INTERFACE, Some("StateChanged"), None),
+            // Devices, networks in range and saved profiles coming and going
+            subscribe(MANAGER_INTERFACE, Some("DeviceAdded"), None),
+            subscribe(MANAGER_INTERFACE, Some("DeviceRemoved"), None),
+            subscribe(WIRELESS_INTERFACE, Some("AccessPointAdded"), None),
+            subscribe(WIRELESS_INTERFACE, Some("AccessPointRemoved"), None),
+            subscribe(SETTINGS_INTERFACE, Some("NewConnection"), None),
+            subscribe(SETTINGS_INTERFACE, Some("ConnectionRemoved"), None),
+        ];
+        // Property changes, but only on the objects the snapshot follows
+        for interface in [
+            MANAGER_INTERFACE,
+            DEVICE_INTERFACE,
+            WIRELESS_INTERFACE,
+            ACTIVE_INTERFACE,
+        ] {
+            ids.push(subscribe(
+                PROPERTIES_INTERFACE,
+                Some("PropertiesChanged"),
+                Some(interface),
+            ));
+        }
+
+        ids.push(self.connection.signal_subscribe(
+            Some("org.freedesktop.DBus"),
+            Some("org.freedesktop.DBus"),
+            Some("NameOwnerChanged"),
+            Some("/org/freedesktop/DBus"),
+            Some(BUS_NAME),
+            DBusSignalFlags::NONE,
+            move |_, _, _, _, _, _| callback(),
+        ));
+
+        Subscription {
+            connection: self.connection.clone(),
+            ids,
+        }
+    }
+
+    /// Read devices, access points and connections. Blocks, so keep it off
+    /// the main thread.
+    pub fn read(&self) -> Result<Snapshot> {
+        let manager = self.properties(MANAGER_PATH, MANAGER_INTERFACE)?;
+
+        // Objects can disappear between listing and reading them
+        let active_connections = manager
+            .paths("ActiveConnections")
+            .iter()
+            .filter_map(|path| skip_gone(path, self.read_active_connection(path)))
+            .collect();
+        let devices = manager
+            .paths("Devices")
+            .iter()
+            .filter_map(|path| skip_gone(path, self.read_device(path)))
+            .collect();
+
+        let reply = self.call_sync(SETTINGS_PATH, SETTINGS_INTERFACE, "ListConnections", None)?;
+        let connections = parse::<Vec<ObjectPath>>(&reply.child_value(0))?
+            .iter()
+            .filter_map(|path| skip_gone(path, self.read_connection(path)))
+            .collect();
+
+        Ok(Snapshot {
+            wireless_enabled: manager.get("WirelessEnabled").unwrap_or(false),
+            primary_connection: manager.path("PrimaryConnection"),
+            active_connections,
+            devices,
+            connections,
+        })
+    }
+
+    fn read_active_connection(&self, path: &str) -> Result<ActiveConnection> {
+        let active = self.properties(path, ACTIVE_INTERFACE)?;
+        let kind = active.string("Type");
+        let ip4_addresses = match active.path("Ip4Config") {
+            Some(config) => self.read_addresses(&config, IP4_CONFIG_INTERFACE)?,
+            None => Vec::new(),
+        };
+        let ip6_addresses = match active.path("Ip6Config") {
+            Some(config) => self.read_addresses(&config, IP6_CONFIG_INTERFACE)?,
+            None => Vec::new(),
+        };
+
+        Ok(ActiveConnection {
+            path: path.to_string(),
+            id: active.string("Id"),
+            uuid: active.string("Uuid"),
+            activated: active.get::<u32>("State") == Some(ACTIVE_STATE_ACTIVATED),
+            // WireGuard connections aren't VPN plugins, but are VPNs to the user
+            vpn: active.get("Vpn").unwrap_or(false) || kind == "wireguard",
+            kind,
+            devices: active.paths("Devices"),
+            ip4_addresses,
+            ip6_addresses,
+        })
+    }
+
+    fn read_addresses(&self, config: &str, interface: &str) -> Result<Vec<String>> {
+        let config = self.properties(config, interface)?;
+        let addresses: Vec<HashMap<String, Variant>> =
+            config.get("AddressData").unwrap_or_default();
+        Ok(addresses
+            .iter()
+            .filter_map(|address| address.get("address")?.get::<String>())
+            .collect())
+    }
+
+    fn read_device(&self, path: &str) -> Result<Device> {
+        let device = self.properties(path, DEVICE_INTERFACE)?;
+        let mut result = Device {
+            path: path.to_string(),
+            interface: device.string("Interface"),
+            wifi: device.get::<u32>("DeviceType") == Some(DEVICE_TYPE_WIFI),
+            active_connection: device.path("ActiveConnection"),
+            active_access_point: None,
+            access_points: Vec::new(),
+            last_scan: -1,
+        };
+
+        if result.wifi {
+            let wireless = self.properties(path, WIRELESS_INTERFACE)?;
+            result.active_access_point = wireless.path("ActiveAccessPoint");
+            result.last_scan = wireless.get("LastScan").unwrap_or(-1);
+            result.access_points = wireless
+                .paths("AccessPoints")
+                .iter()
+                .filter_map(|path| skip_gone(path, self.read_access_point(path)))
+                .collect();
+        }
+
+        Ok(result)
+    }
+
+    fn read_access_point(&self, path: &str) -> Result<AccessPoint> {
+        let access_point = self.properties(path, ACCESS_POINT_INTERFACE)?;
+        let ssid: Vec<u8> = access_point.get("Ssid").unwrap_or_default();
+        let flags: u32 = access_point.get("Flags").unwrap_or(0);
+        let wpa_flags: u32 = access_point.get("WpaFlags").unwrap_or(0);
+        let rsn_flags: u32 = access_point.get("RsnFlags").unwrap_or(0);
+
+        Ok(AccessPoint {
+            path: path.to_string(),
+            ssid: String::from_utf8_lossy(&ssid).into_owned(),
+            strength: access_point.get("Strength").unwrap_or(0),
+            secured: flags & AP_FLAGS_PRIVACY != 0 || wpa_flags != 0 || rsn_flags != 0,
+            bssid: access_point.string("HwAddress"),
+        })
+    }
+
+    fn read_connection(&self, path: &str) -> Result<Connection> {
+        let reply = self.call_sync(path, CONNECTION_INTERFACE, "GetSettings", None)?;
+        let settings: HashMap<String, HashMap<String, Variant>> = parse(&reply.child_value(0))?;
+        let setting = |group: &str, key: &str| settings.get(group)?.get(key).cloned();
+
+        Ok(Connection {
+            path: path.to_string(),
+            id: setting("connection", "id")
+                .and_then(|id| id.get())
+                .unwrap_or_default(),
+            uuid: setting("connection", "uuid")
+                .and_then(|uuid| uuid.get())
+                .unwrap_or_default(),
+            kind: setting("connection", "type")
+                .and_then(|kind| kind.get())
+                .unwrap_or_default(),
+            ssid: setting("802-11-wireless", "ssid")
+                .and_then(|ssid| ssid.get::<Vec<u8>>())
+                .map(|ssid| String::from_utf8_lossy(&ssid).into_owned()),
+        })
+    }
+
+    /// Ask a wireless device to scan; new access points arrive as signals
+    pub async fn request_scan(&self, device: &str) -> Result<()> {
+        let options: HashMap<String, Variant> = HashMap::new();
+        self.call(
+            device,
+            WIRELESS_INTERFACE,
+            "RequestScan",
+            (options,).to_variant(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Bring up a saved connection, on a given device and access point if set
+    pub async fn activate(
+        &self,
+        connection: &str,
+        device: Option<&str>,
+        specific_object: Option<&str>,
+    ) -> Result<()> {
+        let parameters = (
+            object_path(Some(connection))?,
+            object_path(device)?,
+            object_path(specific_object)?,
+        )
+            .to_variant();
+        self.call(
+            MANAGER_PATH,
+            MANAGER_INTERFACE,
+            "ActivateConnection",
+            parameters,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Save a new connection to an access point and bring it up.
+    /// NetworkManager fills in the SSID and key management from the
+    /// access point.
+    pub async fn connect_wifi(
+        &self,
+        device: &str,
+        access_point: &str,
+        password: Option<&str>,
+    ) -> Result<()> {
+        let mut settings: HashMap<String, HashMap<String, Variant>> = HashMap::new();
+        if let Some(password) = password {
+            settings.insert(
+                "802-11-wireless-security".to_string(),
+                HashMap::from([("psk".to_string(), password.to_variant())]),
+            );
+        }
+
+        let parameters = (
+            settings,
+            object_path(Some(device))?,
+            object_path(Some(access_point))?,
+        )
+            .to_variant();
+        self.call(
+            MANAGER_PATH,
+            MANAGER_INTERFACE,
+            "AddAndActivateConnection",
+            parameters,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Take down an active connection
+    pub async fn deactivate(&self, active_connection: &str) -> Result<()> {
+        let parameters = (object_path(Some(active_connection))?,).to_variant();
+        self.call(
+            MANAGER_PATH,
+            MANAGER_INTERFACE,
+            "DeactivateConnection",
+            parameters,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Switch the WiFi radio on or off
+    pub async fn set_wireless_enabled(&self, enabled: bool) -> Result<()> {
+        let parameters = (MANAGER_INTERFACE, "WirelessEnabled", enabled.to_variant()).to_variant();
+        self.call(MANAGER_PATH, PROPERTIES_INTERFACE, "Set", parameters)
+            .await?;
+        Ok(())
+    }
+
+    fn properties(&self, path: &str, interface: &str) -> Result<Properties> {
+        let reply = self.call_sync(
+            path,
+            PROPERTIES_INTERFACE,
+            "GetAll",
+            Some(&(interface,).to_variant()),
+        )?;
+        Ok(Properties(parse(&reply.child_value(0))?))
+    }
+
+    fn call_sync(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        parameters: Option<&Variant>,
+    ) -> Result<Variant> {
+        self.connection
+            .call_sync(
+                Some(BUS_NAME),
+                path,
+                interface,
+                method,
+                parameters,
+                None,
+                DBusCallFlags::NONE,
+                CALL_TIMEOUT_MS,
+                gio::Cancellable::NONE,
+            )
+            .with_context(|| format!("NetworkManager {}.{} on {} failed", interface, method, path))
+    }
+
+    async fn call(
+        &self,
+        path: &str,
+        interface: &str,
+        method: &str,
+        parameters: Variant,
+    ) -> Result<Variant> {
+        self.connection
+            .call_future(
+                Some(BUS_NAME),
+                path,
+                interface,
+                method,
+                Some(&parameters),
+                None,
+                DBusCallFlags::NONE,
+                CALL_TIMEOUT_MS,
+            )
+            .await
+            .with_context(|| format!("NetworkManager {} failed", method))
+    }
+}
+
+/// Properties of one object, from `org.freedesktop.DBus.Properties.GetAll`
+struct Properties(HashMap<String, Variant>);
+
+impl Properties {
+    fn get<T: FromVariant>(&self, name: &str) -> Option<T> {
+        self.0.get(name)?.get()
+    }
+
+    fn string(&self, name: &str) -> String {
+        self.get(name).unwrap_or_default()
+    }
+
+    /// An object path, where `/` means none
+    fn path(&self, name: &str) -> Option<String> {
+        self.get::<ObjectPath>(name)
+            .map(String::from)
+            .filter(|path| path != "/")
+    }
+
+    fn paths(&self, name: &str) -> Vec<String> {
+        self.get::<Vec<ObjectPath>>(name)
+            .unwrap_or_default()
+            .into_iter()
+            .map(String::from)
+            .collect()
+    }
+}
+
+fn parse<T: FromVariant>(value: &Variant) -> Result<T> {
+    value
+        .get()
+        .ok_or_else(|| anyhow!("Unexpected reply type {}", value.type_()))
+}
+
+fn object_path(path: Option<&str>) -> Result<ObjectPath> {
+    let path = path.unwrap_or("/");
+    ObjectPath::try_from(path).map_err(|_| anyhow!("Invalid object path {}", path))
+}
+
+fn skip_gone<T>(path: &str, result: Result<T>) -> Option<T> {
+    result
+        .map_err(|e| debug!("Skipping {}: {:#}", path, e))
+        .ok()
+}
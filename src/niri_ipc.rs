@@ -1,34 +1,124 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use gtk4::glib;
+use serde::Serialize;
 use serde_json::Value;
-use std::io::{BufRead, BufReader};
-use std::process::{Child, Command, Stdio};
+use std::io::{BufRead, BufReader, Write};
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc;
 use std::thread;
+use std::time::Duration;
 use tracing::{debug, error, info, warn};
 
+/// How long a query or action may take before niri is considered unresponsive
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A request to niri, sent as JSON over `$NIRI_SOCKET`
+#[derive(Debug, Clone, Serialize)]
+pub enum Request {
+    Workspaces,
+    Windows,
+    Outputs,
+    FocusedWindow,
+    KeyboardLayouts,
+    /// Keep the connection open and stream events
+    EventStream,
+    Action(Action),
+}
+
+/// Actions the panel asks niri to perform
+#[derive(Debug, Clone, Serialize)]
+pub enum Action {
+    FocusWorkspace { reference: WorkspaceReference },
+    FocusWindow { id: u64 },
+    CloseWindow { id: Option<u64> },
+    ToggleWindowFloating { id: Option<u64> },
+    FullscreenWindow { id: Option<u64> },
+    MoveWindowToWorkspace {
+        window_id: Option<u64>,
+        reference: WorkspaceReference,
+        focus: bool,
+    },
+    SwitchLayout { layout: LayoutSwitchTarget },
+    ToggleOverview {},
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum WorkspaceReference {
+    Id(u64),
+    Index(u8),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub enum LayoutSwitchTarget {
+    Next,
+    Prev,
+    Index(u8),
+}
+
+/// An event from niri's event stream. See `NiriState::apply` for how each
+/// one changes the compositor state.
 #[derive(Debug, Clone)]
 pub enum NiriEvent {
     WorkspacesChanged {
         workspaces: Vec<WorkspaceInfo>,
     },
-    WindowsChanged {
-        windows: Vec<WindowInfo>,
+    WorkspaceUrgencyChanged {
+        id: u64,
+        urgent: bool,
     },
     WorkspaceActivated {
         id: u64,
         focused: bool,
     },
+    WorkspaceActiveWindowChanged {
+        workspace_id: u64,
+        active_window_id: Option<u64>,
+    },
+    WindowsChanged {
+        windows: Vec<WindowInfo>,
+    },
+    WindowOpenedOrChanged {
+        window: WindowInfo,
+    },
+    WindowClosed {
+        id: u64,
+    },
     WindowFocusChanged {
+        id: Option<u64>,
+    },
+    WindowUrgencyChanged {
         id: u64,
+        urgent: bool,
+    },
+    /// New tile sizes of windows that were resized, e.g. made fullscreen
+    WindowLayoutsChanged {
+        tile_sizes: Vec<(u64, (f64, f64))>,
     },
     KeyboardLayoutsChanged {
-        names: Vec<String>,
-        current_idx: usize,
+        keyboard_layouts: KeyboardLayouts,
+    },
+    KeyboardLayoutSwitched {
+        idx: usize,
     },
     OverviewOpenedOrClosed {
         is_open: bool,
     },
+    ConfigLoaded {
+        failed: bool,
+    },
+    /// Sent by the panel rather than niri when the event stream connects,
+    /// or is lost and will be retried
+    ConnectionChanged {
+        connected: bool,
+    },
+    /// Sent by the panel rather than niri after re-reading the outputs,
+    /// since niri has no event for them
+    OutputsChanged {
+        outputs: Vec<OutputInfo>,
+    },
     Unknown(Value),
 }
 
@@ -44,162 +134,409 @@ pub struct WorkspaceInfo {
     pub active_window_id: Option<u64>,
 }
 
+/// An output (monitor) as reported by niri
+#[derive(Debug, Clone)]
+pub struct OutputInfo {
+    pub name: String,
+    pub make: String,
+    pub model: String,
+    /// Position and size in the logical coordinate space, if the output is enabled
+    pub logical: Option<LogicalOutput>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LogicalOutput {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct KeyboardLayouts {
+    pub names: Vec<String>,
+    pub current_idx: usize,
+}
+
 #[derive(Debug, Clone)]
 pub struct WindowInfo {
     pub id: u64,
     pub title: String,
     pub app_id: Option<String>,
     pub pid: u64,
-    pub workspace_id: u64,
+    /// Workspace the window is on; `None` while it isn't on any
+    pub workspace_id: Option<u64>,
     pub is_focused: bool,
     pub is_floating: bool,
     pub is_urgent: bool,
+    /// Size of the window's tile in logical pixels; `None` from niri
+    /// versions that don't report window layouts
+    pub tile_size: Option<(f64, f64)>,
 }
 
-/// Start the event stream and return a channel to receive events
-pub fn start_event_stream() -> Result<(mpsc::Receiver<NiriEvent>, Child)> {
-    // Create a channel for sending events back to the main thread
-    let (tx, rx) = mpsc::channel();
-
-    // Start the event-stream command
-    let mut child = Command::new("niri")
-        .args(["msg", "-j", "event-stream"])
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-
-    let stdout = match child.stdout.take() {
-        Some(stdout) => stdout,
-        None => return Err(anyhow!("Failed to get stdout")),
-    };
-    
-    let stderr = match child.stderr.take() {
-        Some(stderr) => stderr,
-        None => return Err(anyhow!("Failed to get stderr")),
-    };
+/// Path of the niri IPC socket, from `$NIRI_SOCKET`
+pub fn socket_path() -> Result<PathBuf> {
+    match std::env::var_os("NIRI_SOCKET") {
+        Some(path) if !path.is_empty() => Ok(PathBuf::from(path)),
+        _ => bail!("NIRI_SOCKET is not set; is niri running?"),
+    }
+}
 
-    // Clone the sender for the stderr handler
-    let stderr_tx = tx.clone();
+/// A connection to niri's IPC socket. Each connection carries one request.
+pub struct NiriSocket {
+    stream: UnixStream,
+    reader: BufReader<UnixStream>,
+}
 
-    // Handle stderr in a separate thread
-    thread::spawn(move || {
-        let reader = BufReader::new(stderr);
-        for line in reader.lines() {
-            match line {
-                Ok(line) => {
-                    warn!("Niri event stream stderr: {}", line);
-                }
-                Err(e) => {
-                    error!("Error reading from niri event stream stderr: {}", e);
-                    break;
-                }
-            }
+impl NiriSocket {
+    /// Connect to the running niri instance
+    pub fn connect() -> Result<Self> {
+        Self::connect_to(&socket_path()?)
+    }
+
+    /// Connect to a niri socket at a specific path
+    pub fn connect_to(path: &Path) -> Result<Self> {
+        let stream = UnixStream::connect(path)
+            .with_context(|| format!("Failed to connect to niri socket {:?}", path))?;
+        let reader = BufReader::new(stream.try_clone()?);
+        Ok(Self { stream, reader })
+    }
+
+    /// Send a request and return the payload of niri's reply
+    pub fn send(&mut self, request: &Request) -> Result<Value> {
+        let mut line = serde_json::to_string(request)?;
+        line.push('\n');
+        self.stream.write_all(line.as_bytes())?;
+
+        let mut reply = String::new();
+        if self.reader.read_line(&mut reply)? == 0 {
+            bail!("niri closed the connection without replying");
         }
-    });
+        parse_reply(&reply)
+    }
 
-    // Handle stdout in a separate thread
-    thread::spawn(move || {
-        let reader = BufReader::new(stdout);
-        for line in reader.lines() {
-            match line {
-                Ok(line) => {
-                    debug!("Raw niri event: {}", line);
-                    
-                    // Parse the JSON event
-                    match serde_json::from_str::<Value>(&line) {
-                        Ok(json) => {
-                            // Convert the JSON value to an event
-                            if let Some(event) = parse_event(json) {
-                                if let Err(e) = tx.send(event) {
-                                    error!("Failed to send event to channel: {}", e);
-                                    break;
+    /// Turn this connection into an event stream, forwarding parsed events
+    /// from a background thread until the stream is shut down
+    pub fn into_event_stream(mut self) -> Result<(mpsc::Receiver<NiriEvent>, EventStreamHandle)> {
+        self.send(&Request::EventStream)?;
+
+        let (tx, rx) = mpsc::channel();
+        let handle = EventStreamHandle {
+            stream: self.stream.try_clone()?,
+        };
+        let reader = self.reader;
+
+        thread::spawn(move || {
+            for line in reader.lines() {
+                match line {
+                    Ok(line) => {
+                        debug!("Raw niri event: {}", line);
+
+                        // Parse the JSON event
+                        match serde_json::from_str::<Value>(&line) {
+                            Ok(json) => {
+                                // Convert the JSON value to an event
+                                if let Some(event) = parse_event(json) {
+                                    if let Err(e) = tx.send(event) {
+                                        error!("Failed to send event to channel: {}", e);
+                                        break;
+                                    }
                                 }
                             }
-                        }
-                        Err(e) => {
-                            error!("Failed to parse JSON from event stream: {}", e);
+                            Err(e) => {
+                                error!("Failed to parse JSON from event stream: {}", e);
+                            }
                         }
                     }
+                    Err(e) => {
+                        error!("Error reading from niri event stream: {}", e);
+                        break;
+                    }
                 }
-                Err(e) => {
-                    error!("Error reading from niri event stream: {}", e);
-                    break;
-                }
+            }
+            info!("Niri event stream thread exited");
+        });
+
+        Ok((rx, handle))
+    }
+}
+
+/// Stops an event stream started with `start_event_stream`
+pub struct EventStreamHandle {
+    stream: UnixStream,
+}
+
+impl EventStreamHandle {
+    /// Close the connection, which ends the reader thread
+    pub fn shutdown(&self) {
+        debug!("Closing niri event stream");
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Parse a reply line: `{"Ok": ...}` or `{"Err": "message"}`
+fn parse_reply(reply: &str) -> Result<Value> {
+    let json: Value = serde_json::from_str(reply.trim())
+        .with_context(|| format!("Invalid reply from niri: {:?}", reply.trim()))?;
+    match json {
+        Value::Object(mut map) => {
+            if let Some(payload) = map.remove("Ok") {
+                Ok(payload)
+            } else if let Some(error) = map.remove("Err") {
+                Err(anyhow!("niri: {}", error.as_str().unwrap_or(&error.to_string())))
+            } else {
+                Err(anyhow!("Unexpected reply from niri: {}", reply.trim()))
             }
         }
-        info!("Niri event stream thread exited");
-    });
+        _ => Err(anyhow!("Unexpected reply from niri: {}", reply.trim())),
+    }
+}
+
+/// Take the payload of a response named `kind`, e.g. `{"Workspaces": [...]}`
+fn response_payload(mut response: Value, kind: &str) -> Result<Value> {
+    response
+        .get_mut(kind)
+        .map(Value::take)
+        .ok_or_else(|| anyhow!("Expected a {} response from niri, got {}", kind, response))
+}
+
+/// Typed queries and actions, each using up the connection
+impl NiriSocket {
+    /// All workspaces, sorted by ID
+    pub fn workspaces(mut self) -> Result<Vec<WorkspaceInfo>> {
+        let payload = response_payload(self.send(&Request::Workspaces)?, "Workspaces")?;
+        let mut workspaces = parse_workspaces(&payload)?;
+        workspaces.sort_by_key(|w| w.id);
+        Ok(workspaces)
+    }
+
+    /// All open windows
+    pub fn windows(mut self) -> Result<Vec<WindowInfo>> {
+        let payload = response_payload(self.send(&Request::Windows)?, "Windows")?;
+        parse_windows(&payload)
+    }
+
+    /// The focused window, if any
+    pub fn focused_window(mut self) -> Result<Option<WindowInfo>> {
+        let payload = response_payload(self.send(&Request::FocusedWindow)?, "FocusedWindow")?;
+        if payload.is_null() {
+            return Ok(None);
+        }
+        parse_window(&payload)
+            .map(Some)
+            .ok_or_else(|| anyhow!("Unexpected window from niri: {}", payload))
+    }
+
+    /// Configured keyboard layouts and the active one
+    pub fn keyboard_layouts(mut self) -> Result<KeyboardLayouts> {
+        let payload = response_payload(self.send(&Request::KeyboardLayouts)?, "KeyboardLayouts")?;
+        parse_keyboard_layouts(&payload)
+            .ok_or_else(|| anyhow!("Unexpected keyboard layouts from niri: {}", payload))
+    }
+
+    /// All connected outputs, sorted by name
+    pub fn outputs(mut self) -> Result<Vec<OutputInfo>> {
+        let payload = response_payload(self.send(&Request::Outputs)?, "Outputs")?;
+        let outputs = payload
+            .as_object()
+            .ok_or_else(|| anyhow!("Unexpected outputs from niri: {}", payload))?;
+
+        let mut parsed: Vec<OutputInfo> = outputs.values().filter_map(parse_output).collect();
+        parsed.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(parsed)
+    }
+
+    /// Ask niri to perform an action
+    pub fn action(mut self, action: Action) -> Result<()> {
+        let reply = self.send(&Request::Action(action.clone()))?;
+        if reply.as_str() != Some("Handled") {
+            warn!("Unexpected reply to {:?}: {}", action, reply);
+        }
+        Ok(())
+    }
+}
+
+/// Connect for a single request, giving up if niri doesn't answer in time
+fn connect_for_request() -> Result<NiriSocket> {
+    let socket = NiriSocket::connect()?;
+    socket.stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    socket.stream.set_write_timeout(Some(REQUEST_TIMEOUT))?;
+    Ok(socket)
+}
+
+/// All workspaces, sorted by ID
+pub fn workspaces() -> Result<Vec<WorkspaceInfo>> {
+    connect_for_request()?.workspaces()
+}
+
+/// All open windows
+pub fn windows() -> Result<Vec<WindowInfo>> {
+    connect_for_request()?.windows()
+}
 
-    Ok((rx, child))
+/// The focused window, if any
+pub fn focused_window() -> Result<Option<WindowInfo>> {
+    connect_for_request()?.focused_window()
 }
 
-/// Parse a JSON value into a NiriEvent
-fn parse_event(json: Value) -> Option<NiriEvent> {
+/// Configured keyboard layouts and the active one
+pub fn keyboard_layouts() -> Result<KeyboardLayouts> {
+    connect_for_request()?.keyboard_layouts()
+}
+
+/// All connected outputs, sorted by name
+pub fn outputs() -> Result<Vec<OutputInfo>> {
+    connect_for_request()?.outputs()
+}
+
+/// Ask niri to perform an action
+pub fn action(action: Action) -> Result<()> {
+    connect_for_request()?.action(action)
+}
+
+/// Start the event stream and return a channel to receive events
+pub fn start_event_stream() -> Result<(mpsc::Receiver<NiriEvent>, EventStreamHandle)> {
+    NiriSocket::connect()?.into_event_stream()
+}
+
+/// Parse a JSON value from the event stream into a NiriEvent. Events the
+/// panel doesn't know become `NiriEvent::Unknown`; known events that don't
+/// have the expected shape are logged and dropped.
+pub fn parse_event(json: Value) -> Option<NiriEvent> {
     // The event stream contains objects with a single key
     // The key is the event type, and the value is the event data
-    if let Some((event_type, event_data)) = json.as_object()?.iter().next() {
-        match event_type.as_str() {
-            "WorkspacesChanged" => parse_workspaces_changed(event_data),
-            "WindowsChanged" => parse_windows_changed(event_data),
-            "WorkspaceActivated" => parse_workspace_activated(event_data),
-            "WindowFocusChanged" => parse_window_focus_changed(event_data),
-            "KeyboardLayoutsChanged" => parse_keyboard_layouts_changed(event_data),
-            "OverviewOpenedOrClosed" => parse_overview_opened_or_closed(event_data),
-            _ => {
-                warn!("Unknown event type: {}", event_type);
-                Some(NiriEvent::Unknown(json.clone()))
-            }
-        }
-    } else {
+    let Some((event_type, event_data)) = json.as_object().and_then(|o| o.iter().next()) else {
         warn!("Unexpected event format: {:?}", json);
-        None
+        return None;
+    };
+
+    let event = match event_type.as_str() {
+        "WorkspacesChanged" => parse_workspaces_changed(event_data),
+        "WorkspaceUrgencyChanged" => parse_workspace_urgency_changed(event_data),
+        "WorkspaceActivated" => parse_workspace_activated(event_data),
+        "WorkspaceActiveWindowChanged" => parse_workspace_active_window_changed(event_data),
+        "WindowsChanged" => parse_windows_changed(event_data),
+        "WindowOpenedOrChanged" => parse_window_opened_or_changed(event_data),
+        "WindowClosed" => parse_window_closed(event_data),
+        "WindowFocusChanged" => parse_window_focus_changed(event_data),
+        "WindowUrgencyChanged" => parse_window_urgency_changed(event_data),
+        "WindowLayoutsChanged" => parse_window_layouts_changed(event_data),
+        "KeyboardLayoutsChanged" => parse_keyboard_layouts_changed(event_data),
+        "KeyboardLayoutSwitched" => parse_keyboard_layout_switched(event_data),
+        "OverviewOpenedOrClosed" => parse_overview_opened_or_closed(event_data),
+        "ConfigLoaded" => parse_config_loaded(event_data),
+        _ => {
+            debug!("Unknown event type: {}", event_type);
+            return Some(NiriEvent::Unknown(json.clone()));
+        }
+    };
+
+    if event.is_none() {
+        // Most likely niri changed the shape of this event
+        warn!("Failed to parse niri {} event: {}", event_type, event_data);
     }
+    event
 }
 
 fn parse_workspaces_changed(data: &Value) -> Option<NiriEvent> {
-    let workspaces = data.get("workspaces")?.as_array()?;
-    let mut parsed_workspaces = Vec::with_capacity(workspaces.len());
-
-    for workspace in workspaces {
-        parsed_workspaces.push(WorkspaceInfo {
-            id: workspace.get("id")?.as_u64()?,
-            idx: workspace.get("idx")?.as_u64()? as u32,
-            name: workspace.get("name").and_then(|n| n.as_str()).map(String::from),
-            output: workspace.get("output")?.as_str()?.to_string(),
-            is_urgent: workspace.get("is_urgent")?.as_bool()?,
-            is_active: workspace.get("is_active")?.as_bool()?,
-            is_focused: workspace.get("is_focused")?.as_bool()?,
-            active_window_id: workspace.get("active_window_id").and_then(|w| w.as_u64()),
-        });
-    }
+    let workspaces = parse_workspaces(data.get("workspaces")?).ok()?;
+    Some(NiriEvent::WorkspacesChanged { workspaces })
+}
+
+fn parse_windows_changed(data: &Value) -> Option<NiriEvent> {
+    let windows = parse_windows(data.get("windows")?).ok()?;
+    Some(NiriEvent::WindowsChanged { windows })
+}
+
+/// Parse an array of workspaces, as sent by the Workspaces request and event
+fn parse_workspaces(data: &Value) -> Result<Vec<WorkspaceInfo>> {
+    data.as_array()
+        .ok_or_else(|| anyhow!("Expected an array of workspaces, got {}", data))?
+        .iter()
+        .map(|workspace| {
+            parse_workspace(workspace)
+                .ok_or_else(|| anyhow!("Unexpected workspace from niri: {}", workspace))
+        })
+        .collect()
+}
 
-    Some(NiriEvent::WorkspacesChanged {
-        workspaces: parsed_workspaces,
+fn parse_workspace(workspace: &Value) -> Option<WorkspaceInfo> {
+    Some(WorkspaceInfo {
+        id: workspace.get("id")?.as_u64()?,
+        idx: workspace.get("idx")?.as_u64()? as u32,
+        name: workspace.get("name").and_then(|n| n.as_str()).map(String::from),
+        // Workspaces are not on any output while no monitor is connected
+        output: workspace
+            .get("output")
+            .and_then(|o| o.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        is_urgent: workspace.get("is_urgent")?.as_bool()?,
+        is_active: workspace.get("is_active")?.as_bool()?,
+        is_focused: workspace.get("is_focused")?.as_bool()?,
+        active_window_id: workspace.get("active_window_id").and_then(|w| w.as_u64()),
     })
 }
 
-fn parse_windows_changed(data: &Value) -> Option<NiriEvent> {
-    let windows = data.get("windows")?.as_array()?;
-    let mut parsed_windows = Vec::with_capacity(windows.len());
-
-    for window in windows {
-        parsed_windows.push(WindowInfo {
-            id: window.get("id")?.as_u64()?,
-            title: window.get("title")?.as_str()?.to_string(),
-            app_id: window
-                .get("app_id")
-                .and_then(|a| a.as_str())
-                .map(String::from),
-            pid: window.get("pid")?.as_u64()?,
-            workspace_id: window.get("workspace_id")?.as_u64()?,
-            is_focused: window.get("is_focused")?.as_bool()?,
-            is_floating: window.get("is_floating")?.as_bool()?,
-            is_urgent: window.get("is_urgent")?.as_bool()?,
-        });
-    }
+/// Parse an array of windows, as sent by the Windows request and event
+fn parse_windows(data: &Value) -> Result<Vec<WindowInfo>> {
+    data.as_array()
+        .ok_or_else(|| anyhow!("Expected an array of windows, got {}", data))?
+        .iter()
+        .map(|window| {
+            parse_window(window).ok_or_else(|| anyhow!("Unexpected window from niri: {}", window))
+        })
+        .collect()
+}
 
-    Some(NiriEvent::WindowsChanged {
-        windows: parsed_windows,
+fn parse_window(window: &Value) -> Option<WindowInfo> {
+    Some(WindowInfo {
+        id: window.get("id")?.as_u64()?,
+        title: window
+            .get("title")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .to_string(),
+        app_id: window
+            .get("app_id")
+            .and_then(|a| a.as_str())
+            .map(String::from),
+        // niri doesn't know the PID of some clients
+        pid: window.get("pid").and_then(|p| p.as_u64()).unwrap_or(0),
+        workspace_id: window.get("workspace_id").and_then(|w| w.as_u64()),
+        is_focused: window.get("is_focused")?.as_bool()?,
+        is_floating: window.get("is_floating")?.as_bool()?,
+        is_urgent: window
+            .get("is_urgent")
+            .and_then(|u| u.as_bool())
+            .unwrap_or(false),
+        tile_size: window.get("layout").and_then(parse_tile_size),
+    })
+}
+
+/// Tile size from a window layout, `{"tile_size": [w, h], ...}`
+fn parse_tile_size(layout: &Value) -> Option<(f64, f64)> {
+    let size = layout.get("tile_size")?.as_array()?;
+    Some((size.first()?.as_f64()?, size.get(1)?.as_f64()?))
+}
+
+fn parse_output(output: &Value) -> Option<OutputInfo> {
+    let logical = output.get("logical").and_then(|logical| {
+        Some(LogicalOutput {
+            x: logical.get("x")?.as_i64()? as i32,
+            y: logical.get("y")?.as_i64()? as i32,
+            width: logical.get("width")?.as_u64()? as u32,
+            height: logical.get("height")?.as_u64()? as u32,
+            scale: logical.get("scale")?.as_f64()?,
+        })
+    });
+
+    Some(OutputInfo {
+        name: output.get("name")?.as_str()?.to_string(),
+        make: output.get("make").and_then(|m| m.as_str()).unwrap_or_default().to_string(),
+        model: output.get("model").and_then(|m| m.as_str()).unwrap_or_default().to_string(),
+        logical,
     })
 }
 
@@ -210,24 +547,84 @@ fn parse_workspace_activated(data: &Value) -> Option<NiriEvent> {
     })
 }
 
+fn parse_workspace_urgency_changed(data: &Value) -> Option<NiriEvent> {
+    Some(NiriEvent::WorkspaceUrgencyChanged {
+        id: data.get("id")?.as_u64()?,
+        urgent: data.get("urgent")?.as_bool()?,
+    })
+}
+
+fn parse_workspace_active_window_changed(data: &Value) -> Option<NiriEvent> {
+    Some(NiriEvent::WorkspaceActiveWindowChanged {
+        workspace_id: data.get("workspace_id")?.as_u64()?,
+        active_window_id: data.get("active_window_id").and_then(|w| w.as_u64()),
+    })
+}
+
+fn parse_window_opened_or_changed(data: &Value) -> Option<NiriEvent> {
+    Some(NiriEvent::WindowOpenedOrChanged {
+        window: parse_window(data.get("window")?)?,
+    })
+}
+
+fn parse_window_closed(data: &Value) -> Option<NiriEvent> {
+    Some(NiriEvent::WindowClosed {
+        id: data.get("id")?.as_u64()?,
+    })
+}
+
 fn parse_window_focus_changed(data: &Value) -> Option<NiriEvent> {
+    // The ID is null when no window is focused
     Some(NiriEvent::WindowFocusChanged {
+        id: data.get("id").and_then(|id| id.as_u64()),
+    })
+}
+
+fn parse_window_urgency_changed(data: &Value) -> Option<NiriEvent> {
+    Some(NiriEvent::WindowUrgencyChanged {
         id: data.get("id")?.as_u64()?,
+        urgent: data.get("urgent")?.as_bool()?,
     })
 }
 
+fn parse_window_layouts_changed(data: &Value) -> Option<NiriEvent> {
+    // `changes` is a list of `[window_id, layout]` pairs; a layout without
+    // a tile size only skips that window
+    let tile_sizes = data
+        .get("changes")?
+        .as_array()?
+        .iter()
+        .filter_map(|change| {
+            let id = change.get(0)?.as_u64()?;
+            Some((id, parse_tile_size(change.get(1)?)?))
+        })
+        .collect();
+    Some(NiriEvent::WindowLayoutsChanged { tile_sizes })
+}
+
 fn parse_keyboard_layouts_changed(data: &Value) -> Option<NiriEvent> {
-    let keyboard_layouts = data.get("keyboard_layouts")?;
-    let names = keyboard_layouts
+    Some(NiriEvent::KeyboardLayoutsChanged {
+        keyboard_layouts: parse_keyboard_layouts(data.get("keyboard_layouts")?)?,
+    })
+}
+
+fn parse_keyboard_layout_switched(data: &Value) -> Option<NiriEvent> {
+    Some(NiriEvent::KeyboardLayoutSwitched {
+        idx: data.get("idx")?.as_u64()? as usize,
+    })
+}
+
+fn parse_keyboard_layouts(data: &Value) -> Option<KeyboardLayouts> {
+    let names = data
         .get("names")?
         .as_array()?
         .iter()
         .filter_map(|n| n.as_str().map(String::from))
         .collect();
-    
-    Some(NiriEvent::KeyboardLayoutsChanged {
+
+    Some(KeyboardLayouts {
         names,
-        current_idx: keyboard_layouts.get("current_idx")?.as_u64()? as usize,
+        current_idx: data.get("current_idx")?.as_u64()? as usize,
     })
 }
 
@@ -237,29 +634,46 @@ fn parse_overview_opened_or_closed(data: &Value) -> Option<NiriEvent> {
     })
 }
 
-/// Attach an event stream to the GLib main context and provide a callback
+fn parse_config_loaded(data: &Value) -> Option<NiriEvent> {
+    Some(NiriEvent::ConfigLoaded {
+        failed: data.get("failed").and_then(|f| f.as_bool()).unwrap_or(false),
+    })
+}
+
+/// Attach an event stream from `start_event_stream` to the GLib main
+/// context and provide a callback. `on_closed` runs once the stream ends,
+/// whether niri went away or the stream was shut down.
 pub fn attach_event_stream(
-    callback: impl Fn(NiriEvent) + 'static,
-) -> Result<(glib::SourceId, Child)> {
-    let (rx, child) = start_event_stream()?;
-    
+    rx: mpsc::Receiver<NiriEvent>,
+    on_event: impl Fn(NiriEvent) + 'static,
+    on_closed: impl FnOnce() + 'static,
+) -> glib::SourceId {
     // Create a channel to forward events to the GLib main context
     let (sender, receiver) = glib::MainContext::channel(glib::Priority::DEFAULT);
     
-    // Spawn a thread to receive events and forward them to the GLib main context
+    // Spawn a thread to receive events and forward them to the GLib main
+    // context, followed by `None` when the stream ends
     thread::spawn(move || {
         while let Ok(event) = rx.recv() {
-            if sender.send(event).is_err() {
-                break;
+            if sender.send(Some(event)).is_err() {
+                return;
             }
         }
+        let _ = sender.send(None);
     });
     
     // Attach the receiver to the GLib main context
-    let source_id = receiver.attach(None, move |event| {
-        callback(event);
-        glib::ControlFlow::Continue
-    });
-    
-    Ok((source_id, child))
-}
\ No newline at end of file
+    let mut on_closed = Some(on_closed);
+    receiver.attach(None, move |event| match event {
+        Some(event) => {
+            on_event(event);
+            glib::ControlFlow::Continue
+        }
+        None => {
+            if let Some(on_closed) = on_closed.take() {
+                on_closed();
+            }
+            glib::ControlFlow::Break
+        }
+    })
+}
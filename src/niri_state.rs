@@ -0,0 +1,495 @@
+use anyhow::{anyhow, Result};
+use gtk4::{gio, glib};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::niri_ipc::{
+    self, EventStreamHandle, KeyboardLayouts, NiriEvent, OutputInfo, WindowInfo, WorkspaceInfo,
+};
+use crate::widgets::Lifecycle;
+
+/// niri's workspaces, windows, outputs and keyboard layouts, kept up to
+/// date by applying events from the event stream
+#[derive(Debug, Clone, Default)]
+pub struct NiriState {
+    // Sorted by ID
+    workspaces: Vec<WorkspaceInfo>,
+    windows: BTreeMap<u64, WindowInfo>,
+    // Sorted by name
+    outputs: Vec<OutputInfo>,
+    keyboard_layouts: Option<KeyboardLayouts>,
+    overview_open: bool,
+    connected: bool,
+}
+
+/// First delay before reconnecting to niri; doubled after each failure
+const RECONNECT_DELAY_MIN: Duration = Duration::from_secs(1);
+const RECONNECT_DELAY_MAX: Duration = Duration::from_secs(30);
+
+impl NiriState {
+    /// Query niri for its current state
+    pub fn snapshot() -> Result<Self> {
+        let mut state = Self::default();
+        state.apply(&NiriEvent::WorkspacesChanged {
+            workspaces: niri_ipc::workspaces()?,
+        });
+        state.apply(&NiriEvent::WindowsChanged {
+            windows: niri_ipc::windows()?,
+        });
+        match niri_ipc::outputs() {
+            Ok(outputs) => state.apply(&NiriEvent::OutputsChanged { outputs }),
+            Err(e) => warn!("Could not get outputs from niri: {:#}", e),
+        }
+        match niri_ipc::keyboard_layouts() {
+            Ok(keyboard_layouts) => {
+                state.apply(&NiriEvent::KeyboardLayoutsChanged { keyboard_layouts })
+            }
+            Err(e) => warn!("Could not get keyboard layouts from niri: {:#}", e),
+        }
+        Ok(state)
+    }
+
+    /// Update the state with an event from the event stream
+    pub fn apply(&mut self, event: &NiriEvent) {
+        match event {
+            NiriEvent::WorkspacesChanged { workspaces } => {
+                self.workspaces = workspaces.clone();
+                self.workspaces.sort_by_key(|w| w.id);
+            }
+            NiriEvent::WorkspaceUrgencyChanged { id, urgent } => {
+                if let Some(workspace) = self.workspace_mut(*id) {
+                    workspace.is_urgent = *urgent;
+                }
+            }
+            NiriEvent::WorkspaceActivated { id, focused } => {
+                let activated_output = self.workspace(*id).map(|w| w.output.clone());
+                for workspace in &mut self.workspaces {
+                    // Only one workspace is active per output
+                    if Some(&workspace.output) == activated_output.as_ref() {
+                        workspace.is_active = workspace.id == *id;
+                    }
+                    if workspace.id == *id {
+                        workspace.is_focused = *focused;
+                    } else if *focused {
+                        workspace.is_focused = false;
+                    }
+                }
+            }
+            NiriEvent::WorkspaceActiveWindowChanged {
+                workspace_id,
+                active_window_id,
+            } => {
+                if let Some(workspace) = self.workspace_mut(*workspace_id) {
+                    workspace.active_window_id = *active_window_id;
+                }
+            }
+            NiriEvent::WindowsChanged { windows } => {
+                self.windows = windows.iter().map(|w| (w.id, w.clone())).collect();
+            }
+            NiriEvent::WindowOpenedOrChanged { window } => {
+                // A newly focused window takes focus from the others
+                if window.is_focused {
+                    for other in self.windows.values_mut() {
+                        other.is_focused = false;
+                    }
+                }
+                self.windows.insert(window.id, window.clone());
+            }
+            NiriEvent::WindowClosed { id } => {
+                self.windows.remove(id);
+            }
+            NiriEvent::WindowFocusChanged { id } => {
+                for window in self.windows.values_mut() {
+                    window.is_focused = Some(window.id) == *id;
+                }
+            }
+            NiriEvent::WindowUrgencyChanged { id, urgent } => {
+                if let Some(window) = self.windows.get_mut(id) {
+                    window.is_urgent = *urgent;
+                }
+            }
+            NiriEvent::WindowLayoutsChanged { tile_sizes } => {
+                for (id, tile_size) in tile_sizes {
+                    if let Some(window) = self.windows.get_mut(id) {
+                        window.tile_size = Some(*tile_size);
+                    }
+                }
+            }
+            NiriEvent::KeyboardLayoutsChanged { keyboard_layouts } => {
+                self.keyboard_layouts = Some(keyboard_layouts.clone());
+            }
+            NiriEvent::KeyboardLayoutSwitched { idx } => {
+                if let Some(keyboard_layouts) = &mut self.keyboard_layouts {
+                    keyboard_layouts.current_idx = *idx;
+                }
+            }
+            NiriEvent::OverviewOpenedOrClosed { is_open } => {
+                self.overview_open = *is_open;
+            }
+            NiriEvent::ConnectionChanged { connected } => {
+                self.connected = *connected;
+            }
+            NiriEvent::OutputsChanged { outputs } => {
+                self.outputs = outputs.clone();
+                self.outputs.sort_by(|a, b| a.name.cmp(&b.name));
+            }
+            NiriEvent::ConfigLoaded { .. } | NiriEvent::Unknown(_) => {}
+        }
+    }
+
+    /// All workspaces, sorted by ID
+    pub fn workspaces(&self) -> &[WorkspaceInfo] {
+        &self.workspaces
+    }
+
+    /// Workspaces on an output (all outputs if `None`), sorted by ID
+    pub fn workspaces_on_output(&self, output: Option<&str>) -> Vec<WorkspaceInfo> {
+        self.workspaces
+            .iter()
+            .filter(|w| output.map_or(true, |output| w.output == output))
+            .cloned()
+            .collect()
+    }
+
+    pub fn workspace(&self, id: u64) -> Option<&WorkspaceInfo> {
+        self.workspaces.iter().find(|w| w.id == id)
+    }
+
+    fn workspace_mut(&mut self, id: u64) -> Option<&mut WorkspaceInfo> {
+        self.workspaces.iter_mut().find(|w| w.id == id)
+    }
+
+    /// All windows, sorted by ID
+    pub fn windows(&self) -> impl Iterator<Item = &WindowInfo> {
+        self.windows.values()
+    }
+
+    pub fn window(&self, id: u64) -> Option<&WindowInfo> {
+        self.windows.get(&id)
+    }
+
+    /// Windows on a workspace, sorted by ID
+    pub fn windows_on_workspace(&self, workspace_id: u64) -> Vec<WindowInfo> {
+        self.windows
+            .values()
+            .filter(|w| w.workspace_id == Some(workspace_id))
+            .cloned()
+            .collect()
+    }
+
+    pub fn focused_window(&self) -> Option<&WindowInfo> {
+        self.windows.values().find(|w| w.is_focused)
+    }
+
+    /// The active window of the active workspace on an output, or the
+    /// focused window if `output` is `None`
+    pub fn active_window_on_output(&self, output: Option<&str>) -> Option<&WindowInfo> {
+        let Some(output) = output else {
+            return self.focused_window();
+        };
+        self.workspaces
+            .iter()
+            .find(|w| w.output == output && w.is_active)
+            .and_then(|w| w.active_window_id)
+            .and_then(|id| self.window(id))
+    }
+
+    /// The output of the focused workspace
+    pub fn focused_output(&self) -> Option<&str> {
+        self.workspaces
+            .iter()
+            .find(|w| w.is_focused)
+            .map(|w| w.output.as_str())
+    }
+
+    /// An output by connector name, e.g. "DP-1"
+    pub fn output(&self, name: &str) -> Option<&OutputInfo> {
+        self.outputs.iter().find(|o| o.name == name)
+    }
+
+    pub fn keyboard_layouts(&self) -> Option<&KeyboardLayouts> {
+        self.keyboard_layouts.as_ref()
+    }
+
+    pub fn is_overview_open(&self) -> bool {
+        self.overview_open
+    }
+
+    /// Whether the event stream is connected, so the state is current
+    pub fn is_connected(&self) -> bool {
+        self.connected
+    }
+}
+
+type Subscriber = Rc<dyn Fn(&NiriState, &NiriEvent)>;
+
+/// The state shared by every widget, and the event stream feeding it. The
+/// stream runs while at least one widget is subscribed, and is reconnected
+/// with backoff when niri goes away.
+#[derive(Default)]
+struct SharedState {
+    // Shared with the subscribers of the event being dispatched; copied
+    // only if it changes while one of them still holds it
+    state: RefCell<Rc<NiriState>>,
+    // Subscribers, and whether each one keeps the stream running
+    subscribers: RefCell<Vec<(u64, bool, Subscriber)>>,
+    next_id: Cell<u64>,
+    // Whether widgets are subscribed and the stream should be running
+    running: Cell<bool>,
+    stream: RefCell<Option<EventStreamHandle>>,
+    // Bumped on every connection attempt, so a stale stream's close is ignored
+    generation: Cell<u64>,
+    reconnect: RefCell<Option<glib::SourceId>>,
+    reconnect_delay: Cell<Duration>,
+}
+
+thread_local! {
+    static SHARED: SharedState = SharedState::default();
+}
+
+/// Call `callback` after each event is applied to the shared state, until
+/// the widget is shut down. The first subscriber connects to niri.
+pub fn subscribe(lifecycle: &Lifecycle, callback: impl Fn(&NiriState, &NiriEvent) + 'static) {
+    add_subscriber(lifecycle, true, Rc::new(callback));
+
+    if !SHARED.with(|shared| shared.running.replace(true)) {
+        SHARED.with(|shared| shared.reconnect_delay.set(RECONNECT_DELAY_MIN));
+        connect();
+    }
+}
+
+/// Like `subscribe`, but without connecting to niri if no widget needs it
+pub fn watch(lifecycle: &Lifecycle, callback: impl Fn(&NiriState, &NiriEvent) + 'static) {
+    add_subscriber(lifecycle, false, Rc::new(callback));
+}
+
+fn add_subscriber(lifecycle: &Lifecycle, keeps_alive: bool, callback: Subscriber) {
+    let id = SHARED.with(|shared| {
+        let id = shared.next_id.get();
+        shared.next_id.set(id + 1);
+        shared.subscribers.borrow_mut().push((id, keeps_alive, callback));
+        id
+    });
+
+    lifecycle.on_shutdown(move || {
+        let last = SHARED.with(|shared| {
+            let mut subscribers = shared.subscribers.borrow_mut();
+            subscribers.retain(|(subscriber_id, _, _)| *subscriber_id != id);
+            shared.running.get() && !subscribers.iter().any(|(_, keeps_alive, _)| *keeps_alive)
+        });
+        if last {
+            stop();
+        }
+    });
+}
+
+/// Read the shared state
+pub fn with<R>(f: impl FnOnce(&NiriState) -> R) -> R {
+    SHARED.with(|shared| f(&shared.state.borrow()))
+}
+
+/// Whether the event stream is connected, or `None` when no widget follows niri
+pub fn is_connected() -> Option<bool> {
+    SHARED.with(|shared| {
+        shared
+            .running
+            .get()
+            .then(|| shared.state.borrow().is_connected())
+    })
+}
+
+/// A snapshot and the event stream following it, opened off the main thread
+type Connection = (NiriState, mpsc::Receiver<NiriEvent>, EventStreamHandle);
+
+/// Load a snapshot and start following the event stream, retrying later on
+/// failure. The queries block on niri, so they run on a thread.
+fn connect() {
+    let generation = SHARED.with(|shared| {
+        shared.generation.set(shared.generation.get() + 1);
+        shared.generation.get()
+    });
+
+    let connecting = gio::spawn_blocking(|| {
+        NiriState::snapshot().and_then(|state| {
+            let (rx, stream) = niri_ipc::start_event_stream()?;
+            Ok((state, rx, stream))
+        })
+    });
+    glib::MainContext::default().spawn_local(async move {
+        let connection = connecting
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("Connecting to niri panicked")));
+        on_connected(generation, connection);
+    });
+}
+
+fn on_connected(generation: u64, connection: Result<Connection>) {
+    // Stopped, or superseded by a newer attempt, while connecting
+    let current = SHARED.with(|shared| {
+        shared.running.get() && shared.generation.get() == generation
+    });
+    if !current {
+        if let Ok((_, _, stream)) = connection {
+            stream.shutdown();
+        }
+        return;
+    }
+
+    match connection {
+        Ok((state, rx, stream)) => {
+            info!("Following the niri event stream");
+            niri_ipc::attach_event_stream(rx, on_event, move || on_closed(generation));
+            SHARED.with(|shared| {
+                *shared.state.borrow_mut() = Rc::new(state);
+                *shared.stream.borrow_mut() = Some(stream);
+                shared.reconnect_delay.set(RECONNECT_DELAY_MIN);
+            });
+            // Subscribers redraw from the fresh snapshot
+            dispatch(NiriEvent::ConnectionChanged { connected: true });
+        }
+        Err(e) => {
+            warn!("Could not connect to niri: {:#}", e);
+            set_disconnected();
+            schedule_reconnect();
+        }
+    }
+}
+
+fn on_event(event: NiriEvent) {
+    // Outputs come and go with their workspaces, and change mode or scale
+    // on a config reload
+    let outputs_may_change = matches!(
+        event,
+        NiriEvent::WorkspacesChanged { .. } | NiriEvent::ConfigLoaded { .. }
+    );
+    dispatch(event);
+    if outputs_may_change {
+        refresh_outputs();
+    }
+}
+
+/// Re-read niri's outputs, e.g. after a monitor changed mode or scale. The
+/// query blocks on niri, so it runs on a thread.
+pub fn refresh_outputs() {
+    let (generation, connected) = SHARED.with(|shared| {
+        (shared.generation.get(), shared.state.borrow().is_connected())
+    });
+    if !connected {
+        return;
+    }
+
+    let reading = gio::spawn_blocking(niri_ipc::outputs);
+    glib::MainContext::default().spawn_local(async move {
+        let outputs = match reading.await {
+            Ok(Ok(outputs)) => outputs,
+            Ok(Err(e)) => {
+                warn!("Could not get outputs from niri: {:#}", e);
+                return;
+            }
+            Err(_) => return,
+        };
+        // Dropped if the stream was lost or replaced while reading
+        let current = SHARED.with(|shared| {
+            shared.running.get()
+                && shared.generation.get() == generation
+                && shared.state.borrow().is_connected()
+        });
+        if current {
+            dispatch(NiriEvent::OutputsChanged { outputs });
+        }
+    });
+}
+
+/// The event stream ended; reconnect unless it was closed on purpose
+fn on_closed(generation: u64) {
+    let current = SHARED.with(|shared| {
+        shared.running.get() && shared.generation.get() == generation
+    });
+    if !current {
+        return;
+    }
+
+    warn!("Lost connection to the niri event stream");
+    SHARED.with(|shared| shared.stream.borrow_mut().take());
+    set_disconnected();
+    schedule_reconnect();
+}
+
+fn set_disconnected() {
+    if with(|state| state.is_connected()) {
+        dispatch(NiriEvent::ConnectionChanged { connected: false });
+    }
+}
+
+fn schedule_reconnect() {
+    let delay = SHARED.with(|shared| {
+        let delay = shared.reconnect_delay.get();
+        shared
+            .reconnect_delay
+            .set((delay * 2).min(RECONNECT_DELAY_MAX));
+        delay
+    });
+
+    info!("Reconnecting to niri in {:?}", delay);
+    let source_id = glib::timeout_add_local_once(delay, || {
+        SHARED.with(|shared| shared.reconnect.borrow_mut().take());
+        if SHARED.with(|shared| shared.running.get()) {
+            connect();
+        }
+    });
+    if let Some(previous) = SHARED.with(|shared| shared.reconnect.borrow_mut().replace(source_id)) {
+        previous.remove();
+    }
+}
+
+/// Close the event stream once no widget needs it
+fn stop() {
+    SHARED.with(|shared| {
+        shared.running.set(false);
+        shared.generation.set(shared.generation.get() + 1);
+        if let Some(source_id) = shared.reconnect.borrow_mut().take() {
+            source_id.remove();
+        }
+        if let Some(stream) = shared.stream.borrow_mut().take() {
+            stream.shutdown();
+        }
+        *shared.state.borrow_mut() = Rc::default();
+    });
+
+    // Let watchers know nothing is following niri any more
+    dispatch(NiriEvent::ConnectionChanged { connected: false });
+}
+
+fn dispatch(event: NiriEvent) {
+    debug!("Applying niri event: {:?}", event);
+    let (state, subscribers) = SHARED.with(|shared| {
+        Rc::make_mut(&mut shared.state.borrow_mut()).apply(&event);
+        let subscribers: Vec<(u64, Subscriber)> = shared
+            .subscribers
+            .borrow()
+            .iter()
+            .map(|(id, _, subscriber)| (*id, subscriber.clone()))
+            .collect();
+        (Rc::clone(&shared.state.borrow()), subscribers)
+    });
+
+    // Subscribers hold on to this event's state: one may shut down the last
+    // widget following niri, which replaces the shared state with an empty
+    // one while the others still run
+    for (id, subscriber) in &subscribers {
+        let subscribed = SHARED.with(|shared| {
+            shared
+                .subscribers
+                .borrow()
+                .iter()
+                .any(|(subscriber_id, _, _)| subscriber_id == id)
+        });
+        if subscribed {
+            subscriber(&state, &event);
+        }
+    }
+}
@@ -4,173 +4,290 @@ use gtk4::prelude::*;
 use gtk4::{ApplicationWindow, Box, Orientation};
 use std::cell::RefCell;
 use std::rc::Rc;
+use tracing::warn;
 
-use crate::config::PanelConfig;
+use crate::config::{PanelConfig, PanelPosition};
+use crate::niri_ipc;
+use crate::niri_state;
 use crate::popover_registry::PopoverRegistry;
 use crate::widgets::{
-    Battery, Bluetooth, Clock, Git, Launcher, Network, Overview, Places, Power, Search, Secrets,
-    Servers, Sound, Widget, Workspaces,
+    Battery, Bluetooth, Clock, Custom, Git, KeyboardLayout, Launcher, Lifecycle, Network, Overview,
+    Places, Plugin, Power, Search,
+    Secrets, Servers, Sound, StatusIndicator, Taskbar, Widget, WindowTitle, Workspaces,
 };
 
 pub struct Panel {
     container: Box,
+    // Widgets by layout name, kept for shutdown
+    widgets: Vec<(String, Rc<dyn Widget>)>,
+    config_indicator: StatusIndicator,
+    // Stops watching the niri connection
+    lifecycle: Lifecycle,
     _config: PanelConfig,
 }
 
 impl Panel {
     pub fn new(
         config: PanelConfig,
+        output: Option<String>,
         window_weak: WeakRef<ApplicationWindow>,
         active_popovers: Rc<RefCell<i32>>,
     ) -> Result<Self> {
-        let container = Box::new(Orientation::Horizontal, 0);
+        // Left and right panels stack their sections top to bottom
+        let vertical = config.position.is_vertical();
+        let orientation = if vertical {
+            Orientation::Vertical
+        } else {
+            Orientation::Horizontal
+        };
+
+        let container = Box::new(orientation, 0);
         container.add_css_class("panel");
-        container.set_margin_top(0);
-        container.set_margin_bottom(0);
-        container.set_margin_start(5);
-        container.set_margin_end(0);
+        if vertical {
+            container.add_css_class("vertical");
+            container.set_margin_top(5);
+        } else {
+            container.set_margin_start(5);
+        }
 
-        // Create left box for launcher and workspaces
-        let left_box = Box::new(Orientation::Horizontal, 10);
+        // Create left box for launcher and workspaces (top box when vertical)
+        let left_box = Box::new(orientation, 10);
         left_box.add_css_class("panel-left");
-        left_box.set_halign(gtk4::Align::Start);
-        left_box.set_hexpand(true);
 
-        // Create center box (empty for now, can add window title later)
-        let center_box = Box::new(Orientation::Horizontal, 10);
+        // Create center box
+        let center_box = Box::new(orientation, 10);
         center_box.add_css_class("panel-center");
-        center_box.set_halign(gtk4::Align::Center);
-        center_box.set_hexpand(false);
 
-        // Create right box for clock and system tray
-        let right_box = Box::new(Orientation::Horizontal, 10);
+        // Create right box for clock and system tray (bottom box when vertical)
+        let right_box = Box::new(orientation, 10);
         right_box.add_css_class("panel-right");
-        right_box.set_halign(gtk4::Align::End);
-        right_box.set_hexpand(true);
 
-        // Add widgets with keyboard mode management where needed
-        let overview = Overview::new()?;
-        left_box.append(overview.widget());
-
-        if config.show_workspaces {
-            let workspaces = Workspaces::new()?;
-            left_box.append(workspaces.widget());
-        }
-        
-        // Get the global popover registry
-        let registry = PopoverRegistry::global();
-        
-        if config.show_launcher {
-            let launcher = Launcher::new(window_weak.clone(), active_popovers.clone())?;
-            left_box.append(launcher.widget());
-            // Register launcher popover
-            if let Some(popover) = launcher.popover() {
-                let _ = registry.register("launcher", popover.clone());
-            }
+        if vertical {
+            left_box.set_valign(gtk4::Align::Start);
+            left_box.set_vexpand(true);
+            center_box.set_valign(gtk4::Align::Center);
+            center_box.set_vexpand(false);
+            right_box.set_valign(gtk4::Align::End);
+            right_box.set_vexpand(true);
+        } else {
+            left_box.set_halign(gtk4::Align::Start);
+            left_box.set_hexpand(true);
+            center_box.set_halign(gtk4::Align::Center);
+            center_box.set_hexpand(false);
+            right_box.set_halign(gtk4::Align::End);
+            right_box.set_hexpand(true);
         }
 
-        if config.show_places {
-            let places = Places::new(window_weak.clone(), active_popovers.clone())?;
-            left_box.append(places.widget());
-            if let Some(popover) = places.popover() {
-                let _ = registry.register("places", popover.clone());
-            }
-        }
-        
-        if config.show_servers {
-            let servers = Servers::new(window_weak.clone(), active_popovers.clone())?;
-            left_box.append(servers.widget());
-            if let Some(popover) = servers.popover() {
-                let _ = registry.register("servers", popover.clone());
+        // Add widgets in the configured order for each section
+        let layout = config.layout();
+        let mut widgets = Vec::new();
+        for (section, names) in [
+            (&left_box, &layout.left),
+            (&center_box, &layout.center),
+            (&right_box, &layout.right),
+        ] {
+            for name in names {
+                match Self::build_widget(
+                    name,
+                    &config,
+                    output.clone(),
+                    window_weak.clone(),
+                    active_popovers.clone(),
+                ) {
+                    Ok(Some((root, widget))) => {
+                        section.append(&root);
+                        widgets.push((name.clone(), widget));
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        // Don't leave the widgets built so far running
+                        Self::shutdown_widgets(&widgets);
+                        return Err(e);
+                    }
+                }
             }
         }
 
-        if config.show_search {
-            let search = Search::new(window_weak.clone(), active_popovers.clone())?;
-            left_box.append(search.widget());
-            if let Some(popover) = search.popover() {
-                let _ = registry.register("search", popover.clone());
-            }
-        }
+        // Hidden until a config reload fails
+        let config_indicator = StatusIndicator::new("config-error", "Configuration error");
+        right_box.append(config_indicator.widget());
 
-        if config.show_git {
-            let git = Git::new(window_weak.clone(), active_popovers.clone(), &config)?;
-            left_box.append(git.widget());
-            if let Some(popover) = git.popover() {
-                let _ = registry.register("git", popover.clone());
+        // Hidden unless widgets follow niri and the connection is lost
+        let lifecycle = Lifecycle::new();
+        let niri_indicator = StatusIndicator::new("niri-disconnected", "Disconnected from niri");
+        right_box.append(niri_indicator.widget());
+        Self::update_niri_indicator(&niri_indicator);
+        niri_state::watch(&lifecycle, move |_, event| {
+            if let niri_ipc::NiriEvent::ConnectionChanged { .. } = event {
+                Self::update_niri_indicator(&niri_indicator);
             }
-        }
+        });
 
-        if config.show_secrets {
-            let secrets = Secrets::new(window_weak.clone(), active_popovers.clone())?;
-            left_box.append(secrets.widget());
-            if let Some(popover) = secrets.popover() {
-                let _ = registry.register("secrets", popover.clone());
-            }
-        }
+        // Pack everything
+        container.append(&left_box);
+        container.append(&center_box);
+        container.append(&right_box);
 
-        if config.show_sound {
-            let sound = Sound::new(window_weak.clone(), active_popovers.clone())?;
-            right_box.append(sound.widget());
-            // Register sound popover
-            if let Some(popover) = sound.popover() {
-                let _ = registry.register("sound", popover.clone());
-            }
-        }
+        Ok(Self {
+            container,
+            widgets,
+            config_indicator,
+            lifecycle,
+            _config: config,
+        })
+    }
 
-        if config.show_bluetooth {
-            let bluetooth = Bluetooth::new(window_weak.clone(), active_popovers.clone())?;
-            right_box.append(bluetooth.widget());
-            if let Some(popover) = bluetooth.popover() {
-                let _ = registry.register("bluetooth", popover.clone());
+    /// Build a widget by its layout name and register its popover, if any.
+    /// Unknown names are logged and skipped.
+    fn build_widget(
+        name: &str,
+        config: &PanelConfig,
+        output: Option<String>,
+        window_weak: WeakRef<ApplicationWindow>,
+        active_popovers: Rc<RefCell<i32>>,
+    ) -> Result<Option<(gtk4::Widget, Rc<dyn Widget>)>> {
+        let popover_output = output.clone();
+        let (root, widget): (gtk4::Widget, Rc<dyn Widget>) = match name {
+            "overview" => {
+                let overview = Overview::new()?;
+                (overview.widget().clone().upcast(), Rc::new(overview))
             }
-        }
-
-        if config.show_network {
-            let network = Network::new(window_weak.clone(), active_popovers.clone())?;
-            right_box.append(network.widget());
-            if let Some(popover) = network.popover() {
-                let _ = registry.register("network", popover.clone());
+            "workspaces" => {
+                let workspaces = Workspaces::new(output)?;
+                if config.position.is_vertical() {
+                    workspaces.widget().set_orientation(Orientation::Vertical);
+                }
+                (workspaces.widget().clone().upcast(), Rc::new(workspaces))
             }
-        }
-
-        if config.show_battery {
-            let battery = Battery::new(window_weak.clone(), active_popovers.clone())?;
-            right_box.append(battery.widget());
-            if let Some(popover) = battery.popover() {
-                let _ = registry.register("battery", popover.clone());
+            "taskbar" => {
+                let taskbar = Taskbar::new(output, &config.taskbar)?;
+                if config.position.is_vertical() {
+                    taskbar.widget().set_orientation(Orientation::Vertical);
+                }
+                (taskbar.widget().clone().upcast(), Rc::new(taskbar))
             }
-        }
-
-        if config.show_clock {
-            let clock = Clock::new(
-                &config.clock_format,
-                window_weak.clone(),
-                active_popovers.clone(),
-            )?;
-            right_box.append(clock.widget());
-            if let Some(popover) = clock.popover() {
-                let _ = registry.register("clock", popover.clone());
+            "launcher" => {
+                let launcher = Launcher::new(window_weak, active_popovers)?;
+                (launcher.widget().clone().upcast(), Rc::new(launcher))
             }
+            "places" => {
+                let places = Places::new(window_weak, active_popovers)?;
+                (places.widget().clone().upcast(), Rc::new(places))
+            }
+            "servers" => {
+                let servers = Servers::new(window_weak, active_popovers)?;
+                (servers.widget().clone().upcast(), Rc::new(servers))
+            }
+            "search" => {
+                let search = Search::new(window_weak, active_popovers)?;
+                (search.widget().clone().upcast(), Rc::new(search))
+            }
+            "git" => {
+                let git = Git::new(window_weak, active_popovers, config)?;
+                (git.widget().clone().upcast(), Rc::new(git))
+            }
+            "secrets" => {
+                let secrets = Secrets::new(window_weak, active_popovers)?;
+                (secrets.widget().clone().upcast(), Rc::new(secrets))
+            }
+            "sound" => {
+                let sound = Sound::new(window_weak, active_popovers)?;
+                (sound.widget().clone().upcast(), Rc::new(sound))
+            }
+            "bluetooth" => {
+                let bluetooth = Bluetooth::new(window_weak, active_popovers)?;
+                (bluetooth.widget().clone().upcast(), Rc::new(bluetooth))
+            }
+            "network" => {
+                let network = Network::new(window_weak, active_popovers)?;
+                (network.widget().clone().upcast(), Rc::new(network))
+            }
+            "battery" => {
+                let battery = Battery::new(window_weak, active_popovers)?;
+                (battery.widget().clone().upcast(), Rc::new(battery))
+            }
+            "clock" => {
+                let clock = Clock::new(&config.clock_format, window_weak, active_popovers)?;
+                (clock.widget().clone().upcast(), Rc::new(clock))
+            }
+            "power" => {
+                let power = Power::new(window_weak, active_popovers)?;
+                (power.widget().clone().upcast(), Rc::new(power))
+            }
+            "keyboard-layout" => {
+                let keyboard_layout =
+                    KeyboardLayout::new(&config.keyboard_layout, window_weak, active_popovers)?;
+                (keyboard_layout.widget().clone().upcast(), Rc::new(keyboard_layout))
+            }
+            "window-title" => {
+                let window_title = WindowTitle::new(output, window_weak, active_popovers)?;
+                (window_title.widget().clone().upcast(), Rc::new(window_title))
+            }
+            _ if config.custom_widget(name).is_some() => {
+                let custom = Custom::new(config.custom_widget(name).unwrap())?;
+                (custom.widget().clone().upcast(), Rc::new(custom))
+            }
+            _ if config.plugin_widget(name).is_some() => {
+                let plugin =
+                    Plugin::new(config.plugin_widget(name).unwrap(), window_weak, active_popovers)?;
+                (plugin.widget().clone().upcast(), Rc::new(plugin))
+            }
+            _ => {
+                warn!("Unknown widget '{}' in layout, skipping", name);
+                return Ok(None);
+            }
+        };
+
+        // Register the popover so it can be shown over IPC
+        if let Some(popover) = widget.popover() {
+            // Open popovers away from the screen edge the panel sits on
+            popover.set_position(match config.position {
+                PanelPosition::Top => gtk4::PositionType::Bottom,
+                PanelPosition::Bottom => gtk4::PositionType::Top,
+                PanelPosition::Left => gtk4::PositionType::Right,
+                PanelPosition::Right => gtk4::PositionType::Left,
+            });
+            let _ = PopoverRegistry::global().register(
+                name,
+                popover_output.as_deref(),
+                popover.clone(),
+            );
         }
 
-        if config.show_power {
-            let power = Power::new(window_weak.clone(), active_popovers.clone())?;
-            right_box.append(power.widget());
-            if let Some(popover) = power.popover() {
-                let _ = registry.register("power", popover.clone());
+        Ok(Some((root, widget)))
+    }
+
+    /// Close open popovers and stop every widget's timers and child processes,
+    /// before the panel is replaced or its window destroyed
+    pub fn shutdown(&self) {
+        self.lifecycle.shutdown();
+        Self::shutdown_widgets(&self.widgets);
+    }
+
+    fn shutdown_widgets(widgets: &[(String, Rc<dyn Widget>)]) {
+        let registry = PopoverRegistry::global();
+        for (name, widget) in widgets {
+            if let Some(popover) = widget.popover() {
+                // Hiding updates the window's open popover count
+                popover.popdown();
+                registry.unregister(name, popover);
             }
+            widget.shutdown();
         }
+    }
 
-        // Pack everything
-        container.append(&left_box);
-        container.append(&right_box);
-        // container.append(&center_box);
+    fn update_niri_indicator(indicator: &StatusIndicator) {
+        indicator.set_problem(match niri_state::is_connected() {
+            Some(false) => Some(
+                "Lost the connection to niri. Workspaces are not updating; reconnecting in the background.",
+            ),
+            _ => None,
+        });
+    }
 
-        Ok(Self {
-            container,
-            _config: config,
-        })
+    /// Show or clear the error indicator for a config file that failed to load
+    pub fn set_config_error(&self, error: Option<&str>) {
+        self.config_indicator.set_problem(error);
     }
 
     pub fn container(&self) -> &Box {
@@ -0,0 +1,128 @@
+//! JSON protocol spoken over stdio by `[[plugin]]` processes.
+//!
+//! A plugin writes one message per line to stdout, describing its bar button
+//! and popover. The panel writes one event per line to the plugin's stdin
+//! when the user interacts with the popover.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// A message from a plugin
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum PluginMessage {
+    /// Replace what the plugin shows on the panel
+    Bar {
+        #[serde(default)]
+        text: String,
+        #[serde(default)]
+        icon: Option<String>,
+        #[serde(default)]
+        tooltip: Option<String>,
+        /// Extra CSS classes for the bar button
+        #[serde(default)]
+        class: Vec<String>,
+    },
+    /// Replace the popover's content; an empty list disables the popover
+    Popover { elements: Vec<Element> },
+}
+
+/// A popover element. Elements with an `id` report interactions under it.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum Element {
+    /// Rows the user can pick from
+    List { id: String, items: Vec<ListItem> },
+    Button {
+        id: String,
+        label: String,
+        #[serde(default)]
+        icon: Option<String>,
+    },
+    Slider {
+        id: String,
+        #[serde(default)]
+        label: Option<String>,
+        value: f64,
+        #[serde(default)]
+        min: f64,
+        #[serde(default = "default_slider_max")]
+        max: f64,
+        #[serde(default = "default_slider_step")]
+        step: f64,
+    },
+    Toggle {
+        id: String,
+        label: String,
+        #[serde(default)]
+        active: bool,
+    },
+    /// Single-line text input, reported when Enter is pressed
+    Entry {
+        id: String,
+        #[serde(default)]
+        placeholder: Option<String>,
+        #[serde(default)]
+        text: String,
+    },
+}
+
+impl Element {
+    /// The id interactions with this element are reported under
+    pub fn id(&self) -> &str {
+        match self {
+            Element::List { id, .. }
+            | Element::Button { id, .. }
+            | Element::Slider { id, .. }
+            | Element::Toggle { id, .. }
+            | Element::Entry { id, .. } => id,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ListItem {
+    pub id: String,
+    pub label: String,
+    #[serde(default)]
+    pub detail: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+}
+
+fn default_slider_max() -> f64 {
+    100.0
+}
+
+fn default_slider_step() -> f64 {
+    1.0
+}
+
+/// An interaction the panel reports to a plugin
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum PluginEvent {
+    /// A button was clicked
+    Clicked { id: String },
+    /// A list row was picked
+    Selected { id: String, item: String },
+    /// A slider was moved
+    Changed { id: String, value: f64 },
+    /// A toggle was switched
+    Toggled { id: String, active: bool },
+    /// Enter was pressed in an entry
+    Submitted { id: String, text: String },
+    PopoverOpened,
+    PopoverClosed,
+}
+
+/// Parse one line written by a plugin
+pub fn parse_message(line: &str) -> Result<PluginMessage> {
+    serde_json::from_str(line).with_context(|| format!("Invalid plugin message: {}", line))
+}
+
+/// Encode an event as a line for the plugin's stdin
+pub fn encode_event(event: &PluginEvent) -> String {
+    // Serializing these plain enums can't fail
+    serde_json::to_string(event).unwrap_or_default()
+}
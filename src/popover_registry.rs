@@ -1,17 +1,30 @@
 use anyhow::Result;
 use gtk4::prelude::*;
 use gtk4::Popover;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
 use tracing::info;
 
+use crate::events::{EventBus, PanelEvent};
+use crate::niri_state;
 use crate::Widget;
 
+/// A registered popover and the output its panel is on
+#[derive(Debug)]
+struct Entry {
+    id: u64,
+    output: Option<String>,
+    popover: Popover,
+    visible: bool,
+}
+
 /// Registry to store and access all panel widget popovers
 #[derive(Debug, Default)]
 pub struct PopoverRegistry {
-    inner: RefCell<HashMap<String, Rc<Popover>>>,
+    // Every panel registers its own widgets, so a name can have several
+    // popovers, one per monitor
+    inner: RefCell<HashMap<String, Vec<Entry>>>,
+    next_id: Cell<u64>,
 }
 
 // Singleton instance of the registry
@@ -28,33 +41,185 @@ impl PopoverRegistry {
         }
     }
 
-    /// Register a popover with a name
-    pub fn register(&self, name: &str, popover: Popover) -> Result<()> {
-        info!("Registering popover: {}", name);
-        self.inner.borrow_mut().insert(name.to_string(), Rc::new(popover));
+    /// Register a popover with a name, for the panel on `output`
+    pub fn register(&self, name: &str, output: Option<&str>, popover: Popover) -> Result<()> {
+        info!("Registering popover: {} ({})", name, output.unwrap_or("all outputs"));
+
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+
+        let show_name = name.to_string();
+        popover.connect_show(move |_| {
+            PopoverRegistry::global().set_visible(&show_name, id, true);
+        });
+        let hide_name = name.to_string();
+        popover.connect_hide(move |_| {
+            PopoverRegistry::global().set_visible(&hide_name, id, false);
+        });
+
+        let visible = popover.is_visible();
+        self.inner
+            .borrow_mut()
+            .entry(name.to_string())
+            .or_default()
+            .push(Entry {
+                id,
+                output: output.map(str::to_string),
+                popover,
+                visible: false,
+            });
+        if visible {
+            self.set_visible(name, id, true);
+        }
         Ok(())
     }
 
-    /// Show a popover by name
+    /// Record that one of a name's popovers was shown or hidden. Clients
+    /// hear about a name opening when its first popover is shown, and
+    /// closing when its last one is hidden.
+    fn set_visible(&self, name: &str, id: u64, visible: bool) {
+        let (was_open, is_open) = {
+            let mut inner = self.inner.borrow_mut();
+            // Ignore popovers that have since been unregistered
+            let Some(entries) = inner.get_mut(name) else {
+                return;
+            };
+            let was_open = entries.iter().any(|entry| entry.visible);
+            let Some(entry) = entries.iter_mut().find(|entry| entry.id == id) else {
+                return;
+            };
+            entry.visible = visible;
+            (was_open, entries.iter().any(|entry| entry.visible))
+        };
+
+        if was_open != is_open {
+            let widget = name.to_string();
+            EventBus::global().emit(if is_open {
+                PanelEvent::PopoverOpened { widget }
+            } else {
+                PanelEvent::PopoverClosed { widget }
+            });
+        }
+    }
+
+    /// Remove a popover when its panel goes away
+    pub fn unregister(&self, name: &str, popover: &Popover) {
+        let id = self.inner.borrow().get(name).and_then(|entries| {
+            entries
+                .iter()
+                .find(|entry| entry.popover == *popover)
+                .map(|entry| entry.id)
+        });
+        let Some(id) = id else {
+            return;
+        };
+
+        info!("Unregistering popover: {}", name);
+        // Report the name closed if this was its last visible popover
+        self.set_visible(name, id, false);
+        let mut inner = self.inner.borrow_mut();
+        if let Some(entries) = inner.get_mut(name) {
+            entries.retain(|entry| entry.id != id);
+            if entries.is_empty() {
+                inner.remove(name);
+            }
+        }
+    }
+
+    /// The popovers registered under a name. Cloned out of the registry,
+    /// since showing or hiding one calls back into it.
+    fn popovers(&self, name: &str) -> Vec<(Option<String>, Popover, bool)> {
+        self.inner
+            .borrow()
+            .get(name)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .map(|entry| (entry.output.clone(), entry.popover.clone(), entry.visible))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// The popover to show for a name: the one on the focused output, or
+    /// the first panel's when niri's focus is unknown or has no panel
+    fn pick(&self, name: &str) -> Option<Popover> {
+        let popovers = self.popovers(name);
+        let focused = niri_state::with(|state| state.focused_output().map(str::to_string));
+        popovers
+            .iter()
+            .find(|(output, _, _)| focused.is_some() && *output == focused)
+            .or_else(|| popovers.first())
+            .map(|(_, popover, _)| popover.clone())
+    }
+
+    /// Show a popover by name, on the focused output's panel
     pub fn show(&self, name: &str) -> Result<bool> {
-        if let Some(popover) = self.inner.borrow().get(name) {
+        if let Some(popover) = self.pick(name) {
             info!("Showing popover: {}", name);
             popover.popup();
             return Ok(true);
         }
-        
+
         Ok(false)
     }
 
-    /// Hide a popover by name
+    /// Hide a popover by name, on every panel
     pub fn hide(&self, name: &str) -> Result<bool> {
-        if let Some(popover) = self.inner.borrow().get(name) {
-            info!("Hiding popover: {}", name);
+        let popovers = self.popovers(name);
+        if popovers.is_empty() {
+            return Ok(false);
+        }
+
+        info!("Hiding popover: {}", name);
+        for (_, popover, _) in popovers {
             popover.popdown();
-            return Ok(true);
         }
-        
-        Ok(false)
+        Ok(true)
+    }
+
+    /// Hide a popover if it is shown on any panel, or show it on the focused
+    /// output's panel. Returns whether it is now visible, or `None` if no
+    /// popover has this name.
+    pub fn toggle(&self, name: &str) -> Result<Option<bool>> {
+        let popovers = self.popovers(name);
+        if popovers.is_empty() {
+            return Ok(None);
+        }
+
+        if popovers.iter().any(|(_, _, visible)| *visible) {
+            self.hide(name)?;
+            Ok(Some(false))
+        } else {
+            self.show(name)?;
+            Ok(Some(true))
+        }
+    }
+
+    /// Hide every visible popover, returning the names of those hidden
+    pub fn hide_all(&self) -> Vec<String> {
+        let names = self.visible_names();
+        for name in &names {
+            for (_, popover, visible) in self.popovers(name) {
+                if visible {
+                    info!("Hiding popover: {}", name);
+                    popover.popdown();
+                }
+            }
+        }
+        names
+    }
+
+    /// Names of the popovers shown on any panel, in sorted order
+    pub fn visible_names(&self) -> Vec<String> {
+        let names: BTreeSet<String> = self
+            .inner
+            .borrow()
+            .iter()
+            .filter(|(_, entries)| entries.iter().any(|entry| entry.visible))
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.into_iter().collect()
     }
 
     /// Get all registered popover names
@@ -77,6 +242,8 @@ impl PopoverRegistry {
             Widget::Battery => "battery",
             Widget::Clock => "clock",
             Widget::Power => "power",
+            Widget::WindowTitle => "window-title",
+            Widget::KeyboardLayout => "keyboard-layout",
         }
     }
-}
\ No newline at end of file
+}
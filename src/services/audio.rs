@@ -0,0 +1,251 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use tracing::debug;
+
+use super::{Service, ServiceSpec, Trigger};
+use crate::command::Cmd;
+use crate::events::{EventBus, PanelEvent};
+use crate::widgets::Lifecycle;
+
+/// Volume and device of the default audio output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AudioInfo {
+    pub volume: u32,
+    pub muted: bool,
+    pub device_name: String,
+    pub device_id: String,
+}
+
+thread_local! {
+    static SERVICE: Service<Option<AudioInfo>> = Service::new(ServiceSpec {
+        name: "audio",
+        fetch,
+        interval: Duration::from_secs(1),
+        idle_interval: Duration::from_secs(10),
+        monitor: Some(monitor),
+        on_change: Some(emit_volume_event),
+    });
+}
+
+/// The default output's volume, `None` when no sound server answers
+pub fn service() -> Service<Option<AudioInfo>> {
+    SERVICE.with(Service::clone)
+}
+
+/// Tell event subscribers about the new volume
+fn emit_volume_event(info: &Option<AudioInfo>) {
+    if let Some(info) = info {
+        EventBus::global().emit(PanelEvent::VolumeChanged {
+            volume: info.volume,
+            muted: info.muted,
+            device: Some(info.device_name.clone()).filter(|name| !name.is_empty()),
+        });
+    }
+}
+
+/// Follow `pactl subscribe`, which PipeWire's pulse server also speaks, and
+/// poll whenever a sink or the default device changes
+fn monitor(trigger: Trigger<Option<AudioInfo>>, lifecycle: &Lifecycle) -> Result<()> {
+    let mut child = Command::new("pactl")
+        .arg("subscribe")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to start pactl subscribe")?;
+    let stdout = child.stdout.take().context("pactl subscribe has no stdout")?;
+    lifecycle.add_child(child);
+
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            let Ok(line) = line else { break };
+            // e.g. "Event 'change' on sink #54"; streams are "sink-input"
+            let relevant = line.contains("on sink #") || line.contains("on server");
+            if relevant && !trigger.trigger() {
+                break;
+            }
+        }
+        debug!("pactl subscribe exited");
+    });
+
+    Ok(())
+}
+
+/// Read the default output's volume from WirePlumber, PulseAudio or ALSA
+fn fetch() -> Option<AudioInfo> {
+    // Try wpctl first (WirePlumber/PipeWire) - using @DEFAULT_AUDIO_SINK@
+    if let Ok(volume_output) = Cmd::new("wpctl")
+        .args(&["get-volume", "@DEFAULT_AUDIO_SINK@"])
+        .run_blocking()
+    {
+        if volume_output.status.success() && !volume_output.stdout.is_empty() {
+            let volume_str = String::from_utf8_lossy(&volume_output.stdout);
+
+            // Parse volume (format: "Volume: 0.50 [MUTED]" or "Volume: 0.50")
+            let muted = volume_str.contains("[MUTED]");
+            let volume = if let Some(vol_str) = volume_str.split(':').nth(1) {
+                if let Some(vol_val) = vol_str.split_whitespace().next() {
+                    (vol_val.parse::<f32>().unwrap_or(0.0) * 100.0) as u32
+                } else {
+                    0
+                }
+            } else {
+                0
+            };
+
+            // Get device name and ID from wpctl status
+            let (device_name, device_id) = if let Ok(status_output) =
+                Cmd::new("wpctl").arg("status").run_blocking()
+            {
+                let status_str = String::from_utf8_lossy(&status_output.stdout);
+                let mut in_sinks_section = false;
+                let mut found_device_name = "Unknown".to_string();
+                let mut found_device_id = String::new();
+
+                for line in status_str.lines() {
+                    if line.contains("Sinks:") {
+                        in_sinks_section = true;
+                        continue;
+                    }
+                    if in_sinks_section
+                        && (line.starts_with(" ├─")
+                            || line.starts_with(" └─")
+                            || line.starts_with(" │"))
+                    {
+                        if line.contains("*") {
+                            // Parse format: " │  *   60. Family 17h/19h/1ah HD Audio Controller Analog Stereo [vol: 0.17]"
+                            // Find the part after the number and dot
+                            if let Some(dot_pos) = line.find('.') {
+                                // Get ID
+                                let prefix = &line[..dot_pos];
+                                found_device_id =
+                                    prefix.split_whitespace().last().unwrap_or("").to_string();
+
+                                let after_dot = &line[dot_pos + 1..].trim();
+                                // Find where [vol: starts
+                                if let Some(vol_pos) = after_dot.find("[vol:") {
+                                    found_device_name = after_dot[..vol_pos].trim().to_string();
+                                } else if let Some(bracket_pos) = after_dot.find('[') {
+                                    found_device_name =
+                                        after_dot[..bracket_pos].trim().to_string();
+                                } else {
+                                    found_device_name = after_dot.to_string();
+                                }
+                                break;
+                            }
+                        }
+                    }
+                    if in_sinks_section && !line.starts_with(" ") && !line.trim().is_empty() {
+                        break;
+                    }
+                }
+
+                (found_device_name, found_device_id)
+            } else {
+                ("Unknown".to_string(), String::new())
+            };
+
+            return Some(AudioInfo {
+                volume,
+                muted,
+                device_name,
+                device_id,
+            });
+        }
+    }
+
+    // Try pactl as fallback (PulseAudio/older PipeWire)
+    if let Ok(output) = Cmd::new("pactl")
+        .args(&["get-sink-volume", "@DEFAULT_SINK@"])
+        .run_blocking()
+    {
+        let output_str = String::from_utf8_lossy(&output.stdout);
+        let volume = output_str
+            .split('/')
+            .nth(1)
+            .and_then(|s| s.trim().trim_end_matches('%').parse::<u32>().ok())
+            .unwrap_or(0);
+
+        let muted = if let Ok(mute_output) = Cmd::new("pactl")
+            .args(&["get-sink-mute", "@DEFAULT_SINK@"])
+            .run_blocking()
+        {
+            String::from_utf8_lossy(&mute_output.stdout).contains("yes")
+        } else {
+            false
+        };
+
+        // Get device name and ID
+        let (device_name, device_id) = if let Ok(device_output) =
+            Cmd::new("pactl").args(&["get-default-sink"]).run_blocking()
+        {
+            let sink_name = String::from_utf8_lossy(&device_output.stdout)
+                .trim()
+                .to_string();
+
+            // Get human-readable name
+            if let Ok(desc_output) = Cmd::new("pactl").args(&["list", "sinks"]).run_blocking() {
+                let desc_str = String::from_utf8_lossy(&desc_output.stdout);
+                let mut found_sink = false;
+                for desc_line in desc_str.lines() {
+                    if desc_line.contains(&format!("Name: {}", sink_name)) {
+                        found_sink = true;
+                    }
+                    if found_sink && desc_line.trim().starts_with("Description:") {
+                        let description = desc_line
+                            .split(':')
+                            .nth(1)
+                            .unwrap_or("Unknown")
+                            .trim()
+                            .to_string();
+                        return Some(AudioInfo {
+                            volume,
+                            muted,
+                            device_name: description,
+                            device_id: sink_name.clone(),
+                        });
+                    }
+                }
+            }
+            (sink_name.clone(), sink_name)
+        } else {
+            ("Unknown".to_string(), String::new())
+        };
+
+        return Some(AudioInfo {
+            volume,
+            muted,
+            device_name,
+            device_id,
+        });
+    }
+
+    // Fallback to amixer (ALSA)
+    if let Ok(output) = Cmd::new("amixer").args(&["get", "Master"]).run_blocking() {
+        let output_str = String::from_utf8_lossy(&output.stdout);
+        for line in output_str.lines() {
+            if line.contains("Playback") && line.contains('%') {
+                let volume = line
+                    .split('[')
+                    .nth(1)
+                    .and_then(|s| s.split('%').next())
+                    .and_then(|s| s.parse::<u32>().ok())
+                    .unwrap_or(0);
+
+                let muted = line.contains("[off]");
+
+                return Some(AudioInfo {
+                    volume,
+                    muted,
+                    device_name: "Master".to_string(),
+                    device_id: "master".to_string(),
+                });
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,185 @@
+use serde::Serialize;
+use std::time::Duration;
+
+use super::{Service, ServiceSpec};
+use crate::command::{self, Cmd};
+
+/// The adapter and its known devices, as read from bluetoothctl
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BluetoothState {
+    /// Whether bluetoothctl is installed
+    pub available: bool,
+    pub powered: bool,
+    /// Connected devices first, then paired ones, then by name. Empty while
+    /// the adapter is off.
+    pub devices: Vec<BluetoothDevice>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BluetoothDevice {
+    pub address: String,
+    pub name: String,
+    pub icon: String,
+    pub connected: bool,
+    pub paired: bool,
+    pub trusted: bool,
+    pub blocked: bool,
+    pub battery_percentage: Option<u8>,
+    pub device_type: DeviceType,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeviceType {
+    Computer,
+    Phone,
+    AudioHeadset,
+    AudioHeadphones,
+    AudioSpeaker,
+    Keyboard,
+    Mouse,
+    GameController,
+    Unknown,
+}
+
+impl DeviceType {
+    fn from_icon(icon: &str) -> Self {
+        match icon {
+            s if s.contains("computer") => DeviceType::Computer,
+            s if s.contains("phone") => DeviceType::Phone,
+            s if s.contains("headset") => DeviceType::AudioHeadset,
+            s if s.contains("headphones") => DeviceType::AudioHeadphones,
+            s if s.contains("speaker") || s.contains("audio") => DeviceType::AudioSpeaker,
+            s if s.contains("keyboard") => DeviceType::Keyboard,
+            s if s.contains("mouse") => DeviceType::Mouse,
+            s if s.contains("game") || s.contains("controller") => DeviceType::GameController,
+            _ => DeviceType::Unknown,
+        }
+    }
+
+    pub fn icon_name(&self) -> &'static str {
+        match self {
+            DeviceType::Computer => "computer-symbolic",
+            DeviceType::Phone => "phone-symbolic",
+            DeviceType::AudioHeadset => "audio-headset-symbolic",
+            DeviceType::AudioHeadphones => "audio-headphones-symbolic",
+            DeviceType::AudioSpeaker => "audio-speakers-symbolic",
+            DeviceType::Keyboard => "input-keyboard-symbolic",
+            DeviceType::Mouse => "input-mouse-symbolic",
+            DeviceType::GameController => "input-gaming-symbolic",
+            DeviceType::Unknown => "bluetooth-symbolic",
+        }
+    }
+}
+
+thread_local! {
+    static SERVICE: Service<BluetoothState> = Service::new(ServiceSpec {
+        name: "bluetooth",
+        fetch,
+        interval: Duration::from_secs(2),
+        idle_interval: Duration::from_secs(30),
+        monitor: None,
+        on_change: None,
+    });
+}
+
+/// The adapter and its devices
+pub fn service() -> Service<BluetoothState> {
+    SERVICE.with(Service::clone)
+}
+
+fn fetch() -> BluetoothState {
+    if !command::in_path("bluetoothctl") {
+        return BluetoothState::default();
+    }
+
+    let powered = command::stdout(Cmd::new("bluetoothctl").arg("show").run_blocking())
+        .is_some_and(|output| output.lines().any(|line| line.trim() == "Powered: yes"));
+    BluetoothState {
+        available: true,
+        powered,
+        devices: if powered { read_devices() } else { Vec::new() },
+    }
+}
+
+fn read_devices() -> Vec<BluetoothDevice> {
+    let mut devices = Vec::new();
+
+    // Get all devices
+    if let Some(output_str) = command::stdout(Cmd::new("bluetoothctl").arg("devices").run_blocking())
+    {
+        for line in output_str.lines() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            if parts.len() >= 3 && parts[0] == "Device" {
+                let address = parts[1].to_string();
+                let name = parts[2..].join(" ");
+
+                // Get detailed info for each device
+                if let Some(info) = command::stdout(
+                    Cmd::new("bluetoothctl")
+                        .args(["info", &address])
+                        .run_blocking(),
+                ) {
+                    devices.push(parse_device_info(address, name, &info));
+                }
+            }
+        }
+    }
+
+    // Sort: connected first, then paired, then by name
+    devices.sort_by(|a, b| match (a.connected, b.connected) {
+        (true, false) => std::cmp::Ordering::Less,
+        (false, true) => std::cmp::Ordering::Greater,
+        _ => match (a.paired, b.paired) {
+            (true, false) => std::cmp::Ordering::Less,
+            (false, true) => std::cmp::Ordering::Greater,
+            _ => a.name.cmp(&b.name),
+        },
+    });
+
+    devices
+}
+
+fn parse_device_info(address: String, name: String, output_str: &str) -> BluetoothDevice {
+    let mut device = BluetoothDevice {
+        address,
+        name,
+        icon: String::new(),
+        connected: false,
+        paired: false,
+        trusted: false,
+        blocked: false,
+        battery_percentage: None,
+        device_type: DeviceType::Unknown,
+    };
+
+    for line in output_str.lines() {
+        let line = line.trim();
+        if line.starts_with("Name:") {
+            device.name = line.split(':').nth(1).unwrap_or("").trim().to_string();
+        } else if line.starts_with("Icon:") {
+            device.icon = line.split(':').nth(1).unwrap_or("").trim().to_string();
+            device.device_type = DeviceType::from_icon(&device.icon);
+        } else if line == "Connected: yes" {
+            device.connected = true;
+        } else if line == "Paired: yes" {
+            device.paired = true;
+        } else if line == "Trusted: yes" {
+            device.trusted = true;
+        } else if line == "Blocked: yes" {
+            device.blocked = true;
+        } else if line.starts_with("Battery Percentage:") {
+            if let Some(value) = line.split(':').nth(1) {
+                if let Some(percentage_str) = value.trim().strip_suffix("%)") {
+                    if let Some(percentage_str) = percentage_str.strip_prefix("0x") {
+                        if let Ok(hex_val) = u8::from_str_radix(percentage_str, 16) {
+                            device.battery_percentage = Some(hex_val);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    device
+}
@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use tracing::debug;
+
+use super::{Service, ServiceSpec, Trigger};
+use crate::command::Cmd;
+use crate::widgets::Lifecycle;
+
+/// What the active media player is playing
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MediaInfo {
+    pub player_name: String,
+    pub status: String, // Playing, Paused, Stopped
+    pub artist: String,
+    pub title: String,
+    pub album: String,
+    pub art_url: Option<String>,
+}
+
+impl Default for MediaInfo {
+    fn default() -> Self {
+        Self {
+            player_name: String::new(),
+            status: "Stopped".to_string(),
+            artist: String::new(),
+            title: String::new(),
+            album: String::new(),
+            art_url: None,
+        }
+    }
+}
+
+thread_local! {
+    static SERVICE: Service<Option<MediaInfo>> = Service::new(ServiceSpec {
+        name: "media",
+        fetch,
+        interval: Duration::from_secs(2),
+        idle_interval: Duration::from_secs(15),
+        monitor: Some(monitor),
+        on_change: None,
+    });
+}
+
+/// The playing media, `None` when no player is running
+pub fn service() -> Service<Option<MediaInfo>> {
+    SERVICE.with(Service::clone)
+}
+
+/// Poll whenever a player starts, stops or changes track
+fn monitor(trigger: Trigger<Option<MediaInfo>>, lifecycle: &Lifecycle) -> Result<()> {
+    let mut child = Command::new("playerctl")
+        .args(["--follow", "metadata", "--format", "{{status}} {{title}}"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to start playerctl --follow")?;
+    let stdout = child.stdout.take().context("playerctl has no stdout")?;
+    lifecycle.add_child(child);
+
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines() {
+            if line.is_err() || !trigger.trigger() {
+                break;
+            }
+        }
+        debug!("playerctl --follow exited");
+    });
+
+    Ok(())
+}
+
+/// Read what the active MPRIS player is playing through playerctl
+fn fetch() -> Option<MediaInfo> {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    // Static flag to avoid repeated checks for playerctl availability
+    static PLAYERCTL_AVAILABLE: AtomicBool = AtomicBool::new(true);
+
+    if !PLAYERCTL_AVAILABLE.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    // Use a single playerctl command with a combined format to get all info at once
+    // This reduces the process spawns from 3 to 1, which is a major performance improvement
+    let format_str = "{{status}}\\n{{playerName}}\\n{{artist}}\\n{{title}}\\n{{album}}";
+
+    // playerctl is killed if it hangs, e.g. on a player that doesn't answer
+    let output_result = match Cmd::new("playerctl")
+        .args(["metadata", "--format", format_str])
+        .run_blocking()
+    {
+        Ok(output) => {
+            if !output.status.success() {
+                return None; // No players available
+            }
+            output
+        }
+        Err(e) => {
+            // Mark playerctl as unavailable to avoid future attempts, unless
+            // it was there but timed out
+            debug!("playerctl failed: {:#}", e);
+            if e.downcast_ref::<std::io::Error>().is_some() {
+                PLAYERCTL_AVAILABLE.store(false, Ordering::Relaxed);
+            }
+            return None;
+        }
+    };
+
+    // Process the output
+    let metadata_str = String::from_utf8_lossy(&output_result.stdout).to_string();
+    let parts: Vec<&str> = metadata_str.lines().collect();
+
+    // Extract the parts
+    let status = if parts.len() > 0 {
+        match parts[0] {
+            "Playing" | "Paused" | "Stopped" => parts[0].to_string(),
+            _ => "Stopped".to_string(),
+        }
+    } else {
+        "Stopped".to_string()
+    };
+
+    // If stopped, return default info to avoid further processing
+    if status == "Stopped" {
+        return Some(MediaInfo::default());
+    }
+
+    let player_name = if parts.len() > 1 {
+        parts[1].to_string()
+    } else {
+        String::new()
+    };
+    let artist = if parts.len() > 2 {
+        parts[2].to_string()
+    } else {
+        String::new()
+    };
+    let title = if parts.len() > 3 {
+        parts[3].to_string()
+    } else {
+        String::new()
+    };
+    let album = if parts.len() > 4 {
+        parts[4].to_string()
+    } else {
+        String::new()
+    };
+
+    Some(MediaInfo {
+        player_name,
+        status,
+        artist,
+        title,
+        album,
+        art_url: None,
+    })
+}
@@ -0,0 +1,400 @@
+//! Data sources shared by every consumer: the panel widgets on every
+//! monitor, `niri-panel get` and `niri-panel subscribe`.
+//!
+//! Each service polls its source once, off the main thread, caches the
+//! result and tells its subscribers only when the value changed. A service
+//! runs while at least one widget is subscribed. It polls at its fast
+//! interval while one of its widgets' popovers is shown, and backs off to
+//! its idle interval otherwise. Sources that can report changes themselves,
+//! like `pactl subscribe` or an inotify watch, trigger a poll right away.
+//! A poll that hasn't finished after `FETCH_TIMEOUT` is given up on, so a
+//! hung source only delays the service.
+//!
+//! Everything runs on the thread-default main context of the thread that
+//! first subscribes, which is the GTK main loop in the panel.
+
+use gtk4::glib;
+use gtk4::prelude::*;
+use gtk4::Popover;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::widgets::Lifecycle;
+
+pub mod audio;
+pub mod bluetooth;
+pub mod media;
+pub mod network;
+pub mod power;
+pub mod system;
+
+/// How long a poll may run before the service stops waiting for it
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How a service gets its data
+pub struct ServiceSpec<T> {
+    /// Name used in logs
+    pub name: &'static str,
+    /// Read the current value. Runs on a worker thread, so it may block.
+    pub fetch: fn() -> T,
+    /// Poll interval while a popover showing the data is open
+    pub interval: Duration,
+    /// Poll interval otherwise
+    pub idle_interval: Duration,
+    /// Start watching the source for changes, calling the trigger on each.
+    /// Whatever it starts must stop when the lifecycle is shut down.
+    pub monitor: Option<fn(Trigger<T>, &Lifecycle) -> anyhow::Result<()>>,
+    /// Called once per change, before the subscribers
+    pub on_change: Option<fn(&T)>,
+}
+
+enum Message<T> {
+    Fetched { generation: u64, value: T },
+    Triggered,
+}
+
+/// Asks a service to poll now; can be sent to other threads
+pub struct Trigger<T>(glib::Sender<Message<T>>);
+
+impl<T> Clone for Trigger<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Trigger<T> {
+    /// Poll the service as soon as the main loop gets to it. Returns false
+    /// once the service has stopped, so a monitor thread can exit.
+    pub fn trigger(&self) -> bool {
+        self.0.send(Message::Triggered).is_ok()
+    }
+}
+
+type Subscriber<T> = Rc<dyn Fn(&T)>;
+
+/// A cached data source, see the module documentation. Clones share the
+/// same source.
+pub struct Service<T> {
+    inner: Rc<ServiceInner<T>>,
+}
+
+impl<T> Clone for Service<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+struct ServiceInner<T> {
+    spec: ServiceSpec<T>,
+    value: RefCell<Option<T>>,
+    subscribers: RefCell<Vec<(u64, Subscriber<T>)>>,
+    next_id: Cell<u64>,
+    // Number of subscribers' popovers currently shown
+    shown_popovers: Cell<usize>,
+    // Bumped on every start and stop, and when a poll is given up on, so
+    // stale results are dropped
+    generation: Cell<u64>,
+    running: Cell<bool>,
+    fetching: Cell<bool>,
+    // Another poll was asked for while one was running
+    pending: Cell<bool>,
+    timer: RefCell<Option<glib::JoinHandle<()>>>,
+    // Gives up on the running poll after `FETCH_TIMEOUT`
+    watchdog: RefCell<Option<glib::JoinHandle<()>>>,
+    sender: RefCell<Option<glib::Sender<Message<T>>>>,
+    // Stops the monitor when the service stops
+    monitor: RefCell<Option<Lifecycle>>,
+}
+
+impl<T: Clone + PartialEq + Send + 'static> Service<T> {
+    pub fn new(spec: ServiceSpec<T>) -> Self {
+        Self {
+            inner: Rc::new(ServiceInner {
+                spec,
+                value: RefCell::new(None),
+                subscribers: RefCell::new(Vec::new()),
+                next_id: Cell::new(0),
+                shown_popovers: Cell::new(0),
+                generation: Cell::new(0),
+                running: Cell::new(false),
+                fetching: Cell::new(false),
+                pending: Cell::new(false),
+                timer: RefCell::new(None),
+                watchdog: RefCell::new(None),
+                sender: RefCell::new(None),
+                monitor: RefCell::new(None),
+            }),
+        }
+    }
+
+    /// Call `callback` with the current value, if there is one yet, and
+    /// after every change until the widget is shut down
+    pub fn subscribe(&self, lifecycle: &Lifecycle, callback: impl Fn(&T) + 'static) {
+        let inner = &self.inner;
+        let id = inner.next_id.get();
+        inner.next_id.set(id + 1);
+        let callback: Subscriber<T> = Rc::new(callback);
+        inner.subscribers.borrow_mut().push((id, callback.clone()));
+
+        let inner_weak = Rc::downgrade(inner);
+        lifecycle.on_shutdown(move || {
+            if let Some(inner) = inner_weak.upgrade() {
+                inner.subscribers.borrow_mut().retain(|(subscriber_id, _)| *subscriber_id != id);
+                if inner.subscribers.borrow().is_empty() {
+                    inner.stop();
+                }
+            }
+        });
+
+        if !inner.running.get() {
+            inner.start();
+        }
+
+        let value = inner.value.borrow().clone();
+        if let Some(value) = value {
+            callback(&value);
+        }
+    }
+
+    /// Poll faster while the popover is shown, and poll right away when it opens
+    pub fn track_popover(&self, lifecycle: &Lifecycle, popover: &Popover) {
+        let set_shown = self.track_shown(lifecycle);
+        let set_hidden = set_shown.clone();
+        popover.connect_show(move |_| set_shown(true));
+        popover.connect_hide(move |_| set_hidden(false));
+    }
+
+    /// Like `track_popover`, for anything showing the data: the returned
+    /// function tells the service whether it is shown now
+    pub fn track_shown(&self, lifecycle: &Lifecycle) -> impl Fn(bool) + Clone + 'static {
+        let shown = Rc::new(Cell::new(false));
+
+        // Something torn down while shown no longer counts
+        let inner_shutdown = Rc::downgrade(&self.inner);
+        let shown_shutdown = shown.clone();
+        lifecycle.on_shutdown(move || {
+            if let Some(inner) = inner_shutdown.upgrade() {
+                if shown_shutdown.replace(false) {
+                    inner.shown_popovers.set(inner.shown_popovers.get() - 1);
+                }
+            }
+        });
+
+        let inner = Rc::downgrade(&self.inner);
+        let lifecycle = lifecycle.clone();
+        move |is_shown| {
+            let Some(inner) = inner.upgrade() else {
+                return;
+            };
+            if lifecycle.is_stopped() {
+                return;
+            }
+            if is_shown {
+                if !shown.replace(true) {
+                    inner.shown_popovers.set(inner.shown_popovers.get() + 1);
+                }
+                inner.refresh();
+            } else if shown.replace(false) {
+                inner.shown_popovers.set(inner.shown_popovers.get() - 1);
+            }
+        }
+    }
+
+    /// The last value read, or `None` before the first poll or while no widget uses the service
+    pub fn get(&self) -> Option<T> {
+        self.inner.value.borrow().clone()
+    }
+
+    /// Poll now, e.g. after changing the value from the panel
+    pub fn refresh(&self) {
+        if self.inner.running.get() {
+            self.inner.refresh();
+        }
+    }
+}
+
+impl<T: Clone + PartialEq + Send + 'static> ServiceInner<T> {
+    fn start(self: &Rc<Self>) {
+        debug!("Starting {} service", self.spec.name);
+        self.running.set(true);
+        self.generation.set(self.generation.get() + 1);
+
+        let (sender, receiver) = glib::MainContext::channel(glib::Priority::DEFAULT);
+        let inner = Rc::downgrade(self);
+        receiver.attach(None, move |message| {
+            let Some(inner) = inner.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            match message {
+                Message::Fetched { generation, value } => {
+                    if generation == inner.generation.get() {
+                        inner.fetched(value);
+                    }
+                }
+                Message::Triggered => inner.refresh(),
+            }
+            if inner.sender.borrow().is_some() {
+                glib::ControlFlow::Continue
+            } else {
+                glib::ControlFlow::Break
+            }
+        });
+
+        if let Some(monitor) = self.spec.monitor {
+            let lifecycle = Lifecycle::new();
+            let trigger = Trigger(sender.clone());
+            if let Err(e) = monitor(trigger, &lifecycle) {
+                debug!("{} service can't watch for changes, polling only: {:#}", self.spec.name, e);
+            }
+            *self.monitor.borrow_mut() = Some(lifecycle);
+        }
+
+        *self.sender.borrow_mut() = Some(sender);
+        self.refresh();
+    }
+
+    fn stop(&self) {
+        debug!("Stopping {} service", self.spec.name);
+        self.running.set(false);
+        self.generation.set(self.generation.get() + 1);
+        self.fetching.set(false);
+        self.pending.set(false);
+        self.cancel_timer();
+        self.cancel_watchdog();
+        self.sender.borrow_mut().take();
+        if let Some(monitor) = self.monitor.borrow_mut().take() {
+            monitor.shutdown();
+        }
+        // Don't hand out stale data while nothing keeps it fresh
+        self.value.borrow_mut().take();
+    }
+
+    fn cancel_timer(&self) {
+        if let Some(timer) = self.timer.borrow_mut().take() {
+            timer.abort();
+        }
+    }
+
+    fn cancel_watchdog(&self) {
+        if let Some(watchdog) = self.watchdog.borrow_mut().take() {
+            watchdog.abort();
+        }
+    }
+
+    /// Poll on a worker thread, or right after the running poll finishes
+    fn refresh(self: &Rc<Self>) {
+        if !self.running.get() {
+            return;
+        }
+        self.cancel_timer();
+        if self.fetching.replace(true) {
+            self.pending.set(true);
+            return;
+        }
+
+        let Some(sender) = self.sender.borrow().clone() else {
+            return;
+        };
+        let fetch = self.spec.fetch;
+        let generation = self.generation.get();
+        thread::spawn(move || {
+            let value = fetch();
+            let _ = sender.send(Message::Fetched { generation, value });
+        });
+
+        let inner = Rc::downgrade(self);
+        let watchdog = run_after(FETCH_TIMEOUT, move || {
+            if let Some(inner) = inner.upgrade() {
+                inner.watchdog.borrow_mut().take();
+                inner.give_up_fetch();
+            }
+        });
+        self.cancel_watchdog();
+        *self.watchdog.borrow_mut() = Some(watchdog);
+    }
+
+    /// Stop waiting for a poll that hangs. Its thread can't be stopped, but
+    /// its result is dropped and polling carries on.
+    fn give_up_fetch(self: &Rc<Self>) {
+        if !self.running.get() || !self.fetching.get() {
+            return;
+        }
+        warn!(
+            "{} service poll still running after {:?}, giving up on it",
+            self.spec.name, FETCH_TIMEOUT
+        );
+        self.generation.set(self.generation.get() + 1);
+        self.fetching.set(false);
+        self.poll_next();
+    }
+
+    fn fetched(self: &Rc<Self>, value: T) {
+        self.fetching.set(false);
+        self.cancel_watchdog();
+
+        let changed = self.value.borrow().as_ref() != Some(&value);
+        if changed {
+            *self.value.borrow_mut() = Some(value.clone());
+            if let Some(on_change) = self.spec.on_change {
+                on_change(&value);
+            }
+            // Call out without holding the borrow, so callbacks can subscribe
+            let subscribers: Vec<Subscriber<T>> = self
+                .subscribers
+                .borrow()
+                .iter()
+                .map(|(_, callback)| callback.clone())
+                .collect();
+            for callback in subscribers {
+                callback(&value);
+            }
+        }
+
+        self.poll_next();
+    }
+
+    /// Poll again right away if asked to meanwhile, otherwise after the interval
+    fn poll_next(self: &Rc<Self>) {
+        if self.pending.replace(false) {
+            self.refresh();
+        } else {
+            self.schedule();
+        }
+    }
+
+    fn schedule(self: &Rc<Self>) {
+        if !self.running.get() {
+            return;
+        }
+        let interval = if self.shown_popovers.get() > 0 {
+            self.spec.interval
+        } else {
+            self.spec.idle_interval
+        };
+
+        let inner = Rc::downgrade(self);
+        let timer = run_after(interval, move || {
+            if let Some(inner) = inner.upgrade() {
+                // The timer is done once this runs, so don't abort it again
+                inner.timer.borrow_mut().take();
+                inner.refresh();
+            }
+        });
+        self.cancel_timer();
+        *self.timer.borrow_mut() = Some(timer);
+    }
+}
+
+/// Call `func` after `delay` on the thread-default main context, unless the
+/// returned handle is aborted first
+fn run_after(delay: Duration, func: impl FnOnce() + 'static) -> glib::JoinHandle<()> {
+    glib::MainContext::ref_thread_default().spawn_local(async move {
+        glib::timeout_future(delay).await;
+        func();
+    })
+}
@@ -0,0 +1,306 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::time::Duration;
+use tracing::debug;
+
+use super::{Service, ServiceSpec, Trigger};
+use crate::events::{EventBus, PanelEvent};
+use crate::network_manager::{Device, NetworkManager, Snapshot};
+use crate::widgets::Lifecycle;
+
+/// The primary connection, the configured VPNs and the visible WiFi networks
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct NetworkState {
+    #[serde(flatten)]
+    pub info: NetworkInfo,
+    #[serde(skip)]
+    pub vpn_connections: Vec<VpnConnection>,
+    /// The first wireless device, if there is one
+    #[serde(skip)]
+    pub wifi: Option<WifiState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkInfo {
+    pub interface: String,
+    pub connection_type: ConnectionType,
+    pub ssid: Option<String>,
+    pub signal_strength: Option<u8>,
+    pub ip_address: Option<String>,
+    pub ipv6_address: Option<String>,
+    pub connected: bool,
+    pub vpn_active: bool,
+    pub vpn_name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionType {
+    Wifi,
+    Ethernet,
+    Disconnected,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VpnConnection {
+    pub name: String,
+    pub uuid: String,
+    pub active: bool,
+    /// The saved profile, to activate it
+    pub path: String,
+    /// The active connection, to deactivate it
+    pub active_connection: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WifiState {
+    pub device: String,
+    /// Whether the radio is on
+    pub enabled: bool,
+    pub active_connection: Option<String>,
+    /// Changes whenever a scan finishes
+    pub last_scan: i64,
+    /// One entry per SSID, the connected one first, then by signal
+    pub networks: Vec<WifiNetwork>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WifiNetwork {
+    pub ssid: String,
+    pub signal: u8,
+    pub secured: bool,
+    pub connected: bool,
+    /// The strongest access point with this SSID
+    pub access_point: String,
+    /// A saved profile for this SSID, which needs no password
+    pub saved_connection: Option<String>,
+}
+
+impl Default for NetworkInfo {
+    fn default() -> Self {
+        Self {
+            interface: "none".to_string(),
+            connection_type: ConnectionType::Disconnected,
+            ssid: None,
+            signal_strength: None,
+            ip_address: None,
+            ipv6_address: None,
+            connected: false,
+            vpn_active: false,
+            vpn_name: None,
+        }
+    }
+}
+
+thread_local! {
+    static SERVICE: Service<NetworkState> = Service::new(ServiceSpec {
+        name: "network",
+        fetch,
+        // NetworkManager's signals drive updates; polling only catches
+        // anything they missed
+        interval: Duration::from_secs(30),
+        idle_interval: Duration::from_secs(300),
+        monitor: Some(monitor),
+        on_change: Some(emit_network_event),
+    });
+
+    // Last connection told to event subscribers
+    static LAST_INFO: RefCell<Option<NetworkInfo>> = RefCell::new(None);
+}
+
+/// The network connection
+pub fn service() -> Service<NetworkState> {
+    SERVICE.with(Service::clone)
+}
+
+/// Poll whenever NetworkManager reports a change
+fn monitor(trigger: Trigger<NetworkState>, lifecycle: &Lifecycle) -> Result<()> {
+    let subscription = NetworkManager::system()?.subscribe(move || {
+        trigger.trigger();
+    });
+    lifecycle.on_shutdown(move || subscription.unsubscribe());
+    Ok(())
+}
+
+fn fetch() -> NetworkState {
+    match NetworkManager::system().and_then(|network_manager| network_manager.read()) {
+        Ok(snapshot) => network_state(&snapshot),
+        Err(e) => {
+            debug!("Could not read NetworkManager state: {:#}", e);
+            NetworkState::default()
+        }
+    }
+}
+
+/// Tell event subscribers about the connection, but not about every
+/// access point that comes and goes
+fn emit_network_event(state: &NetworkState) {
+    let info = &state.info;
+    let changed = LAST_INFO.with(|last| {
+        let mut last = last.borrow_mut();
+        let changed = last.as_ref() != Some(info);
+        *last = Some(info.clone());
+        changed
+    });
+    if !changed {
+        return;
+    }
+
+    let connection_type = match info.connection_type {
+        ConnectionType::Wifi => "wifi",
+        ConnectionType::Ethernet => "ethernet",
+        ConnectionType::Disconnected => "disconnected",
+    };
+    EventBus::global().emit(PanelEvent::NetworkChanged {
+        connected: info.connected,
+        connection_type: connection_type.to_string(),
+        ssid: info.ssid.clone(),
+        signal_strength: info.signal_strength,
+        vpn_active: info.vpn_active,
+    });
+}
+
+/// What the panel shows of NetworkManager's state
+pub fn network_state(snapshot: &Snapshot) -> NetworkState {
+    let mut info = NetworkInfo::default();
+
+    // The primary connection is a VPN while it carries the default route;
+    // show the connection underneath it then
+    let primary = snapshot
+        .active_connections
+        .iter()
+        .filter(|active| active.activated && !active.vpn)
+        .max_by_key(|active| snapshot.primary_connection.as_deref() == Some(active.path.as_str()));
+
+    if let Some(active) = primary {
+        info.connected = true;
+        info.connection_type = match active.kind.as_str() {
+            "802-11-wireless" => ConnectionType::Wifi,
+            "802-3-ethernet" => ConnectionType::Ethernet,
+            _ => ConnectionType::Disconnected,
+        };
+
+        let device = snapshot
+            .devices
+            .iter()
+            .find(|device| active.devices.contains(&device.path));
+        if let Some(device) = device {
+            info.interface = device.interface.clone();
+            let access_point = device.access_points.iter().find(|access_point| {
+                device.active_access_point.as_deref() == Some(access_point.path.as_str())
+            });
+            if let Some(access_point) = access_point {
+                info.ssid = Some(access_point.ssid.clone());
+                info.signal_strength = Some(access_point.strength);
+            }
+        }
+        // The profile is usually named after the network
+        if info.connection_type == ConnectionType::Wifi && info.ssid.is_none() {
+            info.ssid = Some(active.id.clone());
+        }
+
+        info.ip_address = active
+            .ip4_addresses
+            .iter()
+            .find(|address| !address.starts_with("169.254."))
+            .cloned();
+        info.ipv6_address = active
+            .ip6_addresses
+            .iter()
+            .find(|address| !address.starts_with("fe80:"))
+            .cloned();
+    }
+
+    if let Some(vpn) = snapshot
+        .active_connections
+        .iter()
+        .find(|active| active.activated && active.vpn)
+    {
+        info.vpn_active = true;
+        info.vpn_name = Some(vpn.id.clone());
+    }
+
+    let vpn_connections = snapshot
+        .connections
+        .iter()
+        .filter(|connection| connection.kind == "vpn" || connection.kind == "wireguard")
+        .map(|connection| {
+            let active_connection = snapshot
+                .active_connections
+                .iter()
+                .find(|active| active.uuid == connection.uuid)
+                .map(|active| active.path.clone());
+            VpnConnection {
+                name: connection.id.clone(),
+                uuid: connection.uuid.clone(),
+                active: active_connection.is_some(),
+                path: connection.path.clone(),
+                active_connection,
+            }
+        })
+        .collect();
+
+    let wifi = snapshot
+        .devices
+        .iter()
+        .find(|device| device.wifi)
+        .map(|device| WifiState {
+            device: device.path.clone(),
+            enabled: snapshot.wireless_enabled,
+            active_connection: device.active_connection.clone(),
+            last_scan: device.last_scan,
+            networks: wifi_networks(snapshot, device),
+        });
+
+    NetworkState {
+        info,
+        vpn_connections,
+        wifi,
+    }
+}
+
+fn wifi_networks(snapshot: &Snapshot, device: &Device) -> Vec<WifiNetwork> {
+    let current_ssid = device
+        .access_points
+        .iter()
+        .find(|access_point| {
+            device.active_access_point.as_deref() == Some(access_point.path.as_str())
+        })
+        .map(|access_point| access_point.ssid.as_str());
+
+    let mut networks: Vec<WifiNetwork> = Vec::new();
+    for access_point in &device.access_points {
+        // Hidden networks have no name to show
+        if access_point.ssid.is_empty() {
+            continue;
+        }
+        if let Some(network) = networks
+            .iter_mut()
+            .find(|network| network.ssid == access_point.ssid)
+        {
+            if access_point.strength > network.signal {
+                network.signal = access_point.strength;
+                network.access_point = access_point.path.clone();
+            }
+            continue;
+        }
+
+        let saved_connection = snapshot
+            .connections
+            .iter()
+            .find(|connection| connection.ssid.as_deref() == Some(access_point.ssid.as_str()))
+            .map(|connection| connection.path.clone());
+        networks.push(WifiNetwork {
+            ssid: access_point.ssid.clone(),
+            signal: access_point.strength,
+            secured: access_point.secured,
+            connected: current_ssid == Some(access_point.ssid.as_str()),
+            access_point: access_point.path.clone(),
+            saved_connection,
+        });
+    }
+
+    networks.sort_by(|a, b| b.connected.cmp(&a.connected).then(b.signal.cmp(&a.signal)));
+    networks
+}
@@ -0,0 +1,224 @@
+use anyhow::{anyhow, Result};
+use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+use tracing::{info, warn};
+
+use super::{Service, ServiceSpec, Trigger};
+use crate::command::Cmd;
+use crate::events::{EventBus, PanelEvent};
+use crate::widgets::Lifecycle;
+
+/// Battery and screen brightness
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PowerState {
+    /// `None` on machines without a battery
+    pub battery: Option<BatteryInfo>,
+    /// Backlight level in percent, `None` without a backlight
+    pub brightness: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatteryInfo {
+    pub percentage: u32,
+    pub charging: bool,
+    pub plugged: bool,
+    pub time_to_empty: Option<String>,
+    pub time_to_full: Option<String>,
+}
+
+thread_local! {
+    static SERVICE: Service<PowerState> = Service::new(ServiceSpec {
+        name: "power",
+        fetch,
+        interval: Duration::from_secs(5),
+        idle_interval: Duration::from_secs(30),
+        monitor: Some(monitor),
+        on_change: Some(emit_battery_event),
+    });
+}
+
+/// The battery and backlight
+pub fn service() -> Service<PowerState> {
+    SERVICE.with(Service::clone)
+}
+
+fn fetch() -> PowerState {
+    PowerState {
+        battery: read_battery(),
+        brightness: read_brightness(),
+    }
+}
+
+/// Tell event subscribers about the battery level
+fn emit_battery_event(state: &PowerState) {
+    if let Some(battery) = &state.battery {
+        EventBus::global().emit(PanelEvent::BatteryChanged {
+            percentage: battery.percentage,
+            charging: battery.charging,
+        });
+    }
+}
+
+/// Poll as soon as the backlight changes, e.g. from brightness keys
+fn monitor(trigger: Trigger<PowerState>, lifecycle: &Lifecycle) -> Result<()> {
+    let brightness_path = find_brightness_file().ok_or_else(|| anyhow!("No brightness file found"))?;
+
+    // Lets the watcher thread exit when the service stops
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_thread = stop.clone();
+    lifecycle.on_shutdown(move || stop.store(true, Ordering::Relaxed));
+
+    thread::spawn(move || {
+        let (watch_tx, watch_rx) = mpsc::channel();
+        let mut watcher = match RecommendedWatcher::new(
+            move |res: Result<Event, notify::Error>| {
+                if let Ok(event) = res {
+                    let _ = watch_tx.send(event);
+                }
+            },
+            Config::default(),
+        ) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                warn!("Failed to create brightness watcher: {}", e);
+                return;
+            }
+        };
+
+        if let Err(e) = watcher.watch(&brightness_path, RecursiveMode::NonRecursive) {
+            warn!("Failed to watch brightness file: {}", e);
+            return;
+        }
+        info!("Watching brightness file: {:?}", brightness_path);
+
+        while !stop_thread.load(Ordering::Relaxed) {
+            let event = match watch_rx.recv_timeout(Duration::from_secs(1)) {
+                Ok(event) => event,
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            };
+            if matches!(event.kind, EventKind::Modify(_)) && !trigger.trigger() {
+                break;
+            }
+        }
+
+        info!("Brightness watcher thread exiting");
+    });
+
+    Ok(())
+}
+
+/// Read the first battery from sysfs
+fn read_battery() -> Option<BatteryInfo> {
+    // Try to find battery in /sys/class/power_supply/
+    let power_supply_path = Path::new("/sys/class/power_supply");
+
+    if !power_supply_path.exists() {
+        return None;
+    }
+
+    // Look for BAT0, BAT1, etc.
+    for entry in fs::read_dir(power_supply_path).ok()? {
+        let entry = entry.ok()?;
+        let name = entry.file_name();
+        let name_str = name.to_str()?;
+
+        if name_str.starts_with("BAT") {
+            let bat_path = entry.path();
+
+            // Read capacity
+            let capacity_path = bat_path.join("capacity");
+            let capacity = fs::read_to_string(capacity_path)
+                .ok()?
+                .trim()
+                .parse::<u32>()
+                .ok()?;
+
+            // Read status
+            let status_path = bat_path.join("status");
+            let status = fs::read_to_string(status_path).ok()?.trim().to_string();
+
+            let charging = status == "Charging";
+            let plugged = status == "Charging" || status == "Full";
+
+            // Try to read time estimates
+            let time_to_empty = if !charging && !plugged {
+                read_time_estimate(&bat_path, "time_to_empty")
+            } else {
+                None
+            };
+
+            let time_to_full = if charging {
+                read_time_estimate(&bat_path, "time_to_full")
+            } else {
+                None
+            };
+
+            return Some(BatteryInfo {
+                percentage: capacity,
+                charging,
+                plugged,
+                time_to_empty,
+                time_to_full,
+            });
+        }
+    }
+
+    None
+}
+
+fn read_time_estimate(bat_path: &Path, file_name: &str) -> Option<String> {
+    let time_path = bat_path.join(file_name);
+    if let Ok(time_str) = fs::read_to_string(time_path) {
+        if let Ok(minutes) = time_str.trim().parse::<u32>() {
+            if minutes > 0 && minutes < 1440 {
+                // Less than 24 hours
+                let hours = minutes / 60;
+                let mins = minutes % 60;
+                if hours > 0 {
+                    return Some(format!("{} hr {} min remaining", hours, mins));
+                } else {
+                    return Some(format!("{} min remaining", mins));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn find_brightness_file() -> Option<PathBuf> {
+    let backlight_dir = Path::new("/sys/class/backlight");
+    fs::read_dir(backlight_dir)
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path().join("brightness"))
+        .find(|path| path.exists())
+}
+
+/// Backlight level from sysfs, or from brightnessctl where sysfs isn't readable
+fn read_brightness() -> Option<u32> {
+    if let Some(brightness_path) = find_brightness_file() {
+        let read = |path: &Path| -> Option<u32> { fs::read_to_string(path).ok()?.trim().parse().ok() };
+        let max_path = brightness_path.with_file_name("max_brightness");
+        if let (Some(brightness), Some(max)) = (read(&brightness_path), read(&max_path)) {
+            if max > 0 {
+                return Some(brightness * 100 / max);
+            }
+        }
+    }
+
+    let query = |arg: &str| -> Option<u32> {
+        let output = Cmd::new("brightnessctl").arg(arg).run_blocking().ok()?;
+        String::from_utf8_lossy(&output.stdout).trim().parse().ok()
+    };
+    match (query("get")?, query("max")?) {
+        (_, 0) => None,
+        (current, max) => Some(current * 100 / max),
+    }
+}
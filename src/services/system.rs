@@ -0,0 +1,162 @@
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::{Service, ServiceSpec};
+
+/// CPU load, temperature and power draw
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SystemStats {
+    /// Share of CPU time spent busy since the previous poll, in percent
+    pub cpu_usage: f32,
+    /// Hottest thermal zone in °C
+    pub temperature: Option<f32>,
+    /// Battery discharge rate in watts
+    pub power_consumption: Option<f32>,
+}
+
+/// Files to read stats from, found once since the hardware doesn't change
+struct StatPaths {
+    thermal_zones: Vec<PathBuf>,
+    battery_power: Option<PathBuf>,
+    // current_now and voltage_now, for batteries without power_now
+    battery_current_voltage: Option<(PathBuf, PathBuf)>,
+}
+
+static PATHS: Lazy<StatPaths> = Lazy::new(StatPaths::find);
+
+// Busy and total CPU time at the previous poll
+static LAST_CPU_TIMES: Mutex<Option<(u64, u64)>> = Mutex::new(None);
+
+thread_local! {
+    static SERVICE: Service<SystemStats> = Service::new(ServiceSpec {
+        name: "system",
+        fetch,
+        interval: Duration::from_secs(3),
+        idle_interval: Duration::from_secs(60),
+        monitor: None,
+        on_change: None,
+    });
+}
+
+/// System load, mostly of interest while the battery popover is open
+pub fn service() -> Service<SystemStats> {
+    SERVICE.with(Service::clone)
+}
+
+fn fetch() -> SystemStats {
+    let cpu_usage = fs::read_to_string("/proc/stat")
+        .ok()
+        .and_then(|stat| parse_cpu_times(&stat))
+        .map(|times| {
+            let mut last = LAST_CPU_TIMES.lock().unwrap();
+            let usage = cpu_usage(*last, times);
+            *last = Some(times);
+            usage
+        })
+        .unwrap_or(0.0);
+
+    let paths = &*PATHS;
+    let temperature = paths
+        .thermal_zones
+        .iter()
+        .filter_map(|path| read_number(path))
+        .map(|millidegrees| millidegrees / 1000.0)
+        .reduce(f32::max);
+
+    let power_consumption = match (&paths.battery_power, &paths.battery_current_voltage) {
+        (Some(power_path), _) => read_number(power_path).map(|microwatts| microwatts / 1_000_000.0),
+        (None, Some((current_path, voltage_path))) => {
+            match (read_number(current_path), read_number(voltage_path)) {
+                (Some(microamps), Some(microvolts)) => {
+                    Some(microamps * microvolts / 1_000_000_000_000.0)
+                }
+                _ => None,
+            }
+        }
+        (None, None) => None,
+    };
+
+    SystemStats {
+        cpu_usage,
+        temperature,
+        power_consumption,
+    }
+}
+
+/// Busy and total jiffies from the aggregate `cpu` line of `/proc/stat`
+pub fn parse_cpu_times(stat: &str) -> Option<(u64, u64)> {
+    let line = stat.lines().find(|line| line.starts_with("cpu "))?;
+    let values: Vec<u64> = line
+        .split_whitespace()
+        .skip(1)
+        .filter_map(|value| value.parse().ok())
+        .collect();
+    if values.len() < 4 {
+        return None;
+    }
+
+    // idle and iowait count as not busy
+    let idle = values[3] + values.get(4).copied().unwrap_or(0);
+    let total: u64 = values.iter().sum();
+    Some((total - idle, total))
+}
+
+/// CPU usage in percent between two readings; without a previous reading,
+/// the average since boot
+pub fn cpu_usage(previous: Option<(u64, u64)>, current: (u64, u64)) -> f32 {
+    let (previous_busy, previous_total) = previous.unwrap_or((0, 0));
+    let busy = current.0.saturating_sub(previous_busy);
+    let total = current.1.saturating_sub(previous_total);
+    if total == 0 {
+        0.0
+    } else {
+        busy as f32 / total as f32 * 100.0
+    }
+}
+
+fn read_number(path: &Path) -> Option<f32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+impl StatPaths {
+    fn find() -> Self {
+        let thermal_zones = fs::read_dir("/sys/class/thermal")
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("thermal_zone"))
+            .map(|entry| entry.path().join("temp"))
+            .filter(|path| path.exists())
+            .collect();
+
+        let mut battery_power = None;
+        let mut battery_current_voltage = None;
+        for entry in fs::read_dir("/sys/class/power_supply").into_iter().flatten().flatten() {
+            if !entry.file_name().to_string_lossy().starts_with("BAT") {
+                continue;
+            }
+            let battery = entry.path();
+            let power = battery.join("power_now");
+            let current = battery.join("current_now");
+            let voltage = battery.join("voltage_now");
+            if power.exists() {
+                battery_power = Some(power);
+                break;
+            }
+            if current.exists() && voltage.exists() {
+                battery_current_voltage = Some((current, voltage));
+                break;
+            }
+        }
+
+        Self {
+            thermal_zones,
+            battery_power,
+            battery_current_voltage,
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use serde_json::Value;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use tracing::info;
+
+use crate::widgets::Lifecycle;
+
+type StateProvider = Rc<dyn Fn() -> Value>;
+
+/// Registry of functions reporting each widget's current state as JSON,
+/// used by the `get` IPC command
+#[derive(Default)]
+pub struct StateRegistry {
+    // Every panel registers its own widgets, so a name can have several
+    // providers; the most recent one answers
+    providers: RefCell<HashMap<String, Vec<(u64, StateProvider)>>>,
+    next_id: Cell<u64>,
+}
+
+thread_local! {
+    static INSTANCE: StateRegistry = StateRegistry::default();
+}
+
+impl StateRegistry {
+    /// Register a widget's state provider until the widget is shut down
+    pub fn register(name: &str, lifecycle: &Lifecycle, provider: impl Fn() -> Value + 'static) {
+        let id = INSTANCE.with(|registry| {
+            let id = registry.next_id.get();
+            registry.next_id.set(id + 1);
+            registry
+                .providers
+                .borrow_mut()
+                .entry(name.to_string())
+                .or_default()
+                .push((id, Rc::new(provider)));
+            id
+        });
+        info!("Registered state provider: {}", name);
+
+        let name = name.to_string();
+        lifecycle.on_shutdown(move || {
+            INSTANCE.with(|registry| {
+                let mut providers = registry.providers.borrow_mut();
+                if let Some(entries) = providers.get_mut(&name) {
+                    entries.retain(|(entry_id, _)| *entry_id != id);
+                    if entries.is_empty() {
+                        providers.remove(&name);
+                    }
+                }
+            });
+        });
+    }
+
+    /// Current state of a widget, or `None` if no widget with this name reports state
+    pub fn get(name: &str) -> Option<Value> {
+        // Release the borrow before calling out, in case the provider
+        // touches the registry
+        let provider = INSTANCE.with(|registry| {
+            registry
+                .providers
+                .borrow()
+                .get(name)
+                .and_then(|entries| entries.last())
+                .map(|(_, provider)| provider.clone())
+        })?;
+        Some(provider())
+    }
+
+    /// Names of widgets that report state
+    pub fn names() -> Vec<String> {
+        INSTANCE.with(|registry| {
+            let mut names: Vec<String> = registry.providers.borrow().keys().cloned().collect();
+            names.sort();
+            names
+        })
+    }
+}
@@ -1,46 +1,35 @@
 // Battery widget for niri-panel
 //
 // This widget displays battery status, system stats, brightness control, and power profiles.
-// Recent performance optimizations:
-// - Reduced brightness monitoring polling frequency (250ms instead of 50ms)
-// - Implemented adaptive polling that reduces frequency when popover is not visible
-// - Added caching for hardware paths and information to reduce filesystem operations
-// - Increased system stats collection interval (5s instead of 2s)
-// - Implemented smart polling that only updates when necessary
+// The battery, brightness and stats come from the shared `services::power` and
+// `services::system` services, which poll once for every panel and slow down
+// while the popover is closed.
 
 use anyhow::Result;
-use glib::timeout_add_seconds_local;
-use gtk4::glib::WeakRef;
+use gtk4::glib::{self, WeakRef};
 use gtk4::prelude::*;
 use gtk4::{
     ApplicationWindow, Box, Button, Image, Label, ListBox, ListBoxRow, Orientation, Popover, Scale,
 };
 use gtk4_layer_shell::LayerShell;
-use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::cell::RefCell;
 use std::fs;
-use std::path::{Path, PathBuf};
+use std::path::Path;
 use std::process::Command;
 use std::rc::Rc;
-use std::sync::mpsc;
-use std::thread;
 use std::time::Duration;
 use tracing::{info, warn};
 
-use crate::widgets::Widget as WidgetTrait;
+use crate::command::{self, Cmd};
+use crate::services::power::{self, BatteryInfo};
+use crate::services::system::{self, SystemStats};
+use crate::state_registry::StateRegistry;
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
 
 pub struct Battery {
     button: Button,
     popover: Popover,
-}
-
-#[derive(Debug)]
-struct BatteryInfo {
-    percentage: u32,
-    charging: bool,
-    plugged: bool,
-    time_to_empty: Option<String>,
-    time_to_full: Option<String>,
+    lifecycle: Lifecycle,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -50,13 +39,6 @@ enum PowerProfile {
     Performance,
 }
 
-#[derive(Debug, Clone)]
-struct SystemStats {
-    cpu_usage: f32,
-    temperature: Option<f32>,
-    power_consumption: Option<f32>,
-}
-
 impl PowerProfile {
     fn to_string(&self) -> &str {
         match self {
@@ -96,14 +78,13 @@ impl Battery {
     /// Creates a new Battery widget
     /// 
     /// This widget shows battery status, brightness controls, and system statistics.
-    /// Performance considerations:
-    /// - Uses adaptive polling to reduce CPU usage
-    /// - Caches hardware information to minimize filesystem operations
-    /// - Only updates UI when visible or when significant changes occur
+    /// Its data comes from services shared with the battery widgets on
+    /// other monitors and with `niri-panel get battery`.
     pub fn new(
         window_weak: WeakRef<ApplicationWindow>,
         active_popovers: Rc<RefCell<i32>>,
     ) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
         let button = Button::new();
         button.add_css_class("battery");
 
@@ -228,12 +209,6 @@ impl Battery {
 
         popover_box.append(&brightness_box);
 
-        // Set initial brightness
-        if let Some(current_brightness) = Self::get_brightness() {
-            brightness_scale.set_value(current_brightness as f64);
-            brightness_value_label.set_text(&format!("{}%", current_brightness));
-        }
-
         // Handle brightness changes from the slider
         let brightness_value_weak = brightness_value_label.downgrade();
         let brightness_updating = std::rc::Rc::new(std::cell::RefCell::new(false));
@@ -256,57 +231,41 @@ impl Battery {
             });
         });
 
-        // Set up brightness monitoring using notify
+        // Follow the shared power service for the battery and brightness;
+        // the backlight is watched so brightness keys show up right away
+        let icon_weak = icon.downgrade();
+        let label_weak = label.downgrade();
+        let status_label_weak = status_label.downgrade();
+        let time_label_weak = time_label.downgrade();
         let brightness_scale_weak = brightness_scale.downgrade();
         let brightness_label_weak = brightness_value_label.downgrade();
-        let brightness_updating_for_monitor = brightness_updating.clone();
-
-        if let Ok(brightness_rx) = Self::setup_brightness_monitor() {
-            info!("Brightness monitoring initialized");
-
-            // Spawn a timeout to check for brightness changes
-            // Increased from 50ms to 250ms to reduce CPU usage significantly
-            glib::timeout_add_local(Duration::from_millis(250), move || {
-                // Track if we've made any updates in this cycle
-                let mut updated = false;
-                
-                // Check if we have any brightness updates (process all available updates)
-                while let Ok(brightness) = brightness_rx.try_recv() {
-                    // Only update if we're not currently updating from the slider
-                    if !*brightness_updating_for_monitor.borrow() {
-                        if let (Some(scale), Some(label)) = (
-                            brightness_scale_weak.upgrade(),
-                            brightness_label_weak.upgrade(),
-                        ) {
-                            // Only update UI if the value has changed (avoid redundant updates)
-                            let current_value = scale.value() as u32;
-                            if current_value != brightness {
-                                scale.set_value(brightness as f64);
-                                label.set_text(&format!("{}%", brightness));
-                                updated = true;
-                            }
-                        }
-                    }
-                }
-                
-                // If the popover isn't visible and we didn't make updates, we can slow down polling
-                // This implements adaptive polling based on activity
-                if !updated {
-                    if let Some(scale) = brightness_scale_weak.upgrade() {
-                        if let Some(parent) = scale.parent() {
-                            if !parent.is_visible() {
-                                // When not visible, check even less frequently
-                                return glib::ControlFlow::Continue;
-                            }
-                        }
-                    }
+        let power = power::service();
+        power.track_popover(&lifecycle, &popover);
+        power.subscribe(&lifecycle, move |state| {
+            if let (Some(icon), Some(label), Some(status), Some(time)) = (
+                icon_weak.upgrade(),
+                label_weak.upgrade(),
+                status_label_weak.upgrade(),
+                time_label_weak.upgrade(),
+            ) {
+                Self::update_battery(&icon, &label, &status, &time, state.battery.as_ref());
+            }
+
+            // Only update if we're not currently updating from the slider
+            if *brightness_updating.borrow() {
+                return;
+            }
+            if let (Some(brightness), Some(scale), Some(label)) = (
+                state.brightness,
+                brightness_scale_weak.upgrade(),
+                brightness_label_weak.upgrade(),
+            ) {
+                if scale.value() as u32 != brightness {
+                    scale.set_value(brightness as f64);
+                    label.set_text(&format!("{}%", brightness));
                 }
-                
-                glib::ControlFlow::Continue
-            });
-        } else {
-            warn!("Failed to set up brightness monitoring");
-        }
+            }
+        });
 
         // Power profiles section
         let separator = gtk4::Separator::new(Orientation::Horizontal);
@@ -323,66 +282,71 @@ impl Battery {
         profiles_list.add_css_class("battery-profiles-list");
         profiles_list.set_selection_mode(gtk4::SelectionMode::None);
 
-        // Check if power-profiles-daemon is available
-        let has_ppd = Self::check_power_profiles_daemon();
-
-        if has_ppd {
-            // Add power profile options
-            for profile in [
-                PowerProfile::PowerSaver,
-                PowerProfile::Balanced,
-                PowerProfile::Performance,
-            ] {
-                let row = ListBoxRow::new();
-                row.add_css_class("battery-profile-row");
-
-                let hbox = Box::new(Orientation::Horizontal, 10);
-                hbox.set_margin_start(5);
-                hbox.set_margin_end(5);
-                hbox.set_margin_top(8);
-                hbox.set_margin_bottom(8);
-
-                let profile_icon = Image::from_icon_name(profile.icon_name());
-                profile_icon.set_pixel_size(16);
-                hbox.append(&profile_icon);
-
-                let profile_label = Label::new(Some(profile.display_name()));
-                profile_label.set_hexpand(true);
-                profile_label.set_halign(gtk4::Align::Start);
-                hbox.append(&profile_label);
-
-                let check_icon = Image::from_icon_name("object-select-symbolic");
-                check_icon.set_pixel_size(16);
-                check_icon.set_visible(false);
-                check_icon.add_css_class("battery-profile-check");
-                hbox.append(&check_icon);
-
-                row.set_child(Some(&hbox));
-
-                // Store profile type in widget name for retrieval
-                row.set_widget_name(profile.to_string());
-
-                profiles_list.append(&row);
+        // Add power profile options, shown once power-profiles-daemon answers
+        for profile in [
+            PowerProfile::PowerSaver,
+            PowerProfile::Balanced,
+            PowerProfile::Performance,
+        ] {
+            let row = ListBoxRow::new();
+            row.add_css_class("battery-profile-row");
+
+            let hbox = Box::new(Orientation::Horizontal, 10);
+            hbox.set_margin_start(5);
+            hbox.set_margin_end(5);
+            hbox.set_margin_top(8);
+            hbox.set_margin_bottom(8);
+
+            let profile_icon = Image::from_icon_name(profile.icon_name());
+            profile_icon.set_pixel_size(16);
+            hbox.append(&profile_icon);
+
+            let profile_label = Label::new(Some(profile.display_name()));
+            profile_label.set_hexpand(true);
+            profile_label.set_halign(gtk4::Align::Start);
+            hbox.append(&profile_label);
+
+            let check_icon = Image::from_icon_name("object-select-symbolic");
+            check_icon.set_pixel_size(16);
+            check_icon.set_visible(false);
+            check_icon.add_css_class("battery-profile-check");
+            hbox.append(&check_icon);
+
+            row.set_child(Some(&hbox));
+
+            // Store profile type in widget name for retrieval
+            row.set_widget_name(profile.to_string());
+
+            profiles_list.append(&row);
+        }
+
+        // Handle profile selection
+        profiles_list.connect_row_activated(move |list, row| {
+            let profile_name = row.widget_name();
+            if let Some(profile) = PowerProfile::from_string(&profile_name) {
+                Self::set_power_profile(profile, list);
             }
+        });
 
-            // Handle profile selection
-            profiles_list.connect_row_activated(move |list, row| {
-                let profile_name = row.widget_name();
-                if let Some(profile) = PowerProfile::from_string(&profile_name) {
-                    Self::set_power_profile(profile);
+        profiles_list.set_visible(false);
+        popover_box.append(&profiles_list);
 
-                    // Update UI to show selected profile
-                    Self::update_profile_selection(list);
-                }
-            });
+        let no_ppd_label = Label::new(Some("Power profiles not available"));
+        no_ppd_label.add_css_class("battery-no-ppd");
+        no_ppd_label.set_halign(gtk4::Align::Start);
+        no_ppd_label.set_visible(false);
+        popover_box.append(&no_ppd_label);
 
-            popover_box.append(&profiles_list);
-        } else {
-            let no_ppd_label = Label::new(Some("Power profiles not available"));
-            no_ppd_label.add_css_class("battery-no-ppd");
-            no_ppd_label.set_halign(gtk4::Align::Start);
-            popover_box.append(&no_ppd_label);
-        }
+        // Check if power-profiles-daemon is available
+        let list_weak = profiles_list.downgrade();
+        let no_ppd_weak = no_ppd_label.downgrade();
+        Cmd::new("powerprofilesctl").arg("version").spawn(move |output| {
+            let has_ppd = command::stdout(output).is_some();
+            if let (Some(list), Some(no_ppd)) = (list_weak.upgrade(), no_ppd_weak.upgrade()) {
+                list.set_visible(has_ppd);
+                no_ppd.set_visible(!has_ppd);
+            }
+        });
 
         // Power settings button
         let settings_button = Button::with_label("Power Settings");
@@ -394,99 +358,25 @@ impl Battery {
 
         popover.set_child(Some(&popover_box));
 
-        // Update battery status immediately
-        Self::update_battery(
-            &icon,
-            &label,
-            &status_label,
-            &time_label,
-            &profiles_list,
-            &cpu_label,
-            &temp_label,
-            &power_label,
-        );
-
-        // Update every 30 seconds for battery, every 2 seconds for stats when visible
-        let icon_weak = icon.downgrade();
-        let label_weak = label.downgrade();
-        let status_label_weak = status_label.downgrade();
-        let time_label_weak = time_label.downgrade();
-        let profiles_list_weak = profiles_list.downgrade();
+        // System stats are only shown in the popover, so they are polled
+        // slowly until it opens
         let cpu_weak = cpu_label.downgrade();
         let temp_weak = temp_label.downgrade();
         let power_weak = power_label.downgrade();
-        let popover_weak = popover.downgrade();
-
-        // System stats update timer (increased from 2s to 5s) for when popover is visible
-        // This significantly reduces CPU usage while still providing reasonable updates
-        let last_stats_update = Rc::new(RefCell::new(std::time::Instant::now()));
-        
-        timeout_add_seconds_local(5, move || {
-            if let Some(popover) = popover_weak.upgrade() {
-                // Only update if popover is visible or it's been a long time since last update
-                let should_update = if popover.is_visible() {
-                    true
-                } else {
-                    // When not visible, only update every 30 seconds
-                    last_stats_update.borrow().elapsed().as_secs() >= 30
-                };
-                
-                if should_update {
-                    if let (
-                        Some(icon),
-                        Some(label),
-                        Some(status),
-                        Some(time),
-                        Some(profiles),
-                        Some(cpu),
-                        Some(temp),
-                        Some(power),
-                    ) = (
-                        icon_weak.upgrade(),
-                        label_weak.upgrade(),
-                        status_label_weak.upgrade(),
-                        time_label_weak.upgrade(),
-                        profiles_list_weak.upgrade(),
-                        cpu_weak.upgrade(),
-                        temp_weak.upgrade(),
-                        power_weak.upgrade(),
-                    ) {
-                        Self::update_battery(
-                            &icon, &label, &status, &time, &profiles, &cpu, &temp, &power,
-                        );
-                        
-                        // Update timestamp after successful update
-                        *last_stats_update.borrow_mut() = std::time::Instant::now();
-                    }
-                }
-                glib::ControlFlow::Continue
-            } else {
-                glib::ControlFlow::Break
+        let system = system::service();
+        system.track_popover(&lifecycle, &popover);
+        system.subscribe(&lifecycle, move |stats| {
+            if let (Some(cpu), Some(temp), Some(power)) =
+                (cpu_weak.upgrade(), temp_weak.upgrade(), power_weak.upgrade())
+            {
+                Self::update_stats(&cpu, &temp, &power, stats);
             }
         });
 
-        // Slow update timer (30s) for battery icon/label
-        let icon_weak2 = icon.downgrade();
-        let label_weak2 = label.downgrade();
-        timeout_add_seconds_local(30, move || {
-            if let (Some(icon), Some(label)) = (icon_weak2.upgrade(), label_weak2.upgrade()) {
-                if let Some(info) = Self::get_battery_info() {
-                    // Update icon and label
-                    let icon_name = Self::get_battery_icon_name(&info);
-                    icon.set_from_icon_name(Some(&icon_name));
-                    label.set_text(&format!("{}%", info.percentage));
-
-                    // Update CSS class for low battery
-                    if info.percentage <= 20 && !info.charging {
-                        label.add_css_class("battery-low");
-                    } else {
-                        label.remove_css_class("battery-low");
-                    }
-                }
-                glib::ControlFlow::Continue
-            } else {
-                glib::ControlFlow::Break
-            }
+        // Report the battery to `niri-panel get battery`; null when there is none
+        StateRegistry::register("battery", &lifecycle, || {
+            serde_json::to_value(power::service().get().and_then(|state| state.battery))
+                .unwrap_or_default()
         });
 
         // Show popover on click
@@ -497,119 +387,11 @@ impl Battery {
             popover_ref.popup();
         });
 
-        Ok(Self { button, popover })
-    }
-
-    fn setup_brightness_monitor() -> Result<mpsc::Receiver<u32>> {
-        let (tx, rx) = mpsc::channel();
-
-        // Find brightness file path
-        let brightness_path = Self::find_brightness_file()
-            .ok_or_else(|| anyhow::anyhow!("No brightness file found"))?;
-
-        // Get parent directory for max_brightness
-        let backlight_dir = brightness_path
-            .parent()
-            .ok_or_else(|| anyhow::anyhow!("Invalid brightness path"))?;
-
-        // Read max brightness once
-        let max_brightness = Self::read_max_brightness(backlight_dir);
-
-        // Clone paths for the thread
-        let brightness_path_clone = brightness_path.clone();
-        let backlight_dir_clone = backlight_dir.to_path_buf();
-
-        thread::spawn(move || {
-            // Create channel for watcher events
-            let (watch_tx, watch_rx) = mpsc::channel();
-
-            // Create a watcher with default config
-            let mut watcher = match RecommendedWatcher::new(
-                move |res: Result<Event, notify::Error>| {
-                    if let Ok(event) = res {
-                        let _ = watch_tx.send(event);
-                    }
-                },
-                Config::default(),
-            ) {
-                Ok(w) => w,
-                Err(e) => {
-                    warn!("Failed to create file watcher: {}", e);
-                    return;
-                }
-            };
-
-            // Watch the brightness file
-            if let Err(e) = watcher.watch(&brightness_path_clone, RecursiveMode::NonRecursive) {
-                warn!("Failed to watch brightness file: {}", e);
-                return;
-            }
-
-            info!("Watching brightness file: {:?}", brightness_path_clone);
-
-            // Also watch max_brightness in case it changes
-            let max_brightness_path = backlight_dir_clone.join("max_brightness");
-            let _ = watcher.watch(&max_brightness_path, RecursiveMode::NonRecursive);
-
-            let mut current_max = max_brightness;
-
-            // Process file change events
-            while let Ok(event) = watch_rx.recv() {
-                match event.kind {
-                    EventKind::Modify(_) => {
-                        // Check which file was modified
-                        for path in &event.paths {
-                            if path.file_name() == Some(std::ffi::OsStr::new("max_brightness")) {
-                                // Max brightness changed, re-read it
-                                if let Ok(content) = fs::read_to_string(path) {
-                                    if let Ok(new_max) = content.trim().parse::<u32>() {
-                                        current_max = new_max;
-                                        info!("Max brightness updated to: {}", current_max);
-                                    }
-                                }
-                            } else if path == &brightness_path_clone {
-                                // Brightness changed
-                                if let Ok(content) = fs::read_to_string(path) {
-                                    if let Ok(brightness) = content.trim().parse::<u32>() {
-                                        let percentage = if current_max > 0 {
-                                            (brightness * 100) / current_max
-                                        } else {
-                                            0
-                                        };
-                                        let _ = tx.send(percentage);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
-
-            warn!("Brightness monitor thread exiting");
-        });
-
-        Ok(rx)
-    }
-
-    fn find_brightness_file() -> Option<PathBuf> {
-        let backlight_dir = Path::new("/sys/class/backlight");
-        if let Ok(entries) = fs::read_dir(backlight_dir) {
-            for entry in entries.flatten() {
-                let brightness_path = entry.path().join("brightness");
-                if brightness_path.exists() {
-                    return Some(brightness_path);
-                }
-            }
-        }
-        None
-    }
-
-    fn read_max_brightness(backlight_path: &Path) -> u32 {
-        fs::read_to_string(backlight_path.join("max_brightness"))
-            .ok()
-            .and_then(|s| s.trim().parse().ok())
-            .unwrap_or(100)
+        Ok(Self {
+            button,
+            popover,
+            lifecycle,
+        })
     }
 
     fn create_stat_label(title: &str, initial_value: &str) -> Box {
@@ -635,14 +417,11 @@ impl Battery {
         label: &Label,
         status_label: &Label,
         time_label: &Label,
-        profiles_list: &ListBox,
-        cpu_box: &Box,
-        temp_box: &Box,
-        power_box: &Box,
+        battery: Option<&BatteryInfo>,
     ) {
-        if let Some(info) = Self::get_battery_info() {
+        if let Some(info) = battery {
             // Update icon based on battery level and charging status
-            let icon_name = Self::get_battery_icon_name(&info);
+            let icon_name = Self::get_battery_icon_name(info);
             icon.set_from_icon_name(Some(&icon_name));
 
             // Update label
@@ -661,9 +440,11 @@ impl Battery {
             // Update time estimate
             let time_text = if info.charging {
                 info.time_to_full
+                    .clone()
                     .unwrap_or_else(|| "Calculating...".to_string())
             } else if !info.plugged {
                 info.time_to_empty
+                    .clone()
                     .unwrap_or_else(|| "Calculating...".to_string())
             } else {
                 String::new()
@@ -684,10 +465,9 @@ impl Battery {
             status_label.set_text("No battery detected");
             time_label.set_visible(false);
         }
+    }
 
-        // Update system stats
-        let stats = Self::get_system_stats();
-
+    fn update_stats(cpu_box: &Box, temp_box: &Box, power_box: &Box, stats: &SystemStats) {
         // Update CPU
         if let Some(value_label) = cpu_box.last_child() {
             if let Some(label) = value_label.downcast_ref::<Label>() {
@@ -716,261 +496,6 @@ impl Battery {
                 }
             }
         }
-
-        // Update power profile selection
-        Self::update_profile_selection(profiles_list);
-    }
-
-    fn get_system_stats() -> SystemStats {
-        // Use static caching to avoid repetitive expensive operations
-        use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-        use std::time::{Duration, Instant};
-        
-        // Cache structures
-        struct CachedStats {
-            thermal_zones: Vec<PathBuf>,
-            bat_power_path: Option<PathBuf>,
-            bat_current_path: Option<PathBuf>,
-            bat_voltage_path: Option<PathBuf>,
-            last_update: Instant,
-            stats: SystemStats,
-        }
-        
-        // Initialize static cache for hardware info that rarely changes
-        static CACHE_INITIALIZED: AtomicBool = AtomicBool::new(false);
-        static LAST_UPDATE_TIME: AtomicU64 = AtomicU64::new(0);
-        
-        thread_local! {
-            static STATS_CACHE: RefCell<Option<CachedStats>> = RefCell::new(None);
-        }
-        
-        // Return value from cache if it's recent enough (< 2 seconds old)
-        let now = Instant::now();
-        let now_secs = now.elapsed().as_secs();
-        let last_update = LAST_UPDATE_TIME.load(Ordering::Relaxed);
-        
-        if CACHE_INITIALIZED.load(Ordering::Relaxed) && (now_secs - last_update < 2) {
-            // Return cached stats if they're fresh enough
-            let mut cached_stats = SystemStats {
-                cpu_usage: 0.0,
-                temperature: None,
-                power_consumption: None,
-            };
-            
-            STATS_CACHE.with(|cache| {
-                if let Some(cache_ref) = &*cache.borrow() {
-                    cached_stats = cache_ref.stats.clone();
-                }
-            });
-            
-            return cached_stats;
-        }
-        
-        // Initialize cache or create new stats
-        let mut stats = SystemStats {
-            cpu_usage: 0.0,
-            temperature: None,
-            power_consumption: None,
-        };
-        
-        // Get CPU usage (simple average from /proc/stat)
-        if let Ok(stat_content) = fs::read_to_string("/proc/stat") {
-            if let Some(cpu_line) = stat_content.lines().next() {
-                let values: Vec<u64> = cpu_line
-                    .split_whitespace()
-                    .skip(1)
-                    .filter_map(|s| s.parse().ok())
-                    .collect();
-
-                if values.len() >= 4 {
-                    let idle = values[3];
-                    let total: u64 = values.iter().sum();
-                    if total > 0 {
-                        stats.cpu_usage = ((total - idle) as f32 / total as f32) * 100.0;
-                    }
-                }
-            }
-        }
-        
-        // Use or initialize cache for temperature and power paths
-        STATS_CACHE.with(|cache| {
-            let mut cache_ref = cache.borrow_mut();
-            
-            // Initialize cache if needed
-            if cache_ref.is_none() {
-                let mut thermal_zones = Vec::new();
-                let mut bat_power_path = None;
-                let mut bat_current_path = None;
-                let mut bat_voltage_path = None;
-                
-                // Find thermal zones
-                let thermal_zone_path = Path::new("/sys/class/thermal");
-                if thermal_zone_path.exists() {
-                    if let Ok(entries) = fs::read_dir(thermal_zone_path) {
-                        for entry in entries.flatten() {
-                            let path = entry.path();
-                            if path
-                                .file_name()
-                                .and_then(|n| n.to_str())
-                                .map(|n| n.starts_with("thermal_zone"))
-                                .unwrap_or(false)
-                            {
-                                let temp_path = path.join("temp");
-                                if temp_path.exists() {
-                                    thermal_zones.push(temp_path);
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                // Find battery power paths
-                let power_supply_path = Path::new("/sys/class/power_supply");
-                if power_supply_path.exists() {
-                    if let Ok(entries) = fs::read_dir(power_supply_path) {
-                        for entry in entries.flatten() {
-                            let name = entry.file_name();
-                            let name_str = name.to_str().unwrap_or("");
-
-                            if name_str.starts_with("BAT") {
-                                let bat_path = entry.path();
-                                
-                                // Check for power_now path
-                                let power_path = bat_path.join("power_now");
-                                if power_path.exists() {
-                                    bat_power_path = Some(power_path);
-                                }
-                                
-                                // Check for current/voltage paths for fallback
-                                let current_path = bat_path.join("current_now");
-                                let voltage_path = bat_path.join("voltage_now");
-                                
-                                if current_path.exists() && voltage_path.exists() {
-                                    bat_current_path = Some(current_path);
-                                    bat_voltage_path = Some(voltage_path);
-                                }
-                                
-                                // We found a battery, no need to check more
-                                if bat_power_path.is_some() || (bat_current_path.is_some() && bat_voltage_path.is_some()) {
-                                    break;
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                // Create the cache
-                *cache_ref = Some(CachedStats {
-                    thermal_zones,
-                    bat_power_path,
-                    bat_current_path,
-                    bat_voltage_path,
-                    last_update: now,
-                    stats: stats.clone(),
-                });
-                
-                CACHE_INITIALIZED.store(true, Ordering::Relaxed);
-            }
-            
-            // Get temperature from cached thermal zones
-            if let Some(cache_data) = &*cache_ref {
-                let mut max_temp: Option<f32> = None;
-                
-                for temp_path in &cache_data.thermal_zones {
-                    if let Ok(temp_str) = fs::read_to_string(temp_path) {
-                        if let Ok(temp) = temp_str.trim().parse::<f32>() {
-                            let temp_celsius = temp / 1000.0;
-                            max_temp = Some(max_temp.unwrap_or(0.0).max(temp_celsius));
-                        }
-                    }
-                }
-                
-                stats.temperature = max_temp;
-                
-                // Get power consumption from cached battery path
-                if let Some(power_path) = &cache_data.bat_power_path {
-                    if let Ok(power_str) = fs::read_to_string(power_path) {
-                        if let Ok(power_uw) = power_str.trim().parse::<f32>() {
-                            stats.power_consumption = Some(power_uw / 1_000_000.0);
-                        }
-                    }
-                } else if let (Some(current_path), Some(voltage_path)) = 
-                           (&cache_data.bat_current_path, &cache_data.bat_voltage_path) {
-                    // Fallback to current/voltage calculation
-                    if let (Ok(current_str), Ok(voltage_str)) = (
-                        fs::read_to_string(current_path),
-                        fs::read_to_string(voltage_path),
-                    ) {
-                        if let (Ok(current_ua), Ok(voltage_uv)) = (
-                            current_str.trim().parse::<f32>(),
-                            voltage_str.trim().parse::<f32>(),
-                        ) {
-                            let power_w = (current_ua * voltage_uv) / 1_000_000_000_000.0;
-                            stats.power_consumption = Some(power_w);
-                        }
-                    }
-                }
-                
-                // Update the cache with new stats
-                if let Some(cache_data) = &mut *cache_ref {
-                    cache_data.stats = stats.clone();
-                    cache_data.last_update = now;
-                }
-            }
-        });
-        
-        // Update the last update time
-        LAST_UPDATE_TIME.store(now_secs, Ordering::Relaxed);
-        
-        stats
-    }
-
-    fn get_brightness() -> Option<u32> {
-        // Try using brightnessctl first
-        if let Ok(output) = Command::new("brightnessctl").args(&["get"]).output() {
-            if let Ok(current) = String::from_utf8_lossy(&output.stdout)
-                .trim()
-                .parse::<u32>()
-            {
-                // Get max brightness
-                if let Ok(max_output) = Command::new("brightnessctl").args(&["max"]).output() {
-                    if let Ok(max) = String::from_utf8_lossy(&max_output.stdout)
-                        .trim()
-                        .parse::<u32>()
-                    {
-                        return Some((current * 100) / max);
-                    }
-                }
-            }
-        }
-
-        // Fallback to sysfs
-        let backlight_path = Path::new("/sys/class/backlight");
-        if backlight_path.exists() {
-            if let Ok(entries) = fs::read_dir(backlight_path) {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    let brightness_path = path.join("brightness");
-                    let max_brightness_path = path.join("max_brightness");
-
-                    if let (Ok(brightness_str), Ok(max_str)) = (
-                        fs::read_to_string(&brightness_path),
-                        fs::read_to_string(&max_brightness_path),
-                    ) {
-                        if let (Ok(brightness), Ok(max)) = (
-                            brightness_str.trim().parse::<u32>(),
-                            max_str.trim().parse::<u32>(),
-                        ) {
-                            if max > 0 {
-                                return Some((brightness * 100) / max);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        None
     }
 
     fn set_brightness(percentage: u32) {
@@ -999,135 +524,63 @@ impl Battery {
         }
     }
 
-    fn check_power_profiles_daemon() -> bool {
-        // Check if power-profiles-daemon is available
-        Command::new("powerprofilesctl")
-            .arg("version")
-            .output()
-            .map(|output| output.status.success())
-            .unwrap_or(false)
-    }
-
-    fn get_current_power_profile() -> Option<PowerProfile> {
-        if let Ok(output) = Command::new("powerprofilesctl").arg("get").output() {
-            let profile_str = String::from_utf8_lossy(&output.stdout);
-            PowerProfile::from_string(&profile_str)
-        } else {
-            None
-        }
+    async fn get_current_power_profile() -> Option<PowerProfile> {
+        let output = command::stdout(Cmd::new("powerprofilesctl").arg("get").run().await)?;
+        PowerProfile::from_string(&output)
     }
 
-    fn set_power_profile(profile: PowerProfile) {
-        let _ = Command::new("powerprofilesctl")
-            .arg("set")
-            .arg(profile.to_string())
-            .spawn();
+    /// Switch profiles, then mark the one actually in effect
+    fn set_power_profile(profile: PowerProfile, profiles_list: &ListBox) {
+        let list_weak = profiles_list.downgrade();
+        Cmd::new("powerprofilesctl")
+            .args(["set", profile.to_string()])
+            .spawn(move |result| {
+                if let Err(e) = result {
+                    warn!("Failed to set power profile: {:#}", e);
+                }
+                if let Some(list) = list_weak.upgrade() {
+                    Self::update_profile_selection(&list);
+                }
+            });
     }
 
     fn update_profile_selection(profiles_list: &ListBox) {
-        if let Some(current_profile) = Self::get_current_power_profile() {
-            let mut index = 0;
-            while let Some(row) = profiles_list.row_at_index(index) {
-                if let Some(child) = row.child() {
-                    if let Some(hbox) = child.downcast_ref::<Box>() {
-                        // Check if this row matches current profile
-                        let row_profile = row.widget_name();
-                        let is_selected = PowerProfile::from_string(&row_profile)
-                            == Some(current_profile.clone());
-
-                        // Show/hide check icon
-                        if let Some(check_icon) = hbox.last_child() {
-                            check_icon.set_visible(is_selected);
-                        }
-
-                        // Update row style
-                        if is_selected {
-                            row.add_css_class("battery-profile-selected");
-                        } else {
-                            row.remove_css_class("battery-profile-selected");
-                        }
-                    }
-                }
-                index += 1;
+        let list_weak = profiles_list.downgrade();
+        glib::MainContext::default().spawn_local(async move {
+            let current_profile = Self::get_current_power_profile().await;
+            if let (Some(profiles_list), Some(current_profile)) =
+                (list_weak.upgrade(), current_profile)
+            {
+                Self::show_profile_selection(&profiles_list, current_profile);
             }
-        }
+        });
     }
 
-    fn get_battery_info() -> Option<BatteryInfo> {
-        // Try to find battery in /sys/class/power_supply/
-        let power_supply_path = Path::new("/sys/class/power_supply");
-
-        if !power_supply_path.exists() {
-            return None;
-        }
-
-        // Look for BAT0, BAT1, etc.
-        for entry in fs::read_dir(power_supply_path).ok()? {
-            let entry = entry.ok()?;
-            let name = entry.file_name();
-            let name_str = name.to_str()?;
-
-            if name_str.starts_with("BAT") {
-                let bat_path = entry.path();
-
-                // Read capacity
-                let capacity_path = bat_path.join("capacity");
-                let capacity = fs::read_to_string(capacity_path)
-                    .ok()?
-                    .trim()
-                    .parse::<u32>()
-                    .ok()?;
-
-                // Read status
-                let status_path = bat_path.join("status");
-                let status = fs::read_to_string(status_path).ok()?.trim().to_string();
-
-                let charging = status == "Charging";
-                let plugged = status == "Charging" || status == "Full";
-
-                // Try to read time estimates
-                let time_to_empty = if !charging && !plugged {
-                    Self::read_time_estimate(&bat_path, "time_to_empty")
-                } else {
-                    None
-                };
-
-                let time_to_full = if charging {
-                    Self::read_time_estimate(&bat_path, "time_to_full")
-                } else {
-                    None
-                };
-
-                return Some(BatteryInfo {
-                    percentage: capacity,
-                    charging,
-                    plugged,
-                    time_to_empty,
-                    time_to_full,
-                });
-            }
-        }
-
-        None
-    }
+    fn show_profile_selection(profiles_list: &ListBox, current_profile: PowerProfile) {
+        let mut index = 0;
+        while let Some(row) = profiles_list.row_at_index(index) {
+            if let Some(child) = row.child() {
+                if let Some(hbox) = child.downcast_ref::<Box>() {
+                    // Check if this row matches current profile
+                    let row_profile = row.widget_name();
+                    let is_selected =
+                        PowerProfile::from_string(&row_profile) == Some(current_profile.clone());
+
+                    // Show/hide check icon
+                    if let Some(check_icon) = hbox.last_child() {
+                        check_icon.set_visible(is_selected);
+                    }
 
-    fn read_time_estimate(bat_path: &Path, file_name: &str) -> Option<String> {
-        let time_path = bat_path.join(file_name);
-        if let Ok(time_str) = fs::read_to_string(time_path) {
-            if let Ok(minutes) = time_str.trim().parse::<u32>() {
-                if minutes > 0 && minutes < 1440 {
-                    // Less than 24 hours
-                    let hours = minutes / 60;
-                    let mins = minutes % 60;
-                    if hours > 0 {
-                        return Some(format!("{} hr {} min remaining", hours, mins));
+                    // Update row style
+                    if is_selected {
+                        row.add_css_class("battery-profile-selected");
                     } else {
-                        return Some(format!("{} min remaining", mins));
+                        row.remove_css_class("battery-profile-selected");
                     }
                 }
             }
+            index += 1;
         }
-        None
     }
 
     fn get_battery_icon_name(info: &BatteryInfo) -> String {
@@ -1172,4 +625,8 @@ impl WidgetTrait for Battery {
     fn popover(&self) -> Option<&Popover> {
         Some(&self.popover)
     }
+
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
 }
\ No newline at end of file
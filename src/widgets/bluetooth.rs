@@ -7,72 +7,83 @@ use gtk4::{
     ScrolledWindow, Separator, Spinner, Switch,
 };
 use gtk4_layer_shell::LayerShell;
-use std::cell::RefCell;
+use serde_json::json;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::process::Command;
 use std::rc::Rc;
 use std::time::Duration;
 use tracing::{error, info, warn};
 
-use crate::widgets::Widget as WidgetTrait;
+use crate::command::{self, Cmd};
+use crate::services::bluetooth::{self, BluetoothDevice, BluetoothState};
+use crate::state_registry::StateRegistry;
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
 
 pub struct Bluetooth {
     button: Button,
     popover: Popover,
+    lifecycle: Lifecycle,
 }
 
-#[derive(Debug, Clone)]
-struct BluetoothDevice {
-    address: String,
-    name: String,
-    icon: String,
-    connected: bool,
-    paired: bool,
-    trusted: bool,
-    blocked: bool,
-    battery_percentage: Option<u8>,
-    device_type: DeviceType,
+/// The widgets showing the adapter, updated on each change the service reports
+struct AdapterView {
+    icon: WeakRef<Image>,
+    status_label: WeakRef<Label>,
+    power_switch: WeakRef<Switch>,
+    scan_button: WeakRef<Button>,
+    device_list: WeakRef<ListBox>,
+    popover: WeakRef<Popover>,
+    // Set while the switch follows the adapter, so its handler leaves the power alone
+    syncing_switch: Cell<bool>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
-enum DeviceType {
-    Computer,
-    Phone,
-    AudioHeadset,
-    AudioHeadphones,
-    AudioSpeaker,
-    Keyboard,
-    Mouse,
-    GameController,
-    Unknown,
-}
+impl AdapterView {
+    fn apply(&self, state: &BluetoothState) {
+        let powered = state.powered;
+        let connected_count = state.devices.iter().filter(|device| device.connected).count();
+
+        if let (Some(icon), Some(label)) = (self.icon.upgrade(), self.status_label.upgrade()) {
+            Bluetooth::update_icon(&icon, powered, connected_count);
+
+            // Update status label
+            if connected_count > 0 {
+                label.set_text(&connected_count.to_string());
+                label.set_visible(true);
+            } else {
+                label.set_visible(false);
+            }
+        }
 
-impl DeviceType {
-    fn from_icon(icon: &str) -> Self {
-        match icon {
-            s if s.contains("computer") => DeviceType::Computer,
-            s if s.contains("phone") => DeviceType::Phone,
-            s if s.contains("headset") => DeviceType::AudioHeadset,
-            s if s.contains("headphones") => DeviceType::AudioHeadphones,
-            s if s.contains("speaker") || s.contains("audio") => DeviceType::AudioSpeaker,
-            s if s.contains("keyboard") => DeviceType::Keyboard,
-            s if s.contains("mouse") => DeviceType::Mouse,
-            s if s.contains("game") || s.contains("controller") => DeviceType::GameController,
-            _ => DeviceType::Unknown,
+        // Follow changes made outside the panel
+        if let Some(power_switch) = self.power_switch.upgrade() {
+            if power_switch.is_active() != powered {
+                self.syncing_switch.set(true);
+                power_switch.set_active(powered);
+                self.syncing_switch.set(false);
+            }
+        }
+        if let Some(scan_button) = self.scan_button.upgrade() {
+            scan_button.set_sensitive(powered);
+        }
+
+        if self
+            .popover
+            .upgrade()
+            .is_some_and(|popover| popover.is_visible())
+        {
+            self.show_devices(state);
         }
     }
 
-    fn icon_name(&self) -> &'static str {
-        match self {
-            DeviceType::Computer => "computer-symbolic",
-            DeviceType::Phone => "phone-symbolic",
-            DeviceType::AudioHeadset => "audio-headset-symbolic",
-            DeviceType::AudioHeadphones => "audio-headphones-symbolic",
-            DeviceType::AudioSpeaker => "audio-speakers-symbolic",
-            DeviceType::Keyboard => "input-keyboard-symbolic",
-            DeviceType::Mouse => "input-mouse-symbolic",
-            DeviceType::GameController => "input-gaming-symbolic",
-            DeviceType::Unknown => "bluetooth-symbolic",
+    fn show_devices(&self, state: &BluetoothState) {
+        let Some(list) = self.device_list.upgrade() else {
+            return;
+        };
+        if state.powered {
+            Bluetooth::update_device_list(&list, &state.devices);
+        } else {
+            Bluetooth::show_powered_off(&list);
         }
     }
 }
@@ -82,6 +93,7 @@ impl Bluetooth {
         window_weak: WeakRef<ApplicationWindow>,
         active_popovers: Rc<RefCell<i32>>,
     ) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
         let button = Button::new();
         button.add_css_class("bluetooth");
 
@@ -201,6 +213,17 @@ impl Bluetooth {
         // Check if Bluetooth is available
         let bluetooth_available = Self::check_bluetooth_available();
 
+        let view = Rc::new(AdapterView {
+            icon: icon.downgrade(),
+            status_label: status_label.downgrade(),
+            power_switch: power_switch.downgrade(),
+            scan_button: scan_button.downgrade(),
+            device_list: device_list.downgrade(),
+            popover: popover.downgrade(),
+            syncing_switch: Cell::new(false),
+        });
+        let service = bluetooth::service();
+
         if !bluetooth_available {
             // Show error state
             power_switch.set_sensitive(false);
@@ -216,53 +239,29 @@ impl Bluetooth {
 
             icon.add_css_class("bluetooth-disabled");
         } else {
-            // Set initial Bluetooth state
-            let is_powered = Self::get_bluetooth_powered();
-            power_switch.set_active(is_powered);
-            Self::update_icon(&icon, is_powered, 0);
-
-            // Load initial devices
-            if is_powered {
-                Self::update_device_list(&device_list);
-            }
-
             // Handle power toggle
-            let icon_weak = icon.downgrade();
-            let device_list_weak = device_list.downgrade();
-            let scan_button_weak = scan_button.downgrade();
+            let view_power = view.clone();
             power_switch.connect_state_set(move |_switch, state| {
-                Self::set_bluetooth_power(state);
+                // The switch is only following the adapter
+                if view_power.syncing_switch.get() {
+                    return glib::Propagation::Proceed;
+                }
+
+                // Read the adapter again once it has switched
+                Self::run_bluetoothctl(&["power", if state { "on" } else { "off" }], || {
+                    bluetooth::service().refresh()
+                });
 
                 if let (Some(icon), Some(list), Some(scan)) = (
-                    icon_weak.upgrade(),
-                    device_list_weak.upgrade(),
-                    scan_button_weak.upgrade(),
+                    view_power.icon.upgrade(),
+                    view_power.device_list.upgrade(),
+                    view_power.scan_button.upgrade(),
                 ) {
                     Self::update_icon(&icon, state, 0);
                     scan.set_sensitive(state);
 
-                    if state {
-                        // Wait a moment for Bluetooth to power on
-                        let list_weak = list.downgrade();
-                        timeout_add_local(Duration::from_millis(500), move || {
-                            if let Some(list) = list_weak.upgrade() {
-                                Self::update_device_list(&list);
-                            }
-                            glib::ControlFlow::Break
-                        });
-                    } else {
-                        // Clear device list
-                        while let Some(child) = list.first_child() {
-                            list.remove(&child);
-                        }
-
-                        let disabled_row = ListBoxRow::new();
-                        let disabled_label = Label::new(Some("Bluetooth is turned off"));
-                        disabled_label.add_css_class("dim-label");
-                        disabled_label.set_margin_top(20);
-                        disabled_label.set_margin_bottom(20);
-                        disabled_row.set_child(Some(&disabled_label));
-                        list.append(&disabled_row);
+                    if !state {
+                        Self::show_powered_off(&list);
                     }
                 }
 
@@ -270,15 +269,12 @@ impl Bluetooth {
             });
 
             // Handle scan button
-            let device_list_for_scan = device_list.downgrade();
             let spinner_weak = scan_spinner.downgrade();
             let scan_button_weak = scan_button.downgrade();
             scan_button.connect_clicked(move |_| {
-                if let (Some(list), Some(spinner), Some(button)) = (
-                    device_list_for_scan.upgrade(),
-                    spinner_weak.upgrade(),
-                    scan_button_weak.upgrade(),
-                ) {
+                if let (Some(spinner), Some(button)) =
+                    (spinner_weak.upgrade(), scan_button_weak.upgrade())
+                {
                     // Show spinner and disable button
                     spinner.set_visible(true);
                     spinner.start();
@@ -288,16 +284,13 @@ impl Bluetooth {
                     Self::start_device_scan();
 
                     // Update list after a delay
-                    let list_weak = list.downgrade();
                     let spinner_weak2 = spinner.downgrade();
                     let button_weak = button.downgrade();
                     timeout_add_local(Duration::from_secs(10), move || {
-                        if let (Some(list), Some(spinner), Some(button)) = (
-                            list_weak.upgrade(),
-                            spinner_weak2.upgrade(),
-                            button_weak.upgrade(),
-                        ) {
-                            Self::update_device_list(&list);
+                        if let (Some(spinner), Some(button)) =
+                            (spinner_weak2.upgrade(), button_weak.upgrade())
+                        {
+                            bluetooth::service().refresh();
                             spinner.stop();
                             spinner.set_visible(false);
                             button.set_sensitive(true);
@@ -307,43 +300,39 @@ impl Bluetooth {
                 }
             });
 
-            // Set up periodic updates when popover is visible
-            let device_list_weak = device_list.downgrade();
-            let icon_weak = icon.downgrade();
-            let status_label_weak = status_label.downgrade();
-            let popover_weak = popover.downgrade();
-            timeout_add_local(Duration::from_secs(2), move || {
-                if let Some(popover) = popover_weak.upgrade() {
-                    if popover.is_visible() {
-                        if let Some(list) = device_list_weak.upgrade() {
-                            Self::update_device_list(&list);
-                        }
-                    }
-
-                    // Always update icon to show connection status
-                    if let (Some(icon), Some(label)) =
-                        (icon_weak.upgrade(), status_label_weak.upgrade())
-                    {
-                        let is_powered = Self::get_bluetooth_powered();
-                        let connected_count = Self::get_connected_device_count();
-                        Self::update_icon(&icon, is_powered, connected_count);
-
-                        // Update status label
-                        if connected_count > 0 {
-                            label.set_text(&connected_count.to_string());
-                            label.set_visible(true);
-                        } else {
-                            label.set_visible(false);
-                        }
-                    }
-
-                    glib::ControlFlow::Continue
-                } else {
-                    glib::ControlFlow::Break
+            // Follow the shared bluetooth service; it polls faster while the
+            // popover is open, and the device list is only rebuilt then
+            service.track_popover(&lifecycle, &popover);
+            let view_service = view.clone();
+            service.subscribe(&lifecycle, move |state| view_service.apply(state));
+
+            // Show the last known devices right away; the service refreshes on show
+            let service_show = service.clone();
+            popover.connect_show(move |_| {
+                if let Some(state) = service_show.get() {
+                    view.show_devices(&state);
                 }
             });
         }
 
+        // Report the adapter and connected devices to `niri-panel get bluetooth`
+        StateRegistry::register("bluetooth", &lifecycle, move || {
+            let state = service.get().unwrap_or(BluetoothState {
+                available: bluetooth_available,
+                ..Default::default()
+            });
+            let devices: Vec<&BluetoothDevice> = state
+                .devices
+                .iter()
+                .filter(|device| device.connected)
+                .collect();
+            json!({
+                "available": state.available,
+                "powered": state.powered,
+                "devices": devices,
+            })
+        });
+
         // Handle Escape key
         let escape_controller = gtk4::EventControllerKey::new();
         let popover_weak_escape = popover.downgrade();
@@ -365,163 +354,58 @@ impl Bluetooth {
             popover_ref.popup();
         });
 
-        Ok(Self { button, popover })
+        Ok(Self {
+            button,
+            popover,
+            lifecycle,
+        })
     }
 
     fn check_bluetooth_available() -> bool {
         // Check if bluetoothctl is available
-        Command::new("which")
-            .arg("bluetoothctl")
-            .output()
-            .map(|output| output.status.success())
-            .unwrap_or(false)
-    }
-
-    fn get_bluetooth_powered() -> bool {
-        if let Ok(output) = Command::new("bluetoothctl").arg("show").output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            output_str.lines().any(|line| line.trim() == "Powered: yes")
-        } else {
-            false
-        }
+        command::in_path("bluetoothctl")
     }
 
-    fn set_bluetooth_power(enable: bool) {
-        let power_cmd = if enable { "on" } else { "off" };
-        let _ = Command::new("bluetoothctl")
-            .args(&["power", power_cmd])
-            .spawn();
-    }
-
-    fn get_connected_device_count() -> usize {
-        if let Ok(output) = Command::new("bluetoothctl").arg("devices").output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            let mut connected_count = 0;
-
-            for line in output_str.lines() {
-                if let Some(address) = line.split_whitespace().nth(1) {
-                    if Self::is_device_connected(address) {
-                        connected_count += 1;
-                    }
+    /// Run a bluetoothctl command in the background, logging failures
+    fn run_bluetoothctl(args: &[&str], done: impl FnOnce() + 'static) {
+        let description = args.join(" ");
+        Cmd::new("bluetoothctl")
+            .args(args)
+            .timeout(Duration::from_secs(30))
+            .spawn(move |result| {
+                match result {
+                    Ok(output) if !output.status.success() => warn!(
+                        "bluetoothctl {} failed: {}",
+                        description,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    ),
+                    Ok(_) => {}
+                    Err(e) => warn!("bluetoothctl {} failed: {:#}", description, e),
                 }
-            }
-
-            connected_count
-        } else {
-            0
-        }
+                done();
+            });
     }
 
-    fn is_device_connected(address: &str) -> bool {
-        if let Ok(output) = Command::new("bluetoothctl")
-            .args(&["info", address])
-            .output()
-        {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            output_str
-                .lines()
-                .any(|line| line.trim() == "Connected: yes")
-        } else {
-            false
+    fn show_powered_off(list: &ListBox) {
+        while let Some(child) = list.first_child() {
+            list.remove(&child);
         }
-    }
-
-    fn get_bluetooth_devices() -> Vec<BluetoothDevice> {
-        let mut devices = Vec::new();
-
-        // Get all devices
-        if let Ok(output) = Command::new("bluetoothctl").arg("devices").output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-
-            for line in output_str.lines() {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 3 && parts[0] == "Device" {
-                    let address = parts[1].to_string();
-                    let name = parts[2..].join(" ");
 
-                    // Get detailed info for each device
-                    if let Some(device) = Self::get_device_info(&address, name) {
-                        devices.push(device);
-                    }
-                }
-            }
-        }
-
-        // Sort: connected first, then paired, then by name
-        devices.sort_by(|a, b| match (a.connected, b.connected) {
-            (true, false) => std::cmp::Ordering::Less,
-            (false, true) => std::cmp::Ordering::Greater,
-            _ => match (a.paired, b.paired) {
-                (true, false) => std::cmp::Ordering::Less,
-                (false, true) => std::cmp::Ordering::Greater,
-                _ => a.name.cmp(&b.name),
-            },
-        });
-
-        devices
-    }
-
-    fn get_device_info(address: &str, name: String) -> Option<BluetoothDevice> {
-        if let Ok(output) = Command::new("bluetoothctl")
-            .args(&["info", address])
-            .output()
-        {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-
-            let mut device = BluetoothDevice {
-                address: address.to_string(),
-                name,
-                icon: String::new(),
-                connected: false,
-                paired: false,
-                trusted: false,
-                blocked: false,
-                battery_percentage: None,
-                device_type: DeviceType::Unknown,
-            };
-
-            for line in output_str.lines() {
-                let line = line.trim();
-                if line.starts_with("Name:") {
-                    device.name = line.split(':').nth(1).unwrap_or("").trim().to_string();
-                } else if line.starts_with("Icon:") {
-                    device.icon = line.split(':').nth(1).unwrap_or("").trim().to_string();
-                    device.device_type = DeviceType::from_icon(&device.icon);
-                } else if line == "Connected: yes" {
-                    device.connected = true;
-                } else if line == "Paired: yes" {
-                    device.paired = true;
-                } else if line == "Trusted: yes" {
-                    device.trusted = true;
-                } else if line == "Blocked: yes" {
-                    device.blocked = true;
-                } else if line.starts_with("Battery Percentage:") {
-                    if let Some(value) = line.split(':').nth(1) {
-                        if let Some(percentage_str) = value.trim().strip_suffix("%)") {
-                            if let Some(percentage_str) = percentage_str.strip_prefix("0x") {
-                                if let Ok(hex_val) = u8::from_str_radix(percentage_str, 16) {
-                                    device.battery_percentage = Some(hex_val);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-
-            Some(device)
-        } else {
-            None
-        }
+        let disabled_row = ListBoxRow::new();
+        let disabled_label = Label::new(Some("Bluetooth is turned off"));
+        disabled_label.add_css_class("dim-label");
+        disabled_label.set_margin_top(20);
+        disabled_label.set_margin_bottom(20);
+        disabled_row.set_child(Some(&disabled_label));
+        list.append(&disabled_row);
     }
 
-    fn update_device_list(device_list: &ListBox) {
+    fn update_device_list(device_list: &ListBox, devices: &[BluetoothDevice]) {
         // Clear existing items
         while let Some(child) = device_list.first_child() {
             device_list.remove(&child);
         }
 
-        let devices = Self::get_bluetooth_devices();
-
         if devices.is_empty() {
             let empty_row = ListBoxRow::new();
             let empty_label = Label::new(Some("No devices found"));
@@ -726,31 +610,29 @@ impl Bluetooth {
     }
 
     fn start_device_scan() {
-        let _ = Command::new("bluetoothctl").args(&["scan", "on"]).spawn();
+        // Newer bluetoothctl keeps scanning until it exits, which the timeout takes care of
+        Cmd::new("bluetoothctl")
+            .args(["scan", "on"])
+            .timeout(Duration::from_secs(10))
+            .spawn(|_| {});
 
         // Stop scan after 10 seconds
         timeout_add_local(Duration::from_secs(10), || {
-            let _ = Command::new("bluetoothctl").args(&["scan", "off"]).spawn();
+            Self::run_bluetoothctl(&["scan", "off"], || {});
             glib::ControlFlow::Break
         });
     }
 
     fn connect_device(address: &str) {
-        let _ = Command::new("bluetoothctl")
-            .args(&["connect", address])
-            .spawn();
+        Self::run_bluetoothctl(&["connect", address], || {});
     }
 
     fn disconnect_device(address: &str) {
-        let _ = Command::new("bluetoothctl")
-            .args(&["disconnect", address])
-            .spawn();
+        Self::run_bluetoothctl(&["disconnect", address], || {});
     }
 
     fn pair_device(address: &str) {
-        let _ = Command::new("bluetoothctl")
-            .args(&["pair", address])
-            .spawn();
+        Self::run_bluetoothctl(&["pair", address], || {});
     }
 
     fn show_device_menu(button: &Button, address: &str, trusted: bool) {
@@ -775,15 +657,8 @@ impl Bluetooth {
         let address_clone = address.to_string();
         let popover_weak = popover.downgrade();
         trust_button.connect_clicked(move |_| {
-            if trusted {
-                let _ = Command::new("bluetoothctl")
-                    .args(&["untrust", &address_clone])
-                    .spawn();
-            } else {
-                let _ = Command::new("bluetoothctl")
-                    .args(&["trust", &address_clone])
-                    .spawn();
-            }
+            let action = if trusted { "untrust" } else { "trust" };
+            Self::run_bluetoothctl(&[action, &address_clone], || {});
 
             if let Some(popover) = popover_weak.upgrade() {
                 popover.popdown();
@@ -798,9 +673,7 @@ impl Bluetooth {
         let address_clone = address.to_string();
         let popover_weak = popover.downgrade();
         remove_button.connect_clicked(move |_| {
-            let _ = Command::new("bluetoothctl")
-                .args(&["remove", &address_clone])
-                .spawn();
+            Self::run_bluetoothctl(&["remove", &address_clone], || {});
 
             if let Some(popover) = popover_weak.upgrade() {
                 popover.popdown();
@@ -840,4 +713,8 @@ impl WidgetTrait for Bluetooth {
     fn popover(&self) -> Option<&Popover> {
         Some(&self.popover)
     }
+
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
 }
@@ -1,6 +1,5 @@
 use anyhow::Result;
 use chrono::{Datelike, Local, NaiveDate};
-use glib::timeout_add_seconds_local;
 use gtk4::glib::WeakRef;
 use gtk4::prelude::*;
 use gtk4::{
@@ -16,7 +15,7 @@ use std::path::PathBuf;
 use std::rc::Rc;
 use tracing::{info, warn};
 
-use crate::widgets::Widget as WidgetTrait;
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct TodoItem {
@@ -141,6 +140,7 @@ impl TodoStore {
 pub struct Clock {
     button: Button,
     popover: Popover,
+    lifecycle: Lifecycle,
 }
 
 impl Clock {
@@ -149,6 +149,7 @@ impl Clock {
         window_weak: WeakRef<ApplicationWindow>,
         active_popovers: Rc<RefCell<i32>>,
     ) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
         let button = Button::new();
         button.add_css_class("clock");
 
@@ -304,7 +305,7 @@ impl Clock {
         // Update every second
         let label_weak = label.downgrade();
         let format_for_timer = format.to_string();
-        timeout_add_seconds_local(1, move || {
+        lifecycle.timeout_add_seconds_local(1, move || {
             if let Some(label) = label_weak.upgrade() {
                 Self::update_time(&label, &format_for_timer);
                 glib::ControlFlow::Continue
@@ -494,7 +495,11 @@ impl Clock {
             popover_ref.popup();
         });
 
-        Ok(Self { button, popover })
+        Ok(Self {
+            button,
+            popover,
+            lifecycle,
+        })
     }
 
     fn update_todo_lists(
@@ -836,4 +841,8 @@ impl WidgetTrait for Clock {
     fn popover(&self) -> Option<&Popover> {
         Some(&self.popover)
     }
+
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
 }
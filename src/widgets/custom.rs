@@ -0,0 +1,496 @@
+use anyhow::{Context, Result};
+use gtk4::glib;
+use gtk4::prelude::*;
+use gtk4::{Box, Button, Image, Label, Orientation};
+use serde::Deserialize;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Stdio};
+use std::rc::Rc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::command::{self, Cmd};
+use crate::config::CustomWidgetConfig;
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
+
+/// A widget showing the output of a user-configured command
+pub struct Custom {
+    button: Button,
+    lifecycle: Lifecycle,
+}
+
+/// What a command prints: a JSON object, or plain text used as `text`
+#[derive(Debug, Clone, Default, Deserialize)]
+struct CustomOutput {
+    #[serde(default)]
+    text: String,
+    tooltip: Option<String>,
+    class: Option<CustomClass>,
+    icon: Option<String>,
+}
+
+/// `class` may be a single CSS class or a list of them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum CustomClass {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl CustomOutput {
+    fn parse(output: &str) -> Self {
+        let output = output.trim();
+        if output.starts_with('{') {
+            match serde_json::from_str(output) {
+                Ok(parsed) => return parsed,
+                Err(e) => debug!("Custom widget output is not valid JSON, showing it as text: {}", e),
+            }
+        }
+
+        Self {
+            text: output.lines().next().unwrap_or_default().to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn classes(&self) -> Vec<String> {
+        match &self.class {
+            Some(CustomClass::One(class)) => vec![class.clone()],
+            Some(CustomClass::Many(classes)) => classes.clone(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl Custom {
+    pub fn new(config: &CustomWidgetConfig) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
+
+        let button = Button::new();
+        button.add_css_class("custom");
+        button.add_css_class(&format!("custom-{}", config.name));
+        // Hidden until the command prints something
+        button.set_visible(false);
+
+        let container = Box::new(Orientation::Horizontal, 5);
+        let icon = Image::new();
+        icon.set_visible(false);
+        let label = Label::new(None);
+        label.add_css_class("custom-label");
+        container.append(&icon);
+        container.append(&label);
+        button.set_child(Some(&container));
+
+        // Classes set by the last output, removed again on the next one
+        let output_classes: RefCell<Vec<String>> = RefCell::new(Vec::new());
+
+        // Show what the command prints; it is shared with this widget on
+        // other panels
+        let button_weak = button.downgrade();
+        let command = CustomCommand::subscribe(config, &lifecycle, move |output| {
+            if let Some(button) = button_weak.upgrade() {
+                Self::update(&button, &icon, &label, &output_classes, output);
+            }
+        })?;
+
+        // Left click
+        if let Some(on_click) = config.on_click.clone() {
+            let command = command.clone();
+            button.connect_clicked(move |_| {
+                command.run_action(&on_click);
+            });
+        }
+
+        // Scroll up and down
+        if config.on_scroll_up.is_some() || config.on_scroll_down.is_some() {
+            let scroll_controller =
+                gtk4::EventControllerScroll::new(gtk4::EventControllerScrollFlags::VERTICAL);
+            let on_scroll_up = config.on_scroll_up.clone();
+            let on_scroll_down = config.on_scroll_down.clone();
+            scroll_controller.connect_scroll(move |_, _, dy| {
+                let action = if dy < 0.0 {
+                    on_scroll_up.as_ref()
+                } else {
+                    on_scroll_down.as_ref()
+                };
+                if let Some(action) = action {
+                    command.run_action(action);
+                }
+                glib::Propagation::Stop
+            });
+            button.add_controller(scroll_controller);
+        }
+
+        info!("Created custom widget {}", config.name);
+
+        Ok(Self { button, lifecycle })
+    }
+
+    fn update(
+        button: &Button,
+        icon: &Image,
+        label: &Label,
+        output_classes: &RefCell<Vec<String>>,
+        output: &CustomOutput,
+    ) {
+        label.set_text(&output.text);
+        label.set_visible(!output.text.is_empty());
+
+        match &output.icon {
+            Some(icon_name) => {
+                icon.set_from_icon_name(Some(icon_name));
+                icon.set_visible(true);
+            }
+            None => icon.set_visible(false),
+        }
+
+        button.set_tooltip_text(output.tooltip.as_deref());
+
+        let mut classes = output_classes.borrow_mut();
+        for class in classes.iter() {
+            button.remove_css_class(class);
+        }
+        *classes = output.classes();
+        for class in classes.iter() {
+            button.add_css_class(class);
+        }
+
+        // An empty output hides the widget
+        button.set_visible(!output.text.is_empty() || output.icon.is_some());
+    }
+
+    pub fn widget(&self) -> &Button {
+        &self.button
+    }
+}
+
+impl WidgetTrait for Custom {
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
+}
+
+/// How long a click or scroll command may run before it is killed
+const ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// First delay before restarting a continuous command that exited; doubled
+/// after each exit
+const RESTART_DELAY_MIN: Duration = Duration::from_secs(1);
+const RESTART_DELAY_MAX: Duration = Duration::from_secs(60);
+
+type Subscriber = Rc<dyn Fn(&CustomOutput)>;
+
+/// A custom widget's command, shared by the widget on every panel. Interval
+/// commands run once per tick however many panels show them; continuous
+/// commands run while any panel shows them, and are restarted with backoff
+/// when they exit.
+struct CustomCommand {
+    name: String,
+    exec: String,
+    interval: Option<u32>,
+    subscribers: RefCell<Vec<(u64, Subscriber)>>,
+    next_id: Cell<u64>,
+    // The last output, for widgets added later
+    output: RefCell<Option<CustomOutput>>,
+    // The interval run in progress, and whether to run again once it's done
+    task: RefCell<Option<glib::JoinHandle<()>>>,
+    rerun: Cell<bool>,
+    timer: RefCell<Option<glib::SourceId>>,
+    // The continuous command
+    child: RefCell<Option<Child>>,
+    // Bumped on every start and stop, so a stale run's output is ignored
+    generation: Cell<u64>,
+    restart: RefCell<Option<glib::SourceId>>,
+    restart_delay: Cell<Duration>,
+}
+
+thread_local! {
+    // Running commands by widget name, command and interval
+    static COMMANDS: RefCell<HashMap<(String, String, Option<u32>), Rc<CustomCommand>>> =
+        RefCell::new(HashMap::new());
+}
+
+impl CustomCommand {
+    /// Call `callback` with each of the command's outputs until the widget
+    /// is shut down, starting with the last one. The first widget starts
+    /// the command, and the last one stops it.
+    fn subscribe(
+        config: &CustomWidgetConfig,
+        lifecycle: &Lifecycle,
+        callback: impl Fn(&CustomOutput) + 'static,
+    ) -> Result<Rc<Self>> {
+        let key = (config.name.clone(), config.exec.clone(), config.interval);
+        let existing = COMMANDS.with(|commands| commands.borrow().get(&key).cloned());
+        let command = match existing {
+            Some(command) => command,
+            None => {
+                let command = Rc::new(Self {
+                    name: config.name.clone(),
+                    exec: config.exec.clone(),
+                    interval: config.interval,
+                    subscribers: RefCell::new(Vec::new()),
+                    next_id: Cell::new(0),
+                    output: RefCell::new(None),
+                    task: RefCell::new(None),
+                    rerun: Cell::new(false),
+                    timer: RefCell::new(None),
+                    child: RefCell::new(None),
+                    generation: Cell::new(0),
+                    restart: RefCell::new(None),
+                    restart_delay: Cell::new(RESTART_DELAY_MIN),
+                });
+                command.start()?;
+                COMMANDS.with(|commands| {
+                    commands.borrow_mut().insert(key.clone(), command.clone())
+                });
+                command
+            }
+        };
+
+        if let Some(output) = command.output.borrow().clone() {
+            callback(&output);
+        }
+
+        let id = command.next_id.get();
+        command.next_id.set(id + 1);
+        command.subscribers.borrow_mut().push((id, Rc::new(callback)));
+
+        let command_shutdown = command.clone();
+        lifecycle.on_shutdown(move || {
+            let command = command_shutdown;
+            let last = {
+                let mut subscribers = command.subscribers.borrow_mut();
+                subscribers.retain(|(subscriber_id, _)| *subscriber_id != id);
+                subscribers.is_empty()
+            };
+            if last {
+                command.stop();
+                COMMANDS.with(|commands| {
+                    let mut commands = commands.borrow_mut();
+                    if commands.get(&key).is_some_and(|c| Rc::ptr_eq(c, &command)) {
+                        commands.remove(&key);
+                    }
+                });
+            }
+        });
+
+        Ok(command)
+    }
+
+    fn start(self: &Rc<Self>) -> Result<()> {
+        let generation = self.generation.get() + 1;
+        self.generation.set(generation);
+
+        let Some(interval) = self.interval else {
+            return self.start_continuous(generation);
+        };
+
+        self.run();
+        let command_weak = Rc::downgrade(self);
+        let source_id = glib::timeout_add_seconds_local(interval, move || {
+            match command_weak.upgrade() {
+                Some(command) => {
+                    command.run();
+                    glib::ControlFlow::Continue
+                }
+                None => glib::ControlFlow::Break,
+            }
+        });
+        *self.timer.borrow_mut() = Some(source_id);
+        Ok(())
+    }
+
+    /// Run an interval command in the background. If the last run is still
+    /// going, run again once it finishes instead.
+    fn run(self: &Rc<Self>) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        if self.task.borrow().is_some() {
+            debug!("Custom command still running, running it again afterwards: {}", self.exec);
+            self.rerun.set(true);
+            return;
+        }
+
+        // A run may take up to its interval, and is killed after that
+        let timeout = Duration::from_secs(interval.into()).max(command::DEFAULT_TIMEOUT);
+        let generation = self.generation.get();
+        let command_weak = Rc::downgrade(self);
+        let handle = Cmd::new("sh")
+            .arg("-c")
+            .arg(&self.exec)
+            .timeout(timeout)
+            .spawn(move |result| {
+                let Some(command) = command_weak.upgrade() else {
+                    return;
+                };
+                if command.generation.get() != generation {
+                    return;
+                }
+                command.task.borrow_mut().take();
+
+                match result {
+                    Ok(output) => {
+                        if !output.status.success() {
+                            debug!("Custom command `{}` exited with {}", command.exec, output.status);
+                        }
+                        let output = String::from_utf8_lossy(&output.stdout);
+                        command.dispatch(CustomOutput::parse(&output));
+                    }
+                    Err(e) => warn!("Failed to run custom command `{}`: {:#}", command.exec, e),
+                }
+
+                if command.rerun.replace(false) {
+                    command.run();
+                }
+            });
+        *self.task.borrow_mut() = Some(handle);
+    }
+
+    /// Start a command that keeps printing outputs, one per line
+    fn start_continuous(self: &Rc<Self>, generation: u64) -> Result<()> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.exec)
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start custom command `{}`", self.exec))?;
+        let stdout = child.stdout.take().context("Custom command has no stdout")?;
+        *self.child.borrow_mut() = Some(child);
+
+        // Every line is a new output, followed by `None` once it exits
+        let (sender, receiver) = glib::MainContext::channel(glib::Priority::DEFAULT);
+        let name = self.name.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if sender.send(Some(line)).is_err() {
+                    return;
+                }
+            }
+            debug!("Custom widget {} command closed its output", name);
+            let _ = sender.send(None);
+        });
+
+        let command_weak = Rc::downgrade(self);
+        receiver.attach(None, move |line: Option<String>| {
+            let Some(command) = command_weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            if command.generation.get() != generation {
+                return glib::ControlFlow::Break;
+            }
+            match line {
+                Some(line) => {
+                    // It's up and printing, so a later exit starts the backoff over
+                    command.restart_delay.set(RESTART_DELAY_MIN);
+                    command.dispatch(CustomOutput::parse(&line));
+                    glib::ControlFlow::Continue
+                }
+                None => {
+                    command.on_exit();
+                    glib::ControlFlow::Break
+                }
+            }
+        });
+
+        info!("Started custom widget {} command", self.name);
+        Ok(())
+    }
+
+    /// The continuous command closed its output: hide its widgets and start
+    /// it again later
+    fn on_exit(self: &Rc<Self>) {
+        if let Some(mut child) = self.child.borrow_mut().take() {
+            let _ = child.kill();
+            match child.wait() {
+                Ok(status) => warn!("Custom widget {} command exited with {}", self.name, status),
+                Err(e) => warn!("Custom widget {} command exited: {}", self.name, e),
+            }
+        }
+
+        // An empty output hides the widgets
+        self.dispatch(CustomOutput::default());
+        self.schedule_restart();
+    }
+
+    fn schedule_restart(self: &Rc<Self>) {
+        let delay = self.restart_delay.get();
+        self.restart_delay.set((delay * 2).min(RESTART_DELAY_MAX));
+
+        info!("Restarting custom widget {} command in {:?}", self.name, delay);
+        let command_weak = Rc::downgrade(self);
+        let source_id = glib::timeout_add_local_once(delay, move || {
+            let Some(command) = command_weak.upgrade() else {
+                return;
+            };
+            command.restart.borrow_mut().take();
+            if let Err(e) = command.start() {
+                warn!("{:#}", e);
+                command.schedule_restart();
+            }
+        });
+        if let Some(previous) = self.restart.borrow_mut().replace(source_id) {
+            previous.remove();
+        }
+    }
+
+    /// Stop the command once no widget shows it
+    fn stop(&self) {
+        self.generation.set(self.generation.get() + 1);
+        if let Some(source_id) = self.timer.borrow_mut().take() {
+            source_id.remove();
+        }
+        if let Some(source_id) = self.restart.borrow_mut().take() {
+            source_id.remove();
+        }
+        if let Some(task) = self.task.borrow_mut().take() {
+            task.abort();
+        }
+        if let Some(mut child) = self.child.borrow_mut().take() {
+            debug!("Killing custom widget {} command", self.name);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        info!("Stopped custom widget {} command", self.name);
+    }
+
+    fn dispatch(&self, output: CustomOutput) {
+        *self.output.borrow_mut() = Some(output.clone());
+
+        let subscribers: Vec<Subscriber> = self
+            .subscribers
+            .borrow()
+            .iter()
+            .map(|(_, subscriber)| subscriber.clone())
+            .collect();
+        for subscriber in subscribers {
+            subscriber(&output);
+        }
+    }
+
+    /// Run a click or scroll command, then refresh an interval command so
+    /// the widget shows its effect
+    fn run_action(self: &Rc<Self>, action: &str) {
+        let action_result = action.to_string();
+        let command_weak = Rc::downgrade(self);
+        Cmd::new("sh")
+            .arg("-c")
+            .arg(action)
+            .timeout(ACTION_TIMEOUT)
+            .spawn(move |result| {
+                if let Err(e) = result {
+                    warn!("Failed to run custom command `{}`: {:#}", action_result, e);
+                }
+                if let Some(command) = command_weak.upgrade() {
+                    command.run();
+                }
+            });
+    }
+}
@@ -0,0 +1,275 @@
+use anyhow::Result;
+use gtk4::glib::WeakRef;
+use gtk4::prelude::*;
+use gtk4::{ApplicationWindow, Box, Button, Image, Label, Orientation, Popover};
+use gtk4_layer_shell::LayerShell;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use tracing::{info, warn};
+
+use crate::config::KeyboardLayoutConfig;
+use crate::niri_ipc::{self, Action, KeyboardLayouts, LayoutSwitchTarget, NiriEvent};
+use crate::niri_state;
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
+
+/// Shows the active keyboard layout and switches between layouts
+pub struct KeyboardLayout {
+    button: Button,
+    popover: Popover,
+    lifecycle: Lifecycle,
+}
+
+impl KeyboardLayout {
+    pub fn new(
+        config: &KeyboardLayoutConfig,
+        window_weak: WeakRef<ApplicationWindow>,
+        active_popovers: Rc<RefCell<i32>>,
+    ) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
+        let short_names = Rc::new(config.short_names.clone());
+
+        let button = Button::new();
+        button.add_css_class("keyboard-layout");
+
+        let label = Label::new(None);
+        label.add_css_class("keyboard-layout-label");
+        button.set_child(Some(&label));
+
+        // Popover listing every layout
+        let popover = Popover::new();
+        popover.set_parent(&button);
+        popover.add_css_class("keyboard-layout-popover");
+        popover.set_autohide(true);
+
+        // Handle popover show/hide for keyboard mode. The list is built on
+        // show, so opening the popover over IPC gets current layouts too.
+        let window_weak_show = window_weak.clone();
+        let active_popovers_show = active_popovers.clone();
+        let short_names_show = short_names.clone();
+        popover.connect_show(move |popover| {
+            *active_popovers_show.borrow_mut() += 1;
+            if let Some(window) = window_weak_show.upgrade() {
+                window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::OnDemand);
+                info!("Keyboard layout popover shown - keyboard mode set to OnDemand");
+            }
+            niri_state::with(|niri| match niri.keyboard_layouts() {
+                Some(layouts) => Self::update_popover(popover, layouts, &short_names_show),
+                None => Self::show_no_layouts(popover),
+            });
+        });
+
+        let window_weak_hide = window_weak.clone();
+        let active_popovers_hide = active_popovers.clone();
+        popover.connect_hide(move |_| {
+            *active_popovers_hide.borrow_mut() -= 1;
+            if *active_popovers_hide.borrow() == 0 {
+                if let Some(window) = window_weak_hide.upgrade() {
+                    window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::None);
+                    info!("Keyboard layout popover hidden - keyboard mode set to None");
+                }
+            }
+        });
+
+        // Handle Escape key
+        let escape_controller = gtk4::EventControllerKey::new();
+        let popover_weak_escape = popover.downgrade();
+        escape_controller.connect_key_pressed(move |_, key, _, _| {
+            if key == gtk4::gdk::Key::Escape {
+                if let Some(popover) = popover_weak_escape.upgrade() {
+                    popover.popdown();
+                }
+                gtk4::glib::Propagation::Stop
+            } else {
+                gtk4::glib::Propagation::Proceed
+            }
+        });
+        popover.add_controller(escape_controller);
+
+        // Follow layout changes from the shared niri state
+        let button_weak = button.downgrade();
+        let label_weak = label.downgrade();
+        let short_names_update = short_names.clone();
+        niri_state::subscribe(&lifecycle, move |niri, event| {
+            let relevant = matches!(
+                event,
+                NiriEvent::KeyboardLayoutsChanged { .. }
+                    | NiriEvent::KeyboardLayoutSwitched { .. }
+                    | NiriEvent::ConnectionChanged { .. }
+            );
+            if !relevant {
+                return;
+            }
+            if let (Some(button), Some(label)) = (button_weak.upgrade(), label_weak.upgrade()) {
+                Self::update(&button, &label, niri.keyboard_layouts(), &short_names_update);
+            }
+        });
+        niri_state::with(|niri| {
+            Self::update(&button, &label, niri.keyboard_layouts(), &short_names)
+        });
+
+        // Left click - switch to the next layout
+        button.connect_clicked(|_| {
+            Self::switch_layout(LayoutSwitchTarget::Next);
+        });
+
+        // Right click - list every layout
+        let gesture = gtk4::GestureClick::new();
+        gesture.set_button(3);
+        let popover_ref = popover.clone();
+        gesture.connect_released(move |_, _, _, _| {
+            popover_ref.popup();
+        });
+        button.add_controller(gesture);
+
+        Ok(Self {
+            button,
+            popover,
+            lifecycle,
+        })
+    }
+
+    /// Shown when the popover is opened before niri reported its layouts
+    fn show_no_layouts(popover: &Popover) {
+        let label = Label::new(Some("No keyboard layouts"));
+        label.add_css_class("keyboard-layout-popover-empty");
+        label.set_margin_top(10);
+        label.set_margin_bottom(10);
+        label.set_margin_start(10);
+        label.set_margin_end(10);
+        popover.set_child(Some(&label));
+    }
+
+    fn update(
+        button: &Button,
+        label: &Label,
+        layouts: Option<&KeyboardLayouts>,
+        short_names: &HashMap<String, String>,
+    ) {
+        let Some(name) = layouts.and_then(|layouts| layouts.names.get(layouts.current_idx)) else {
+            // niri hasn't reported its layouts yet
+            button.set_visible(false);
+            return;
+        };
+
+        label.set_text(&short_name(name, short_names));
+        button.set_tooltip_text(Some(name));
+        button.set_visible(true);
+    }
+
+    fn update_popover(
+        popover: &Popover,
+        layouts: &KeyboardLayouts,
+        short_names: &HashMap<String, String>,
+    ) {
+        let popover_box = Box::new(Orientation::Vertical, 2);
+        popover_box.set_margin_top(10);
+        popover_box.set_margin_bottom(10);
+        popover_box.set_margin_start(10);
+        popover_box.set_margin_end(10);
+
+        for (idx, name) in layouts.names.iter().enumerate() {
+            let row = Button::new();
+            row.add_css_class("flat");
+            row.add_css_class("keyboard-layout-item");
+
+            let row_box = Box::new(Orientation::Horizontal, 10);
+            let code = Label::new(Some(&short_name(name, short_names)));
+            code.add_css_class("keyboard-layout-code");
+            code.set_width_chars(3);
+            row_box.append(&code);
+
+            let name_label = Label::new(Some(name));
+            name_label.set_halign(gtk4::Align::Start);
+            name_label.set_hexpand(true);
+            row_box.append(&name_label);
+
+            if idx == layouts.current_idx {
+                row.add_css_class("active");
+                row_box.append(&Image::from_icon_name("object-select-symbolic"));
+            }
+            row.set_child(Some(&row_box));
+
+            let popover_weak = popover.downgrade();
+            let layout_idx = idx.min(u8::MAX as usize) as u8;
+            row.connect_clicked(move |_| {
+                Self::switch_layout(LayoutSwitchTarget::Index(layout_idx));
+                if let Some(popover) = popover_weak.upgrade() {
+                    popover.popdown();
+                }
+            });
+
+            popover_box.append(&row);
+        }
+
+        popover.set_child(Some(&popover_box));
+    }
+
+    fn switch_layout(layout: LayoutSwitchTarget) {
+        if let Err(e) = niri_ipc::action(Action::SwitchLayout { layout }) {
+            warn!("Failed to switch keyboard layout: {:#}", e);
+        }
+    }
+
+    pub fn widget(&self) -> &Button {
+        &self.button
+    }
+}
+
+impl WidgetTrait for KeyboardLayout {
+    fn popover(&self) -> Option<&Popover> {
+        Some(&self.popover)
+    }
+
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
+}
+
+/// Short code for a layout name such as "English (US)" or "German".
+/// Configured names win; otherwise a variant in parentheses is used if it
+/// looks like a country code, then a known language code, then the first
+/// two letters of the name.
+fn short_name(name: &str, short_names: &HashMap<String, String>) -> String {
+    if let Some(short) = short_names.get(name) {
+        return short.clone();
+    }
+
+    if let Some(variant) = name
+        .split_once('(')
+        .and_then(|(_, rest)| rest.split_once(')'))
+        .map(|(variant, _)| variant.trim())
+    {
+        if (2..=3).contains(&variant.len()) && variant.chars().all(|c| c.is_ascii_uppercase()) {
+            return variant.to_string();
+        }
+    }
+
+    let language = name.split(['(', ',']).next().unwrap_or(name).trim();
+    let code = match language.to_lowercase().as_str() {
+        "english" => "EN",
+        "german" => "DE",
+        "french" => "FR",
+        "spanish" => "ES",
+        "italian" => "IT",
+        "portuguese" => "PT",
+        "dutch" => "NL",
+        "swedish" => "SV",
+        "norwegian" => "NO",
+        "danish" => "DA",
+        "finnish" => "FI",
+        "polish" => "PL",
+        "czech" => "CS",
+        "russian" => "RU",
+        "ukrainian" => "UK",
+        "greek" => "EL",
+        "turkish" => "TR",
+        "hebrew" => "HE",
+        "arabic" => "AR",
+        "japanese" => "JA",
+        "korean" => "KO",
+        "chinese" => "ZH",
+        _ => return language.chars().take(2).collect::<String>().to_uppercase(),
+    };
+    code.to_string()
+}
@@ -517,8 +517,11 @@ impl Launcher {
     pub fn widget(&self) -> &Button {
         &self.button
     }
-    
-    pub fn popover(&self) -> Option<&Popover> {
+}
+
+// Implementation of Widget trait
+impl WidgetTrait for Launcher {
+    fn popover(&self) -> Option<&Popover> {
         Some(&self.popover)
     }
 }
@@ -0,0 +1,108 @@
+use gtk4::glib;
+use std::cell::{Cell, RefCell};
+use std::process::Child;
+use std::rc::Rc;
+use std::time::Duration;
+use tracing::debug;
+
+/// Tracks the timers and child processes a widget starts, so they can be
+/// stopped when the widget is replaced on config reload.
+///
+/// Timers added through a `Lifecycle` stop on their next tick after
+/// `shutdown`, and shutdown callbacks run exactly once.
+#[derive(Clone, Default)]
+pub struct Lifecycle {
+    inner: Rc<LifecycleInner>,
+}
+
+#[derive(Default)]
+struct LifecycleInner {
+    stopped: Cell<bool>,
+    on_shutdown: RefCell<Vec<Box<dyn FnOnce()>>>,
+}
+
+impl Lifecycle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `shutdown` has been called
+    pub fn is_stopped(&self) -> bool {
+        self.inner.stopped.get()
+    }
+
+    /// Like `glib::timeout_add_local`, but stops once the widget is shut down
+    pub fn timeout_add_local<F>(&self, interval: Duration, mut func: F)
+    where
+        F: FnMut() -> glib::ControlFlow + 'static,
+    {
+        let lifecycle = self.clone();
+        glib::timeout_add_local(interval, move || {
+            if lifecycle.is_stopped() {
+                glib::ControlFlow::Break
+            } else {
+                func()
+            }
+        });
+    }
+
+    /// Like `glib::timeout_add_seconds_local`, but stops once the widget is shut down
+    pub fn timeout_add_seconds_local<F>(&self, interval: u32, mut func: F)
+    where
+        F: FnMut() -> glib::ControlFlow + 'static,
+    {
+        let lifecycle = self.clone();
+        glib::timeout_add_seconds_local(interval, move || {
+            if lifecycle.is_stopped() {
+                glib::ControlFlow::Break
+            } else {
+                func()
+            }
+        });
+    }
+
+    /// Like `glib::idle_add_local`, but stops once the widget is shut down
+    pub fn idle_add_local<F>(&self, mut func: F)
+    where
+        F: FnMut() -> glib::ControlFlow + 'static,
+    {
+        let lifecycle = self.clone();
+        glib::idle_add_local(move || {
+            if lifecycle.is_stopped() {
+                glib::ControlFlow::Break
+            } else {
+                func()
+            }
+        });
+    }
+
+    /// Run a callback when the widget is shut down
+    pub fn on_shutdown(&self, func: impl FnOnce() + 'static) {
+        if self.is_stopped() {
+            func();
+        } else {
+            self.inner.on_shutdown.borrow_mut().push(Box::new(func));
+        }
+    }
+
+    /// Kill and reap a child process when the widget is shut down
+    pub fn add_child(&self, mut child: Child) {
+        self.on_shutdown(move || {
+            debug!("Killing child process {}", child.id());
+            let _ = child.kill();
+            let _ = child.wait();
+        });
+    }
+
+    /// Stop all timers and run the shutdown callbacks
+    pub fn shutdown(&self) {
+        if self.inner.stopped.replace(true) {
+            return;
+        }
+
+        let callbacks = std::mem::take(&mut *self.inner.on_shutdown.borrow_mut());
+        for callback in callbacks {
+            callback();
+        }
+    }
+}
@@ -3,39 +3,56 @@ use gtk4::Popover;
 mod battery;
 mod bluetooth;
 mod clock;
+mod custom;
 mod git;
+mod keyboard_layout;
 mod keyboard_mode;
 mod launcher;
+mod lifecycle;
 mod network;
 mod overview;
 mod places;
+mod plugin;
 mod power;
 mod search;
 mod secrets;
 mod servers;
 mod sound;
+mod status_indicator;
+mod taskbar;
+mod window_title;
 mod workspaces;
 
-/// Common trait for all widgets that have popovers
+/// Common trait for all panel widgets
 pub trait Widget {
     /// Get the popover for this widget, if any
     fn popover(&self) -> Option<&Popover> {
         None
     }
+
+    /// Stop the widget's timers, threads and child processes before it is replaced
+    fn shutdown(&self) {}
 }
 
 pub use battery::Battery;
 pub use bluetooth::Bluetooth;
 pub use clock::Clock;
+pub use custom::Custom;
 pub use git::Git;
+pub use keyboard_layout::KeyboardLayout;
 pub use keyboard_mode::KeyboardModeManager;
 pub use launcher::Launcher;
+pub use lifecycle::Lifecycle;
 pub use network::Network;
 pub use overview::Overview;
 pub use places::Places;
+pub use plugin::Plugin;
 pub use power::Power;
 pub use search::Search;
 pub use secrets::Secrets;
 pub use servers::Servers;
 pub use sound::Sound;
+pub use status_indicator::StatusIndicator;
+pub use taskbar::Taskbar;
+pub use window_title::WindowTitle;
 pub use workspaces::Workspaces;
@@ -1,9 +1,11 @@
 use anyhow::Result;
 use gtk4::prelude::*;
 use gtk4::{Button, Image, Label};
-use std::process::Command;
 use tracing::warn;
 
+use crate::niri_ipc::{self, Action};
+use crate::widgets::Widget as WidgetTrait;
+
 pub struct Overview {
     button: Button,
 }
@@ -52,21 +54,8 @@ impl Overview {
     }
 
     fn toggle_overview() {
-        match Command::new("niri")
-            .args(&["msg", "action", "toggle-overview"])
-            .output()
-        {
-            Ok(output) => {
-                if !output.status.success() {
-                    warn!(
-                        "Failed to toggle overview: {:?}",
-                        String::from_utf8_lossy(&output.stderr)
-                    );
-                }
-            }
-            Err(e) => {
-                warn!("Failed to execute niri command: {}", e);
-            }
+        if let Err(e) = niri_ipc::action(Action::ToggleOverview {}) {
+            warn!("Failed to toggle overview: {:#}", e);
         }
     }
 
@@ -74,3 +63,5 @@ impl Overview {
         &self.button
     }
 }
+
+impl WidgetTrait for Overview {}
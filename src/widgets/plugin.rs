@@ -0,0 +1,771 @@
+use anyhow::{Context, Result};
+use gtk4::glib::{self, WeakRef};
+use gtk4::prelude::*;
+use gtk4::{
+    ApplicationWindow, Box, Button, Entry, Image, Label, ListBox, ListBoxRow, Orientation,
+    Popover, Scale, Switch,
+};
+use gtk4_layer_shell::LayerShell;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, Command, Stdio};
+use std::rc::Rc;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, info, warn};
+
+use crate::config::PluginConfig;
+use crate::plugin::{self, Element, ListItem, PluginEvent, PluginMessage};
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
+
+/// A widget drawn by an external plugin process, see `crate::plugin`
+pub struct Plugin {
+    button: Button,
+    popover: Popover,
+    lifecycle: Lifecycle,
+}
+
+impl Plugin {
+    pub fn new(
+        config: &PluginConfig,
+        window_weak: WeakRef<ApplicationWindow>,
+        active_popovers: Rc<RefCell<i32>>,
+    ) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
+
+        let button = Button::new();
+        button.add_css_class("plugin");
+        button.add_css_class(&format!("plugin-{}", config.name));
+        // Hidden until the plugin describes its bar button
+        button.set_visible(false);
+
+        let container = Box::new(Orientation::Horizontal, 5);
+        let icon = Image::new();
+        icon.set_visible(false);
+        let label = Label::new(None);
+        label.add_css_class("plugin-label");
+        container.append(&icon);
+        container.append(&label);
+        button.set_child(Some(&container));
+
+        let popover = Popover::new();
+        popover.set_parent(&button);
+        popover.add_css_class("plugin-popover");
+        popover.set_autohide(true);
+
+        // Whether the plugin has given the popover any content
+        let has_popover = Rc::new(Cell::new(false));
+        let content = PopoverContent::new(&popover);
+
+        let popover_ref = popover.clone();
+        let has_popover_click = has_popover.clone();
+        button.connect_clicked(move |_| {
+            if has_popover_click.get() {
+                popover_ref.popup();
+            }
+        });
+
+        // Classes set by the last bar message, removed again on the next one
+        let bar_classes: RefCell<Vec<String>> = RefCell::new(Vec::new());
+
+        // Show what the plugin sends; the process is shared with this
+        // plugin's widgets on other panels
+        let button_weak = button.downgrade();
+        let popover_weak = popover.downgrade();
+        let process = PluginProcess::subscribe(config, &lifecycle, move |process, message| {
+            let (Some(button), Some(popover)) = (button_weak.upgrade(), popover_weak.upgrade())
+            else {
+                return;
+            };
+
+            match message {
+                PluginMessage::Bar {
+                    text,
+                    icon: icon_name,
+                    tooltip,
+                    class,
+                } => {
+                    label.set_text(text);
+                    label.set_visible(!text.is_empty());
+                    match icon_name {
+                        Some(icon_name) => {
+                            icon.set_from_icon_name(Some(icon_name));
+                            icon.set_visible(true);
+                        }
+                        None => icon.set_visible(false),
+                    }
+                    button.set_tooltip_text(tooltip.as_deref());
+
+                    let mut classes = bar_classes.borrow_mut();
+                    for class in classes.iter() {
+                        button.remove_css_class(class);
+                    }
+                    *classes = class.clone();
+                    for class in classes.iter() {
+                        button.add_css_class(class);
+                    }
+
+                    button.set_visible(!text.is_empty() || icon_name.is_some());
+                }
+                PluginMessage::Popover { elements } => {
+                    has_popover.set(!elements.is_empty());
+                    if elements.is_empty() {
+                        popover.popdown();
+                    }
+                    content.update(elements, process);
+                }
+            }
+        })?;
+
+        // Handle popover show/hide for keyboard mode, and tell the plugin
+        let window_weak_show = window_weak.clone();
+        let active_popovers_show = active_popovers.clone();
+        let process_show = process.clone();
+        popover.connect_show(move |_| {
+            *active_popovers_show.borrow_mut() += 1;
+            if let Some(window) = window_weak_show.upgrade() {
+                window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::OnDemand);
+                info!("Plugin popover shown - keyboard mode set to OnDemand");
+            }
+            process_show.send(PluginEvent::PopoverOpened);
+        });
+
+        let window_weak_hide = window_weak.clone();
+        let active_popovers_hide = active_popovers.clone();
+        let process_hide = process.clone();
+        popover.connect_hide(move |_| {
+            *active_popovers_hide.borrow_mut() -= 1;
+            if *active_popovers_hide.borrow() == 0 {
+                if let Some(window) = window_weak_hide.upgrade() {
+                    window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::None);
+                    info!("Plugin popover hidden - keyboard mode set to None");
+                }
+            }
+            process_hide.send(PluginEvent::PopoverClosed);
+        });
+
+        // Handle Escape key
+        let escape_controller = gtk4::EventControllerKey::new();
+        let popover_weak_escape = popover.downgrade();
+        escape_controller.connect_key_pressed(move |_, key, _, _| {
+            if key == gtk4::gdk::Key::Escape {
+                if let Some(popover) = popover_weak_escape.upgrade() {
+                    popover.popdown();
+                }
+                glib::Propagation::Stop
+            } else {
+                glib::Propagation::Proceed
+            }
+        });
+        popover.add_controller(escape_controller);
+
+        Ok(Self {
+            button,
+            popover,
+            lifecycle,
+        })
+    }
+
+    pub fn widget(&self) -> &Button {
+        &self.button
+    }
+}
+
+impl WidgetTrait for Plugin {
+    fn popover(&self) -> Option<&Popover> {
+        Some(&self.popover)
+    }
+
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
+}
+
+/// A popover element's widget, and the control updated in place
+struct Shown {
+    element: Element,
+    widget: gtk4::Widget,
+    control: Control,
+}
+
+enum Control {
+    List(ListBox),
+    Button,
+    // Programmatic changes are made with the handler blocked, so they
+    // aren't reported back to the plugin
+    Slider(Scale, glib::SignalHandlerId),
+    Toggle(Switch, glib::SignalHandlerId),
+    Entry(Entry),
+}
+
+/// The popover's elements, updated in place by `id`, so a slider being
+/// dragged or an entry being typed in survives the plugin's next message
+struct PopoverContent {
+    container: Box,
+    shown: RefCell<Vec<Shown>>,
+}
+
+impl PopoverContent {
+    fn new(popover: &Popover) -> Self {
+        let container = Box::new(Orientation::Vertical, 8);
+        container.set_margin_top(10);
+        container.set_margin_bottom(10);
+        container.set_margin_start(10);
+        container.set_margin_end(10);
+        container.set_size_request(250, -1);
+        popover.set_child(Some(&container));
+
+        Self {
+            container,
+            shown: RefCell::new(Vec::new()),
+        }
+    }
+
+    fn update(&self, elements: &[Element], process: &Rc<PluginProcess>) {
+        let mut old = std::mem::take(&mut *self.shown.borrow_mut());
+        let mut shown: Vec<Shown> = Vec::with_capacity(elements.len());
+
+        for element in elements {
+            let existing = old
+                .iter()
+                .position(|s| s.element.id() == element.id())
+                .map(|index| old.remove(index));
+            let current = match existing {
+                Some(mut existing) => {
+                    if !existing.update(element) {
+                        let created = Shown::new(element, process);
+                        self.container.append(&created.widget);
+                        self.container.remove(&existing.widget);
+                        existing = created;
+                    }
+                    existing
+                }
+                None => {
+                    let created = Shown::new(element, process);
+                    self.container.append(&created.widget);
+                    created
+                }
+            };
+
+            // Keep the plugin's order
+            self.container
+                .reorder_child_after(&current.widget, shown.last().map(|s| &s.widget));
+            shown.push(current);
+        }
+
+        for removed in old {
+            self.container.remove(&removed.widget);
+        }
+        *self.shown.borrow_mut() = shown;
+    }
+}
+
+impl Shown {
+    fn new(element: &Element, process: &Rc<PluginProcess>) -> Self {
+        let process = process.clone();
+        let (widget, control): (gtk4::Widget, Control) = match element {
+            Element::List { id, items } => {
+                let list = ListBox::new();
+                list.add_css_class("plugin-list");
+                list.set_selection_mode(gtk4::SelectionMode::None);
+                Self::fill_list(&list, items);
+
+                let id = id.clone();
+                list.connect_row_activated(move |_, row| {
+                    process.send(PluginEvent::Selected {
+                        id: id.clone(),
+                        item: row.widget_name().to_string(),
+                    });
+                });
+                (list.clone().upcast(), Control::List(list))
+            }
+            Element::Button { id, label, icon } => {
+                let button = match icon {
+                    Some(icon_name) => {
+                        let button = Button::new();
+                        let button_box = Box::new(Orientation::Horizontal, 8);
+                        button_box.append(&Image::from_icon_name(icon_name));
+                        button_box.append(&Label::new(Some(label)));
+                        button.set_child(Some(&button_box));
+                        button
+                    }
+                    None => Button::with_label(label),
+                };
+                button.add_css_class("plugin-button");
+
+                let id = id.clone();
+                button.connect_clicked(move |_| {
+                    process.send(PluginEvent::Clicked { id: id.clone() });
+                });
+                (button.upcast(), Control::Button)
+            }
+            Element::Slider {
+                id,
+                label,
+                value,
+                min,
+                max,
+                step,
+            } => {
+                let slider_box = Box::new(Orientation::Vertical, 4);
+                if let Some(label) = label {
+                    let slider_label = Label::new(Some(label));
+                    slider_label.set_halign(gtk4::Align::Start);
+                    slider_box.append(&slider_label);
+                }
+
+                let scale = Scale::with_range(Orientation::Horizontal, *min, *max, *step);
+                scale.add_css_class("plugin-slider");
+                scale.set_value(*value);
+                scale.set_hexpand(true);
+
+                let id = id.clone();
+                let handler = scale.connect_value_changed(move |scale| {
+                    process.send(PluginEvent::Changed {
+                        id: id.clone(),
+                        value: scale.value(),
+                    });
+                });
+                slider_box.append(&scale);
+                (slider_box.upcast(), Control::Slider(scale, handler))
+            }
+            Element::Toggle { id, label, active } => {
+                let toggle_box = Box::new(Orientation::Horizontal, 10);
+                let toggle_label = Label::new(Some(label));
+                toggle_label.set_halign(gtk4::Align::Start);
+                toggle_label.set_hexpand(true);
+                toggle_box.append(&toggle_label);
+
+                let switch = Switch::new();
+                switch.add_css_class("plugin-toggle");
+                switch.set_active(*active);
+
+                let id = id.clone();
+                let handler = switch.connect_state_set(move |_, active| {
+                    process.send(PluginEvent::Toggled {
+                        id: id.clone(),
+                        active,
+                    });
+                    glib::Propagation::Proceed
+                });
+                toggle_box.append(&switch);
+                (toggle_box.upcast(), Control::Toggle(switch, handler))
+            }
+            Element::Entry {
+                id,
+                placeholder,
+                text,
+            } => {
+                let entry = Entry::new();
+                entry.add_css_class("plugin-entry");
+                entry.set_placeholder_text(placeholder.as_deref());
+                entry.set_text(text);
+
+                let id = id.clone();
+                entry.connect_activate(move |entry| {
+                    process.send(PluginEvent::Submitted {
+                        id: id.clone(),
+                        text: entry.text().to_string(),
+                    });
+                });
+                (entry.clone().upcast(), Control::Entry(entry))
+            }
+        };
+
+        Self {
+            element: element.clone(),
+            widget,
+            control,
+        }
+    }
+
+    fn fill_list(list: &ListBox, items: &[ListItem]) {
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+
+        for item in items {
+            let row = ListBoxRow::new();
+            let row_box = Box::new(Orientation::Horizontal, 10);
+            row_box.set_margin_top(4);
+            row_box.set_margin_bottom(4);
+            if let Some(icon_name) = &item.icon {
+                row_box.append(&Image::from_icon_name(icon_name));
+            }
+
+            let text_box = Box::new(Orientation::Vertical, 2);
+            let item_label = Label::new(Some(&item.label));
+            item_label.set_halign(gtk4::Align::Start);
+            text_box.append(&item_label);
+            if let Some(detail) = &item.detail {
+                let detail_label = Label::new(Some(detail));
+                detail_label.add_css_class("plugin-list-detail");
+                detail_label.set_halign(gtk4::Align::Start);
+                text_box.append(&detail_label);
+            }
+            row_box.append(&text_box);
+
+            row.set_child(Some(&row_box));
+            row.set_widget_name(&item.id);
+            list.append(&row);
+        }
+    }
+
+    /// Apply a new version of the element to the widget shown for it, or
+    /// return false if only a new widget can show it. Only values the
+    /// plugin changed are applied, so an echo of an old value doesn't undo
+    /// what the user is doing.
+    fn update(&mut self, element: &Element) -> bool {
+        if self.element == *element {
+            return true;
+        }
+
+        let updated = match (&self.element, element, &self.control) {
+            (Element::List { .. }, Element::List { items, .. }, Control::List(list)) => {
+                Self::fill_list(list, items);
+                true
+            }
+            (
+                Element::Slider {
+                    label: old_label,
+                    value: old_value,
+                    min: old_min,
+                    max: old_max,
+                    step: old_step,
+                    ..
+                },
+                Element::Slider {
+                    label,
+                    value,
+                    min,
+                    max,
+                    step,
+                    ..
+                },
+                Control::Slider(scale, handler),
+            ) if (old_label, old_min, old_max, old_step) == (label, min, max, step) => {
+                if value != old_value && scale.value() != *value {
+                    scale.block_signal(handler);
+                    scale.set_value(*value);
+                    scale.unblock_signal(handler);
+                }
+                true
+            }
+            (
+                Element::Toggle {
+                    label: old_label,
+                    active: old_active,
+                    ..
+                },
+                Element::Toggle { label, active, .. },
+                Control::Toggle(switch, handler),
+            ) if old_label == label => {
+                if active != old_active && switch.is_active() != *active {
+                    switch.block_signal(handler);
+                    switch.set_active(*active);
+                    switch.unblock_signal(handler);
+                }
+                true
+            }
+            (
+                Element::Entry {
+                    placeholder: old_placeholder,
+                    text: old_text,
+                    ..
+                },
+                Element::Entry {
+                    placeholder, text, ..
+                },
+                Control::Entry(entry),
+            ) => {
+                if placeholder != old_placeholder {
+                    entry.set_placeholder_text(placeholder.as_deref());
+                }
+                if text != old_text && entry.text() != *text {
+                    entry.set_text(text);
+                }
+                true
+            }
+            _ => false,
+        };
+
+        if updated {
+            self.element = element.clone();
+        }
+        updated
+    }
+}
+
+/// First delay before restarting a plugin that exited; doubled after each exit
+const RESTART_DELAY_MIN: Duration = Duration::from_secs(1);
+const RESTART_DELAY_MAX: Duration = Duration::from_secs(60);
+
+type Subscriber = Rc<dyn Fn(&Rc<PluginProcess>, &PluginMessage)>;
+
+/// A plugin's process, shared by the plugin's widgets on every panel. It
+/// runs while any widget shows the plugin, and is restarted with backoff
+/// when it exits.
+struct PluginProcess {
+    name: String,
+    exec: String,
+    subscribers: RefCell<Vec<(u64, Subscriber)>>,
+    next_id: Cell<u64>,
+    // The last bar and popover messages, for widgets added later
+    bar: RefCell<Option<PluginMessage>>,
+    popover: RefCell<Option<PluginMessage>>,
+    child: RefCell<Option<Child>>,
+    events: RefCell<Option<mpsc::Sender<PluginEvent>>>,
+    // Bumped on every start and stop, so a stale process's exit is ignored
+    generation: Cell<u64>,
+    restart: RefCell<Option<glib::SourceId>>,
+    restart_delay: Cell<Duration>,
+}
+
+thread_local! {
+    // Running plugins by name and command
+    static PROCESSES: RefCell<HashMap<(String, String), Rc<PluginProcess>>> =
+        RefCell::new(HashMap::new());
+}
+
+impl PluginProcess {
+    /// Call `callback` with each of the plugin's messages until the widget
+    /// is shut down, starting with the last ones it sent. The first
+    /// widget starts the plugin, and the last one stops it.
+    fn subscribe(
+        config: &PluginConfig,
+        lifecycle: &Lifecycle,
+        callback: impl Fn(&Rc<Self>, &PluginMessage) + 'static,
+    ) -> Result<Rc<Self>> {
+        let key = (config.name.clone(), config.exec.clone());
+        let existing = PROCESSES.with(|processes| processes.borrow().get(&key).cloned());
+        let process = match existing {
+            Some(process) => process,
+            None => {
+                let process = Rc::new(Self {
+                    name: config.name.clone(),
+                    exec: config.exec.clone(),
+                    subscribers: RefCell::new(Vec::new()),
+                    next_id: Cell::new(0),
+                    bar: RefCell::new(None),
+                    popover: RefCell::new(None),
+                    child: RefCell::new(None),
+                    events: RefCell::new(None),
+                    generation: Cell::new(0),
+                    restart: RefCell::new(None),
+                    restart_delay: Cell::new(RESTART_DELAY_MIN),
+                });
+                process.start()?;
+                PROCESSES.with(|processes| {
+                    processes.borrow_mut().insert(key.clone(), process.clone())
+                });
+                process
+            }
+        };
+
+        let shown = [process.bar.borrow().clone(), process.popover.borrow().clone()];
+        for message in shown.iter().flatten() {
+            callback(&process, message);
+        }
+
+        let id = process.next_id.get();
+        process.next_id.set(id + 1);
+        process.subscribers.borrow_mut().push((id, Rc::new(callback)));
+
+        let process_shutdown = process.clone();
+        lifecycle.on_shutdown(move || {
+            let process = process_shutdown;
+            let last = {
+                let mut subscribers = process.subscribers.borrow_mut();
+                subscribers.retain(|(subscriber_id, _)| *subscriber_id != id);
+                subscribers.is_empty()
+            };
+            if last {
+                process.stop();
+                PROCESSES.with(|processes| {
+                    let mut processes = processes.borrow_mut();
+                    if processes.get(&key).is_some_and(|p| Rc::ptr_eq(p, &process)) {
+                        processes.remove(&key);
+                    }
+                });
+            }
+        });
+
+        Ok(process)
+    }
+
+    /// Start the plugin; its stdin and stdout are handled on their own threads
+    fn start(self: &Rc<Self>) -> Result<()> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(&self.exec)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start plugin `{}`", self.exec))?;
+        let stdin = child.stdin.take().context("Plugin has no stdin")?;
+        let stdout = child.stdout.take().context("Plugin has no stdout")?;
+
+        let generation = self.generation.get() + 1;
+        self.generation.set(generation);
+        *self.child.borrow_mut() = Some(child);
+        *self.events.borrow_mut() = Some(Self::write_events(&self.name, stdin));
+
+        let (sender, receiver) = glib::MainContext::channel(glib::Priority::DEFAULT);
+        Self::read_messages(&self.name, stdout, sender);
+
+        let process_weak = Rc::downgrade(self);
+        receiver.attach(None, move |message: Option<PluginMessage>| {
+            let Some(process) = process_weak.upgrade() else {
+                return glib::ControlFlow::Break;
+            };
+            if process.generation.get() != generation {
+                return glib::ControlFlow::Break;
+            }
+            match message {
+                Some(message) => {
+                    // It's up and talking, so a later exit starts the backoff over
+                    process.restart_delay.set(RESTART_DELAY_MIN);
+                    process.dispatch(message);
+                    glib::ControlFlow::Continue
+                }
+                None => {
+                    process.on_exit();
+                    glib::ControlFlow::Break
+                }
+            }
+        });
+
+        info!("Started plugin {}", self.name);
+        Ok(())
+    }
+
+    /// The plugin closed its output: hide its widgets and start it again later
+    fn on_exit(self: &Rc<Self>) {
+        if let Some(mut child) = self.child.borrow_mut().take() {
+            let _ = child.kill();
+            match child.wait() {
+                Ok(status) => warn!("Plugin {} exited with {}", self.name, status),
+                Err(e) => warn!("Plugin {} exited: {}", self.name, e),
+            }
+        }
+        self.events.borrow_mut().take();
+
+        // An empty bar hides the buttons and an empty popover closes the popovers
+        self.dispatch(PluginMessage::Bar {
+            text: String::new(),
+            icon: None,
+            tooltip: None,
+            class: Vec::new(),
+        });
+        self.dispatch(PluginMessage::Popover {
+            elements: Vec::new(),
+        });
+
+        self.schedule_restart();
+    }
+
+    fn schedule_restart(self: &Rc<Self>) {
+        let delay = self.restart_delay.get();
+        self.restart_delay.set((delay * 2).min(RESTART_DELAY_MAX));
+
+        info!("Restarting plugin {} in {:?}", self.name, delay);
+        let process_weak = Rc::downgrade(self);
+        let source_id = glib::timeout_add_local_once(delay, move || {
+            let Some(process) = process_weak.upgrade() else {
+                return;
+            };
+            process.restart.borrow_mut().take();
+            if let Err(e) = process.start() {
+                warn!("{:#}", e);
+                process.schedule_restart();
+            }
+        });
+        if let Some(previous) = self.restart.borrow_mut().replace(source_id) {
+            previous.remove();
+        }
+    }
+
+    /// Stop the plugin once no widget shows it
+    fn stop(&self) {
+        self.generation.set(self.generation.get() + 1);
+        if let Some(source_id) = self.restart.borrow_mut().take() {
+            source_id.remove();
+        }
+        self.events.borrow_mut().take();
+        if let Some(mut child) = self.child.borrow_mut().take() {
+            debug!("Killing plugin {}", self.name);
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        info!("Stopped plugin {}", self.name);
+    }
+
+    fn dispatch(self: &Rc<Self>, message: PluginMessage) {
+        match &message {
+            PluginMessage::Bar { .. } => *self.bar.borrow_mut() = Some(message.clone()),
+            PluginMessage::Popover { .. } => *self.popover.borrow_mut() = Some(message.clone()),
+        }
+
+        let subscribers: Vec<Subscriber> = self
+            .subscribers
+            .borrow()
+            .iter()
+            .map(|(_, subscriber)| subscriber.clone())
+            .collect();
+        for subscriber in subscribers {
+            subscriber(self, &message);
+        }
+    }
+
+    /// Send an event to the plugin, if it is running
+    fn send(&self, event: PluginEvent) {
+        if let Some(events) = self.events.borrow().as_ref() {
+            let _ = events.send(event);
+        }
+    }
+
+    /// Read messages from the plugin, followed by `None` once it exits
+    fn read_messages(
+        name: &str,
+        stdout: std::process::ChildStdout,
+        sender: glib::Sender<Option<PluginMessage>>,
+    ) {
+        let name = name.to_string();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                let Ok(line) = line else { break };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match plugin::parse_message(&line) {
+                    Ok(message) => {
+                        if sender.send(Some(message)).is_err() {
+                            return;
+                        }
+                    }
+                    Err(e) => warn!("Plugin {}: {:#}", name, e),
+                }
+            }
+            info!("Plugin {} closed its output", name);
+            let _ = sender.send(None);
+        });
+    }
+
+    /// Write events to the plugin from a thread, so a plugin that stops
+    /// reading can't block the panel
+    fn write_events(name: &str, mut stdin: std::process::ChildStdin) -> mpsc::Sender<PluginEvent> {
+        let (sender, receiver) = mpsc::channel::<PluginEvent>();
+        let name = name.to_string();
+        thread::spawn(move || {
+            for event in receiver {
+                if let Err(e) = writeln!(stdin, "{}", plugin::encode_event(&event)) {
+                    debug!("Plugin {} stopped reading events: {}", name, e);
+                    break;
+                }
+            }
+        });
+        sender
+    }
+}
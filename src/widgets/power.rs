@@ -1,5 +1,5 @@
 use anyhow::Result;
-use gtk4::glib::WeakRef;
+use gtk4::glib::{self, WeakRef};
 use gtk4::prelude::*;
 use gtk4::{ApplicationWindow, Box, Button, Image, Label, Orientation, Popover};
 use gtk4_layer_shell::LayerShell;
@@ -10,11 +10,13 @@ use std::rc::Rc;
 use std::time::Duration;
 use tracing::info;
 
-use crate::widgets::Widget as WidgetTrait;
+use crate::command::{self, Cmd};
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
 
 pub struct Power {
     button: Button,
     popover: Popover,
+    lifecycle: Lifecycle,
 }
 
 #[derive(Debug, Clone)]
@@ -112,6 +114,7 @@ impl Power {
         window_weak: WeakRef<ApplicationWindow>,
         active_popovers: Rc<RefCell<i32>>,
     ) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
         let button = Button::new();
         button.add_css_class("power");
 
@@ -277,7 +280,7 @@ impl Power {
         let packages_weak = packages_label.downgrade();
         let popover_weak = popover.downgrade();
 
-        glib::timeout_add_local(Duration::from_secs(2), move || {
+        lifecycle.timeout_add_local(Duration::from_secs(2), move || {
             if let Some(popover) = popover_weak.upgrade() {
                 // Only update if popover is visible
                 if popover.is_visible() {
@@ -357,7 +360,11 @@ impl Power {
             popover_ref.popup();
         });
 
-        Ok(Self { button, popover })
+        Ok(Self {
+            button,
+            popover,
+            lifecycle,
+        })
     }
 
     fn create_stat_label(title: &str, initial_value: &str) -> Box {
@@ -391,8 +398,42 @@ impl Power {
         disk_box: &Box,
         packages_box: &Box,
     ) {
-        let stats = Self::get_system_stats();
+        let os_box = os_box.clone();
+        let kernel_box = kernel_box.clone();
+        let hostname_box = hostname_box.clone();
+        let cpu_model_box = cpu_model_box.clone();
+        let uptime_box = uptime_box.clone();
+        let memory_box = memory_box.clone();
+        let disk_box = disk_box.clone();
+        let packages_box = packages_box.clone();
+        // Counting packages can take a while, so don't hold up the main loop
+        glib::MainContext::default().spawn_local(async move {
+            let stats = Self::get_system_stats().await;
+            Self::show_stats(
+                &stats,
+                &os_box,
+                &kernel_box,
+                &hostname_box,
+                &cpu_model_box,
+                &uptime_box,
+                &memory_box,
+                &disk_box,
+                &packages_box,
+            );
+        });
+    }
 
+    fn show_stats(
+        stats: &SystemStats,
+        os_box: &Box,
+        kernel_box: &Box,
+        hostname_box: &Box,
+        cpu_model_box: &Box,
+        uptime_box: &Box,
+        memory_box: &Box,
+        disk_box: &Box,
+        packages_box: &Box,
+    ) {
         // Update OS
         if let Some(value_label) = os_box.last_child() {
             if let Some(label) = value_label.downcast_ref::<Label>() {
@@ -457,7 +498,7 @@ impl Power {
         }
     }
 
-    fn get_system_stats() -> SystemStats {
+    async fn get_system_stats() -> SystemStats {
         let mut stats = SystemStats {
             os: "Unknown".to_string(),
             kernel: "Unknown".to_string(),
@@ -547,8 +588,7 @@ impl Power {
         }
 
         // Get disk usage for root filesystem
-        if let Ok(output) = Command::new("df").args(&["-BG", "/"]).output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
+        if let Some(output_str) = command::stdout(Cmd::new("df").args(["-BG", "/"]).run().await) {
             if let Some(line) = output_str.lines().nth(1) {
                 let parts: Vec<&str> = line.split_whitespace().collect();
                 if parts.len() >= 3 {
@@ -565,8 +605,7 @@ impl Power {
         let mut package_managers = Vec::new();
 
         // Check for Nix packages
-        if let Ok(output) = Command::new("nix-env").args(&["-q"]).output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
+        if let Some(output_str) = command::stdout(Cmd::new("nix-env").arg("-q").run().await) {
             let count = output_str.lines().count();
             if count > 0 {
                 package_count += count;
@@ -575,11 +614,12 @@ impl Power {
         }
 
         // Check for system packages on NixOS
-        if let Ok(output) = Command::new("nix-store")
-            .args(&["-q", "--requisites", "/run/current-system"])
-            .output()
-        {
-            let output_str = String::from_utf8_lossy(&output.stdout);
+        if let Some(output_str) = command::stdout(
+            Cmd::new("nix-store")
+                .args(["-q", "--requisites", "/run/current-system"])
+                .run()
+                .await,
+        ) {
             let count = output_str.lines().count();
             if count > 0 {
                 package_count += count;
@@ -588,8 +628,7 @@ impl Power {
         }
 
         // Check for other package managers
-        if let Ok(output) = Command::new("dpkg").args(&["-l"]).output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
+        if let Some(output_str) = command::stdout(Cmd::new("dpkg").arg("-l").run().await) {
             let count = output_str
                 .lines()
                 .filter(|line| line.starts_with("ii"))
@@ -598,15 +637,13 @@ impl Power {
                 package_count += count;
                 package_managers.push("dpkg");
             }
-        } else if let Ok(output) = Command::new("rpm").args(&["-qa"]).output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
+        } else if let Some(output_str) = command::stdout(Cmd::new("rpm").arg("-qa").run().await) {
             let count = output_str.lines().count();
             if count > 0 {
                 package_count += count;
                 package_managers.push("rpm");
             }
-        } else if let Ok(output) = Command::new("pacman").args(&["-Q"]).output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
+        } else if let Some(output_str) = command::stdout(Cmd::new("pacman").arg("-Q").run().await) {
             let count = output_str.lines().count();
             if count > 0 {
                 package_count += count;
@@ -790,4 +827,8 @@ impl WidgetTrait for Power {
     fn popover(&self) -> Option<&Popover> {
         Some(&self.popover)
     }
+
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
 }
@@ -6,20 +6,23 @@ use gtk4::{
     ScrolledWindow,
 };
 use gtk4_layer_shell::LayerShell;
-use std::cell::RefCell;
-use std::path::PathBuf;
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::rc::Rc;
-use std::sync::mpsc;
-use std::thread;
 use std::time::Duration;
 use tracing::info;
 
-use crate::widgets::Widget as WidgetTrait;
+use crate::command::{self, Cmd};
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
+
+/// How long each search tool may run before its results are dropped
+const SEARCH_TIMEOUT: Duration = Duration::from_secs(10);
 
 pub struct Search {
     button: Button,
     popover: Popover,
+    lifecycle: Lifecycle,
 }
 
 #[derive(Debug, Clone)]
@@ -133,6 +136,7 @@ impl Search {
         window_weak: WeakRef<ApplicationWindow>,
         active_popovers: Rc<RefCell<i32>>,
     ) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
         let button = Button::new();
         button.add_css_class("search");
 
@@ -294,8 +298,8 @@ impl Search {
         // Load search config
         let config = SearchConfig::default();
 
-        // Set up search handling
-        let (tx, rx) = mpsc::channel::<Vec<SearchResult>>();
+        // Bumped for every search, so a slow search can't replace newer results
+        let search_generation = Rc::new(Cell::new(0u64));
 
         // Create a mutable search config for storing search settings
         let search_config = Rc::new(RefCell::new(config.clone()));
@@ -330,11 +334,11 @@ impl Search {
         });
 
         // Set up the search button click handler
-        let tx_clone = tx.clone();
         let search_config_clone = search_config.clone();
         let search_entry_clone = search_entry.clone();
         let status_weak = status_label.downgrade();
         let results_list_weak = results_list.downgrade();
+        let popover_weak = popover.downgrade();
 
         let start_search = move || {
             let query = search_entry_clone.text().to_string();
@@ -378,47 +382,25 @@ impl Search {
                 list.append(&spinner);
             }
 
-            let tx = tx_clone.clone();
             let config = search_config_clone.borrow().clone();
-            let query_clone = query.clone();
-
-            // Run search in background thread with timeout to prevent hanging
-            thread::spawn(move || {
-                // Create a channel for search termination
-                let (terminate_tx, terminate_rx) = mpsc::channel::<()>();
-
-                // Spawn another thread to do the actual search
-                let search_thread =
-                    thread::spawn(move || Self::search_files(&query_clone, &config));
-
-                // Create a timeout thread that will signal to terminate after 10 seconds
-                thread::spawn(move || {
-                    thread::sleep(Duration::from_secs(10));
-                    let _ = terminate_tx.send(());
-                });
-
-                // Wait for either search completion or timeout
-                let results = match terminate_rx.recv_timeout(Duration::from_secs(10)) {
-                    // Timeout received but still wait for the search thread
-                    Ok(_) | Err(mpsc::RecvTimeoutError::Timeout) => {
-                        // Don't just return empty results, try to get what we have
-                        match search_thread.join() {
-                            Ok(results) => results,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                    // Channel closed (shouldn't happen)
-                    Err(mpsc::RecvTimeoutError::Disconnected) => {
-                        // Try to get results anyway
-                        match search_thread.join() {
-                            Ok(results) => results,
-                            Err(_) => Vec::new(),
-                        }
-                    }
-                };
-
-                // Send the results back to the main thread
-                let _ = tx.send(results);
+            let generation = search_generation.get() + 1;
+            search_generation.set(generation);
+
+            // The search tools run off the main loop, each with a timeout
+            let search_generation = search_generation.clone();
+            let results_list_weak = results_list_weak.clone();
+            let status_weak = status_weak.clone();
+            let popover_weak = popover_weak.clone();
+            glib::MainContext::default().spawn_local(async move {
+                let results = Self::search_files(&query, &config).await;
+                if search_generation.get() != generation {
+                    return;
+                }
+                if let (Some(list), Some(status)) =
+                    (results_list_weak.upgrade(), status_weak.upgrade())
+                {
+                    Self::show_results(&list, &status, results, popover_weak);
+                }
             });
         };
 
@@ -433,72 +415,6 @@ impl Search {
             start_search();
         });
 
-        // Handle search results with less frequent checking to reduce CPU usage
-        let results_list_weak = results_list.downgrade();
-        let status_weak = status_label.downgrade();
-        let popover_weak = popover.downgrade();
-
-        // Use a more efficient approach to processing search results
-        glib::timeout_add_local(Duration::from_millis(250), move || {
-            // Only process one result per timeout to avoid UI freezes
-            match rx.try_recv() {
-                Ok(results) => {
-                    if let (Some(list), Some(status)) =
-                        (results_list_weak.upgrade(), status_weak.upgrade())
-                    {
-                        // Use idle callback to avoid blocking the main thread
-                        let list_clone = list.clone();
-                        let status_clone = status.clone();
-                        let popover_weak_clone = popover_weak.clone();
-
-                        glib::idle_add_local_once(move || {
-                            // Clear previous results
-                            while let Some(child) = list_clone.first_child() {
-                                list_clone.remove(&child);
-                            }
-
-                            if results.is_empty() {
-                                let no_results = Label::new(Some("No results found"));
-                                no_results.add_css_class("search-empty-label");
-                                no_results.set_vexpand(true);
-                                list_clone.append(&no_results);
-                                status_clone.set_text("No results");
-                            } else {
-                                let count = results.len();
-                                // Show fewer results to improve performance
-                                let displayed = count.min(50);
-
-                                // Process results in chunks to avoid freezing the UI
-                                for (i, result) in results.into_iter().take(displayed).enumerate() {
-                                    // Batch operations to reduce UI updates
-                                    let row =
-                                        Self::create_result_row(result, popover_weak_clone.clone());
-                                    list_clone.append(&row);
-
-                                    // Give UI a chance to update for every 10 items
-                                    if i % 10 == 9 {
-                                        while gtk4::glib::MainContext::default().iteration(false) {}
-                                    }
-                                }
-
-                                if count > displayed {
-                                    status_clone.set_text(&format!(
-                                        "Showing {} of {} results",
-                                        displayed, count
-                                    ));
-                                } else {
-                                    status_clone.set_text(&format!("{} results", count));
-                                }
-                            }
-                        });
-                    }
-                }
-                Err(_) => {} // No results to process
-            }
-
-            glib::ControlFlow::Continue
-        });
-
         // Handle Enter key to activate selected item
         let results_list_for_enter = results_list.clone();
         let _popover_for_enter = popover.downgrade();
@@ -550,10 +466,62 @@ impl Search {
             }
         });
 
-        Ok(Self { button, popover })
+        Ok(Self {
+            button,
+            popover,
+            lifecycle,
+        })
     }
 
-    fn search_files(query: &str, config: &SearchConfig) -> Vec<SearchResult> {
+    /// Replace the list with the results, in an idle callback so the
+    /// popover stays responsive
+    fn show_results(
+        list: &ListBox,
+        status: &Label,
+        results: Vec<SearchResult>,
+        popover_weak: WeakRef<Popover>,
+    ) {
+        let list = list.clone();
+        let status = status.clone();
+        glib::idle_add_local_once(move || {
+            // Clear previous results
+            while let Some(child) = list.first_child() {
+                list.remove(&child);
+            }
+
+            if results.is_empty() {
+                let no_results = Label::new(Some("No results found"));
+                no_results.add_css_class("search-empty-label");
+                no_results.set_vexpand(true);
+                list.append(&no_results);
+                status.set_text("No results");
+            } else {
+                let count = results.len();
+                // Show fewer results to improve performance
+                let displayed = count.min(50);
+
+                // Process results in chunks to avoid freezing the UI
+                for (i, result) in results.into_iter().take(displayed).enumerate() {
+                    // Batch operations to reduce UI updates
+                    let row = Self::create_result_row(result, popover_weak.clone());
+                    list.append(&row);
+
+                    // Give UI a chance to update for every 10 items
+                    if i % 10 == 9 {
+                        while gtk4::glib::MainContext::default().iteration(false) {}
+                    }
+                }
+
+                if count > displayed {
+                    status.set_text(&format!("Showing {} of {} results", displayed, count));
+                } else {
+                    status.set_text(&format!("{} results", count));
+                }
+            }
+        });
+    }
+
+    async fn search_files(query: &str, config: &SearchConfig) -> Vec<SearchResult> {
         if query.is_empty() {
             return Vec::new();
         }
@@ -561,18 +529,20 @@ impl Search {
         let mut results = Vec::new();
 
         // Try fd first (fastest and best)
-        if Self::command_exists("fd") {
-            results = Self::search_with_fd(query, config);
+        if let Some(fd_path) = command::find_in_path("fd") {
+            results = Self::search_with_fd(&fd_path, query, config).await;
         }
 
         // If fd didn't find anything or isn't available, try ripgrep
-        if results.is_empty() && Self::command_exists("rg") {
-            results = Self::search_with_ripgrep(query, config);
+        if results.is_empty() {
+            if let Some(rg_path) = command::find_in_path("rg") {
+                results = Self::search_with_ripgrep(&rg_path, query, config).await;
+            }
         }
 
         // Finally, fall back to find
         if results.is_empty() {
-            results = Self::search_with_find(query, config);
+            results = Self::search_with_find(query, config).await;
         }
 
         // Filter by file type if needed
@@ -611,7 +581,11 @@ impl Search {
             .collect()
     }
 
-    fn search_with_fd(query: &str, config: &SearchConfig) -> Vec<SearchResult> {
+    async fn search_with_fd(
+        fd_path: &Path,
+        query: &str,
+        config: &SearchConfig,
+    ) -> Vec<SearchResult> {
         let mut results = Vec::new();
 
         for inclusion in &config.inclusions {
@@ -619,21 +593,7 @@ impl Search {
                 continue;
             }
 
-            // Use exact fd path from system
-            let fd_path = Command::new("which")
-                .arg("fd")
-                .output()
-                .ok()
-                .and_then(|output| {
-                    if output.status.success() {
-                        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or_else(|| "fd".to_string());
-
-            let mut cmd = Command::new(&fd_path);
+            let mut cmd = Cmd::new(&fd_path.to_string_lossy()).timeout(SEARCH_TIMEOUT);
 
             // Basic arguments
             let mut args = vec![
@@ -651,16 +611,16 @@ impl Search {
                 args.push("--hidden");
             }
 
-            cmd.args(&args);
+            cmd = cmd.args(&args);
 
             // Add exclusions
             for exclusion in &config.exclusions {
-                cmd.args(&["--exclude", exclusion]);
+                cmd = cmd.args(&["--exclude", exclusion]);
             }
 
             // Use case-sensitivity flag if needed
             if !config.case_sensitive {
-                cmd.args(&["--ignore-case"]);
+                cmd = cmd.args(&["--ignore-case"]);
             }
 
             // Build the search pattern - simplified for better results
@@ -672,12 +632,12 @@ impl Search {
                 query.to_string()
             };
 
-            cmd.arg(&pattern);
-            cmd.current_dir(inclusion);
+            cmd = cmd.arg(&pattern);
+            cmd = cmd.current_dir(inclusion);
 
             tracing::info!("Running fd search command: {:?} in {:?}", cmd, inclusion);
 
-            if let Ok(output) = cmd.output() {
+            if let Ok(output) = cmd.run().await {
                 let output_str = String::from_utf8_lossy(&output.stdout);
 
                 for line in output_str.lines() {
@@ -702,7 +662,11 @@ impl Search {
         results
     }
 
-    fn search_with_ripgrep(query: &str, config: &SearchConfig) -> Vec<SearchResult> {
+    async fn search_with_ripgrep(
+        rg_path: &Path,
+        query: &str,
+        config: &SearchConfig,
+    ) -> Vec<SearchResult> {
         let mut results = Vec::new();
 
         for inclusion in &config.inclusions {
@@ -710,21 +674,7 @@ impl Search {
                 continue;
             }
 
-            // Use exact ripgrep path from system
-            let rg_path = Command::new("which")
-                .arg("rg")
-                .output()
-                .ok()
-                .and_then(|output| {
-                    if output.status.success() {
-                        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
-                    } else {
-                        None
-                    }
-                })
-                .unwrap_or_else(|| "rg".to_string());
-
-            let mut cmd = Command::new(&rg_path);
+            let mut cmd = Cmd::new(&rg_path.to_string_lossy()).timeout(SEARCH_TIMEOUT);
 
             // Basic arguments
             let mut args = vec!["--files", "--no-ignore-vcs"];
@@ -734,19 +684,19 @@ impl Search {
                 args.push("--hidden");
             }
 
-            cmd.args(&args);
+            cmd = cmd.args(&args);
 
             // Add exclusions
             for exclusion in &config.exclusions {
-                cmd.args(&["--glob", &format!("!{}", exclusion)]);
+                cmd = cmd.args(&["--glob", &format!("!{}", exclusion)]);
             }
 
             // Filter by pattern if needed
             if !query.is_empty() {
-                cmd.args(&["--glob", &format!("*{}*", query)]);
+                cmd = cmd.args(&["--glob", &format!("*{}*", query)]);
             }
 
-            cmd.current_dir(inclusion);
+            cmd = cmd.current_dir(inclusion);
 
             tracing::info!(
                 "Running ripgrep search command: {:?} in {:?}",
@@ -754,7 +704,7 @@ impl Search {
                 inclusion
             );
 
-            if let Ok(output) = cmd.output() {
+            if let Ok(output) = cmd.run().await {
                 let output_str = String::from_utf8_lossy(&output.stdout);
 
                 // Filter results with fuzzy matching
@@ -784,7 +734,7 @@ impl Search {
         results
     }
 
-    fn search_with_find(query: &str, config: &SearchConfig) -> Vec<SearchResult> {
+    async fn search_with_find(query: &str, config: &SearchConfig) -> Vec<SearchResult> {
         let mut results = Vec::new();
 
         for inclusion in &config.inclusions {
@@ -792,18 +742,17 @@ impl Search {
                 continue;
             }
 
-            let mut cmd = Command::new("find");
-            cmd.arg(inclusion);
-            cmd.args(&["-type", "f", "-o", "-type", "d"]);
+            let mut cmd = Cmd::new("find").timeout(SEARCH_TIMEOUT).arg(inclusion);
+            cmd = cmd.args(&["-type", "f", "-o", "-type", "d"]);
 
             // Add exclusions
             for exclusion in &config.exclusions {
-                cmd.args(&["-not", "-path", &format!("*{}*", exclusion)]);
+                cmd = cmd.args(&["-not", "-path", &format!("*{}*", exclusion)]);
             }
 
             // Skip hidden files if not requested
             if !config.search_hidden {
-                cmd.args(&["-not", "-path", r"*/\.*"]);
+                cmd = cmd.args(&["-not", "-path", r"*/\.*"]);
             }
 
             // Add name pattern if there's a query
@@ -813,12 +762,12 @@ impl Search {
                     .replace("]", "\\]")
                     .replace("*", "\\*")
                     .replace("?", "\\?");
-                cmd.args(&["-name", &format!("*{}*", escaped_query)]);
+                cmd = cmd.args(&["-name", &format!("*{}*", escaped_query)]);
             }
 
             tracing::info!("Running find search command: {:?} in {:?}", cmd, inclusion);
 
-            if let Ok(output) = cmd.output() {
+            if let Ok(output) = cmd.run().await {
                 let output_str = String::from_utf8_lossy(&output.stdout);
 
                 // Filter results with fuzzy matching
@@ -988,45 +937,6 @@ impl Search {
         })
     }
 
-    fn command_exists(cmd: &str) -> bool {
-        // Use specific paths for common tools when available
-        match cmd {
-            "fd" => {
-                if let Ok(output) = Command::new("which").arg("fd").output() {
-                    if output.status.success() {
-                        return true;
-                    }
-                }
-                // Check specific Nix store locations
-                std::path::Path::new("/nix/store").exists()
-                    && Command::new("find")
-                        .args(["/nix/store", "-name", "fd", "-type", "f", "-executable"])
-                        .output()
-                        .map(|output| !output.stdout.is_empty())
-                        .unwrap_or(false)
-            }
-            "rg" => {
-                if let Ok(output) = Command::new("which").arg("rg").output() {
-                    if output.status.success() {
-                        return true;
-                    }
-                }
-                // Check specific Nix store locations
-                std::path::Path::new("/nix/store").exists()
-                    && Command::new("find")
-                        .args(["/nix/store", "-name", "rg", "-type", "f", "-executable"])
-                        .output()
-                        .map(|output| !output.stdout.is_empty())
-                        .unwrap_or(false)
-            }
-            _ => Command::new("which")
-                .arg(cmd)
-                .output()
-                .map(|output| output.status.success())
-                .unwrap_or(false),
-        }
-    }
-
     fn create_result_row(
         result: SearchResult,
         popover_weak: gtk4::glib::WeakRef<Popover>,
@@ -1186,4 +1096,8 @@ impl WidgetTrait for Search {
     fn popover(&self) -> Option<&Popover> {
         Some(&self.popover)
     }
+
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
 }
@@ -18,8 +18,12 @@ use std::rc::Rc;
 use std::time::Duration;
 use tracing::{error, info, warn};
 
+use crate::command::{self, Cmd};
 use crate::widgets::Widget as WidgetTrait;
 
+/// gpg may ask for the passphrase, so give `pass` time
+const PASS_TIMEOUT: Duration = Duration::from_secs(120);
+
 pub struct Secrets {
     button: Button,
     popover: Popover,
@@ -248,11 +252,7 @@ impl Secrets {
     }
 
     fn check_pass_available() -> bool {
-        Command::new("which")
-            .arg("pass")
-            .output()
-            .map(|output| output.status.success())
-            .unwrap_or(false)
+        command::in_path("pass")
     }
 
     fn get_password_store_dir() -> PathBuf {
@@ -509,35 +509,40 @@ impl Secrets {
     }
 
     fn copy_password(path: &str, button: &Button) {
-        match Command::new("pass").arg("-c").arg(path).output() {
-            Ok(output) => {
-                if output.status.success() {
-                    // Visual feedback
-                    button.add_css_class("success");
-                    let button_weak = button.downgrade();
-                    timeout_add_local(Duration::from_millis(1000), move || {
+        let button_weak = button.downgrade();
+        Cmd::new("pass")
+            .args(["-c", path])
+            .timeout(PASS_TIMEOUT)
+            .spawn(move |result| match result {
+                Ok(output) => {
+                    if output.status.success() {
+                        // Visual feedback
                         if let Some(button) = button_weak.upgrade() {
-                            button.remove_css_class("success");
+                            button.add_css_class("success");
                         }
-                        glib::ControlFlow::Break
-                    });
-
-                    // Clear clipboard after 45 seconds
-                    timeout_add_local(Duration::from_secs(45), move || {
-                        let _ = Command::new("wl-copy").arg("--clear").spawn();
-                        glib::ControlFlow::Break
-                    });
-                } else {
-                    error!(
-                        "Failed to copy password: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    );
+                        timeout_add_local(Duration::from_millis(1000), move || {
+                            if let Some(button) = button_weak.upgrade() {
+                                button.remove_css_class("success");
+                            }
+                            glib::ControlFlow::Break
+                        });
+
+                        // Clear clipboard after 45 seconds
+                        timeout_add_local(Duration::from_secs(45), move || {
+                            let _ = Command::new("wl-copy").arg("--clear").spawn();
+                            glib::ControlFlow::Break
+                        });
+                    } else {
+                        error!(
+                            "Failed to copy password: {}",
+                            String::from_utf8_lossy(&output.stderr)
+                        );
+                    }
                 }
-            }
-            Err(e) => {
-                error!("Failed to execute pass: {}", e);
-            }
-        }
+                Err(e) => {
+                    error!("Failed to execute pass: {}", e);
+                }
+            });
     }
 
     fn generate_otp(
@@ -546,115 +551,124 @@ impl Secrets {
         otp_label_weak: WeakRef<Label>,
         timer_label_weak: WeakRef<Label>,
     ) {
-        match Command::new("pass").arg("otp").arg(path).output() {
-            Ok(output) => {
-                if output.status.success() {
-                    let otp_code = String::from_utf8_lossy(&output.stdout).trim().to_string();
-
-                    if let Some(otp_label) = otp_label_weak.upgrade() {
-                        otp_label.set_text(&otp_code);
-
-                        // Copy to clipboard using wl-copy
-                        let _ = Command::new("wl-copy")
-                            .stdin(Stdio::piped())
-                            .spawn()
-                            .and_then(|mut child| {
-                                if let Some(stdin) = child.stdin.as_mut() {
-                                    stdin.write_all(otp_code.as_bytes()).ok();
-                                }
-                                child.wait()
-                            });
-
-                        // Visual feedback
-                        button.add_css_class("success");
-                        let button_weak = button.downgrade();
-                        timeout_add_local(Duration::from_millis(1000), move || {
+        let button_weak = button.downgrade();
+        Cmd::new("pass")
+            .args(["otp", path])
+            .timeout(PASS_TIMEOUT)
+            .spawn(move |result| match result {
+                Ok(output) => {
+                    if output.status.success() {
+                        let otp_code = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+                        if let Some(otp_label) = otp_label_weak.upgrade() {
+                            otp_label.set_text(&otp_code);
+
+                            // Copy to clipboard using wl-copy
+                            let _ = Command::new("wl-copy")
+                                .stdin(Stdio::piped())
+                                .spawn()
+                                .and_then(|mut child| {
+                                    if let Some(stdin) = child.stdin.as_mut() {
+                                        stdin.write_all(otp_code.as_bytes()).ok();
+                                    }
+                                    child.wait()
+                                });
+
+                            // Visual feedback
                             if let Some(button) = button_weak.upgrade() {
-                                button.remove_css_class("success");
+                                button.add_css_class("success");
                             }
-                            glib::ControlFlow::Break
-                        });
+                            timeout_add_local(Duration::from_millis(1000), move || {
+                                if let Some(button) = button_weak.upgrade() {
+                                    button.remove_css_class("success");
+                                }
+                                glib::ControlFlow::Break
+                            });
 
-                        // Start countdown timer (30 seconds for TOTP)
-                        let mut remaining = 30;
-                        timeout_add_local(Duration::from_secs(1), move || {
-                            remaining -= 1;
-
-                            if let (Some(timer), Some(otp)) =
-                                (timer_label_weak.upgrade(), otp_label_weak.upgrade())
-                            {
-                                if remaining > 0 {
-                                    timer.set_text(&format!("{}s", remaining));
-                                    glib::ControlFlow::Continue
+                            // Start countdown timer (30 seconds for TOTP)
+                            let mut remaining = 30;
+                            timeout_add_local(Duration::from_secs(1), move || {
+                                remaining -= 1;
+
+                                if let (Some(timer), Some(otp)) =
+                                    (timer_label_weak.upgrade(), otp_label_weak.upgrade())
+                                {
+                                    if remaining > 0 {
+                                        timer.set_text(&format!("{}s", remaining));
+                                        glib::ControlFlow::Continue
+                                    } else {
+                                        timer.set_text("");
+                                        otp.set_text("------");
+                                        glib::ControlFlow::Break
+                                    }
                                 } else {
-                                    timer.set_text("");
-                                    otp.set_text("------");
                                     glib::ControlFlow::Break
                                 }
-                            } else {
-                                glib::ControlFlow::Break
-                            }
-                        });
+                            });
+                        }
+                    } else {
+                        error!(
+                            "Failed to generate OTP: {}",
+                            String::from_utf8_lossy(&output.stderr)
+                        );
                     }
-                } else {
-                    error!(
-                        "Failed to generate OTP: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    );
                 }
-            }
-            Err(e) => {
-                error!("Failed to execute pass otp: {}", e);
-            }
-        }
+                Err(e) => {
+                    error!("Failed to execute pass otp: {}", e);
+                }
+            });
     }
 
     fn show_secret_details(path: &str, name: &str) {
         // Get full password entry
-        match Command::new("pass").arg(path).output() {
-            Ok(output) => {
-                if output.status.success() {
-                    let content = String::from_utf8_lossy(&output.stdout);
-
-                    // Parse the content
-                    let lines: Vec<&str> = content.lines().collect();
-                    let password = lines.get(0).map(|s| s.to_string());
-
-                    let mut info = SecretInfo {
-                        name: name.to_string(),
-                        password,
-                        username: None,
-                        otp_code: None,
-                        otp_remaining: None,
-                        url: None,
-                        notes: None,
-                    };
-
-                    // Parse additional fields
-                    let mut notes = Vec::new();
-                    for line in lines.iter().skip(1) {
-                        if line.starts_with("Username:") || line.starts_with("username:") {
-                            info.username =
-                                Some(line.split(':').nth(1).unwrap_or("").trim().to_string());
-                        } else if line.starts_with("URL:") || line.starts_with("url:") {
-                            info.url =
-                                Some(line.split(':').nth(1).unwrap_or("").trim().to_string());
-                        } else if !line.trim().is_empty() {
-                            notes.push(line.to_string());
+        let name = name.to_string();
+        Cmd::new("pass")
+            .arg(path)
+            .timeout(PASS_TIMEOUT)
+            .spawn(move |result| match result {
+                Ok(output) => {
+                    if output.status.success() {
+                        let content = String::from_utf8_lossy(&output.stdout);
+
+                        // Parse the content
+                        let lines: Vec<&str> = content.lines().collect();
+                        let password = lines.get(0).map(|s| s.to_string());
+
+                        let mut info = SecretInfo {
+                            name: name.to_string(),
+                            password,
+                            username: None,
+                            otp_code: None,
+                            otp_remaining: None,
+                            url: None,
+                            notes: None,
+                        };
+
+                        // Parse additional fields
+                        let mut notes = Vec::new();
+                        for line in lines.iter().skip(1) {
+                            if line.starts_with("Username:") || line.starts_with("username:") {
+                                info.username =
+                                    Some(line.split(':').nth(1).unwrap_or("").trim().to_string());
+                            } else if line.starts_with("URL:") || line.starts_with("url:") {
+                                info.url =
+                                    Some(line.split(':').nth(1).unwrap_or("").trim().to_string());
+                            } else if !line.trim().is_empty() {
+                                notes.push(line.to_string());
+                            }
                         }
-                    }
 
-                    if !notes.is_empty() {
-                        info.notes = Some(notes.join("\n"));
-                    }
+                        if !notes.is_empty() {
+                            info.notes = Some(notes.join("\n"));
+                        }
 
-                    Self::show_details_dialog(info);
+                        Self::show_details_dialog(info);
+                    }
                 }
-            }
-            Err(e) => {
-                error!("Failed to get password details: {}", e);
-            }
-        }
+                Err(e) => {
+                    error!("Failed to get password details: {}", e);
+                }
+            });
     }
 
     fn show_details_dialog(info: SecretInfo) {
@@ -791,24 +805,30 @@ impl Secrets {
         let store_dir = Self::get_password_store_dir();
 
         // Pull
-        match Command::new("pass").args(&["git", "pull"]).output() {
-            Ok(output) => {
-                if output.status.success() {
-                    info!("Password store synced successfully");
-
-                    // Push
-                    let _ = Command::new("pass").args(&["git", "push"]).spawn();
-                } else {
-                    warn!(
-                        "Failed to sync: {}",
-                        String::from_utf8_lossy(&output.stderr)
-                    );
+        Cmd::new("pass")
+            .args(["git", "pull"])
+            .timeout(Duration::from_secs(60))
+            .spawn(move |result| match result {
+                Ok(output) => {
+                    if output.status.success() {
+                        info!("Password store synced successfully");
+
+                        // Push
+                        Cmd::new("pass")
+                            .args(["git", "push"])
+                            .timeout(Duration::from_secs(60))
+                            .spawn(|_| {});
+                    } else {
+                        warn!(
+                            "Failed to sync: {}",
+                            String::from_utf8_lossy(&output.stderr)
+                        );
+                    }
                 }
-            }
-            Err(e) => {
-                warn!("Failed to run git sync: {}", e);
-            }
-        }
+                Err(e) => {
+                    warn!("Failed to run git sync: {}", e);
+                }
+            });
     }
 
     pub fn widget(&self) -> &Button {
@@ -822,114 +842,3 @@ impl WidgetTrait for Secrets {
         Some(&self.popover)
     }
 }
-
-// Add to your style.css:
-/*
-/* Secrets widget */
-.secrets {
-    background: none;
-    border: none;
-    padding: 0 16px;
-    margin: 0;
-    border-radius: 0;
-    min-height: 32px;
-    transition: all 0.2s ease;
-}
-
-.secrets:hover {
-    background-color: rgba(255, 255, 255, 0.05);
-}
-
-.secrets:active {
-    background-color: rgba(255, 255, 255, 0.1);
-}
-
-/* Secrets popover */
-.secrets-popover {
-    background-color: #383c4a;
-    border-radius: 8px;
-    border: 1px solid #20232b;
-    box-shadow: 0 8px 16px rgba(0, 0, 0, 0.4);
-}
-
-.secrets-search {
-    background-color: #2b2e3b;
-    border: 1px solid #20232b;
-    border-radius: 6px;
-    color: #d3dae3;
-    font-size: 14px;
-    padding: 8px 12px;
-    margin-bottom: 10px;
-}
-
-.secrets-search:focus {
-    border-color: #5294e2;
-    outline: none;
-}
-
-.secrets-list {
-    background-color: transparent;
-}
-
-.secret-row {
-    background-color: transparent;
-    border-radius: 6px;
-    transition: all 0.2s ease;
-}
-
-.secret-row:hover {
-    background-color: rgba(255, 255, 255, 0.05);
-}
-
-.secrets-category-header {
-    font-weight: 600;
-    font-size: 12px;
-    opacity: 0.7;
-    text-transform: uppercase;
-}
-
-.secret-name {
-    font-size: 13px;
-    color: #d3dae3;
-}
-
-.secret-action-button {
-    background: none;
-    border: none;
-    padding: 4px;
-    border-radius: 4px;
-    min-width: 24px;
-    min-height: 24px;
-    transition: all 0.2s ease;
-    opacity: 0.7;
-}
-
-.secret-action-button:hover {
-    background-color: rgba(255, 255, 255, 0.1);
-    opacity: 1;
-}
-
-.secret-action-button.success {
-    background-color: rgba(82, 148, 226, 0.3);
-}
-
-.otp-code {
-    font-family: monospace;
-    font-size: 16px;
-    font-weight: bold;
-    color: #5294e2;
-}
-
-.otp-timer {
-    font-size: 11px;
-    color: #7c818c;
-    font-family: monospace;
-}
-
-.error-label {
-    color: #f27835;
-    font-size: 14px;
-    text-align: center;
-    margin: 40px;
-}
-*/
@@ -6,54 +6,23 @@ use gtk4::{
     Switch, ToggleButton,
 };
 use gtk4_layer_shell::LayerShell;
-use notify::{Config, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
 use std::cell::RefCell;
-use std::fs;
-use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::rc::Rc;
-use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
-use std::thread;
 use std::time::Duration;
 use tracing::{info, warn};
 
-use crate::widgets::Widget as WidgetTrait;
+use crate::command::{self, Cmd};
+use crate::services::audio::{self, AudioInfo};
+use crate::services::media::{self, MediaInfo};
+use crate::state_registry::StateRegistry;
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
 
 pub struct Sound {
     button: Button,
     popover: Popover,
-}
-
-#[derive(Debug, Clone)]
-struct AudioInfo {
-    volume: u32,
-    muted: bool,
-    device_name: String,
-    device_id: String,
-}
-
-#[derive(Debug, Clone)]
-struct MediaInfo {
-    player_name: String,
-    status: String, // Playing, Paused, Stopped
-    artist: String,
-    title: String,
-    album: String,
-    art_url: Option<String>,
-}
-
-impl Default for MediaInfo {
-    fn default() -> Self {
-        Self {
-            player_name: String::new(),
-            status: "Stopped".to_string(),
-            artist: String::new(),
-            title: String::new(),
-            album: String::new(),
-            art_url: None,
-        }
-    }
+    lifecycle: Lifecycle,
 }
 
 #[derive(Debug, Clone)]
@@ -69,6 +38,7 @@ impl Sound {
         window_weak: WeakRef<ApplicationWindow>,
         active_popovers: Rc<RefCell<i32>>,
     ) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
         let button = Button::new();
         button.add_css_class("sound");
 
@@ -310,7 +280,19 @@ impl Sound {
             device_id: String::new(),
         }));
 
-        let media_info = Arc::new(Mutex::new(MediaInfo::default()));
+        // Report the shared volume and media state to `niri-panel get sound`
+        StateRegistry::register("sound", &lifecycle, || {
+            let mut state = serde_json::to_value(audio::service().get().flatten())
+                .unwrap_or_default();
+            if let Some(media) = media::service()
+                .get()
+                .flatten()
+                .and_then(|info| serde_json::to_value(info).ok())
+            {
+                state["media"] = media;
+            }
+            state
+        });
 
         // Set initial state
         icon.set_from_icon_name(Some("audio-volume-medium-symbolic"));
@@ -325,47 +307,8 @@ impl Sound {
         player_name.set_text("");
         play_button.set_icon_name("media-playback-start-symbolic");
 
-        // Schedule immediate update after widget is realized
-        let icon_init = icon.clone();
-        let label_init = label.clone();
-        let volume_scale_init = volume_scale.clone();
-        let volume_label_init = volume_label.clone();
-        let mute_switch_init = mute_switch.clone();
-        let device_list_init = device_list.clone();
-        let audio_info_init = audio_info.clone();
-
-        glib::idle_add_local_once(move || {
-            Self::update_audio(
-                &icon_init,
-                &label_init,
-                &volume_scale_init,
-                &volume_label_init,
-                &mute_switch_init,
-                &device_list_init,
-                audio_info_init,
-            );
-        });
-
-        // Schedule media info update
-        let media_title_init = media_title.clone();
-        let media_artist_init = media_artist.clone();
-        let player_name_init = player_name.clone();
-        let play_button_init = play_button.clone();
-        let media_info_init = media_info.clone();
-
-        glib::idle_add_local_once(move || {
-            Self::update_media(
-                &media_title_init,
-                &media_artist_init,
-                &player_name_init,
-                &play_button_init,
-                media_info_init,
-            );
-        });
-
         // Flag to prevent feedback loops
         let volume_updating = std::rc::Rc::new(std::cell::RefCell::new(false));
-        let volume_updating_clone = volume_updating.clone();
 
         // Handle volume scale changes
         let audio_info_scale = audio_info.clone();
@@ -453,184 +396,75 @@ impl Sound {
             Self::update_device_list(&device_list_for_show);
         });
 
-        // Use less frequent polling for audio updates to reduce resource usage
+        // Follow the shared audio service; its poll and `pactl subscribe`
+        // watch are shared with the sound widgets on other monitors
         let icon_weak = icon.downgrade();
         let label_weak = label.downgrade();
         let volume_scale_weak = volume_scale.downgrade();
         let volume_label_weak = volume_label.downgrade();
         let mute_switch_weak = mute_switch.downgrade();
         let device_list_weak = device_list.downgrade();
-        let audio_info_clone = audio_info.clone();
-        let volume_updating_for_monitor = volume_updating.clone();
-        let mute_updating_for_monitor = mute_updating.clone();
         let popover_weak = popover.downgrade();
-
-        // Track time of last update
-        let last_audio_update = Rc::new(RefCell::new(std::time::Instant::now()));
-
-        glib::timeout_add_local(Duration::from_millis(500), move || {
-            // Only update if we're not currently updating from UI controls
-            if !*volume_updating_for_monitor.borrow() && !*mute_updating_for_monitor.borrow() {
-                // Use adaptive update frequency based on popover visibility
-                let should_update = if let Some(popover) = popover_weak.upgrade() {
-                    if popover.is_visible() {
-                        // Update frequently when popover is visible
-                        true
-                    } else {
-                        // Update less often when popover is hidden
-                        last_audio_update.borrow().elapsed().as_millis() > 1000
-                    }
-                } else {
-                    // Default case
-                    true
-                };
-
-                if should_update {
-                    if let (
-                        Some(icon),
-                        Some(label),
-                        Some(scale),
-                        Some(vol_label),
-                        Some(mute),
-                        Some(device_list),
-                    ) = (
-                        icon_weak.upgrade(),
-                        label_weak.upgrade(),
-                        volume_scale_weak.upgrade(),
-                        volume_label_weak.upgrade(),
-                        mute_switch_weak.upgrade(),
-                        device_list_weak.upgrade(),
-                    ) {
-                        // Spawn a thread to get audio info to avoid blocking the UI thread
-                        let icon_clone = icon.clone();
-                        let label_clone = label.clone();
-                        let scale_clone = scale.clone();
-                        let vol_label_clone = vol_label.clone();
-                        let mute_clone = mute.clone();
-                        let device_list_clone = device_list.clone();
-                        let audio_info_thread = audio_info_clone.clone();
-
-                        *last_audio_update.borrow_mut() = std::time::Instant::now();
-
-                        // Use glib idle to avoid blocking
-                        glib::idle_add_local_once(move || {
-                            Self::update_audio(
-                                &icon_clone,
-                                &label_clone,
-                                &scale_clone,
-                                &vol_label_clone,
-                                &mute_clone,
-                                &device_list_clone,
-                                audio_info_thread,
-                            );
-                        });
-                    }
+        let audio_info_service = audio_info.clone();
+        let audio = audio::service();
+        audio.track_popover(&lifecycle, &popover);
+        audio.subscribe(&lifecycle, move |info| {
+            // Skip updates while the user is dragging the slider or flipping the switch
+            if *volume_updating.borrow() || *mute_updating.borrow() {
+                return;
+            }
+            let Some(info) = info else {
+                return;
+            };
+            if let (
+                Some(icon),
+                Some(label),
+                Some(scale),
+                Some(vol_label),
+                Some(mute),
+                Some(device_list),
+                Some(popover),
+            ) = (
+                icon_weak.upgrade(),
+                label_weak.upgrade(),
+                volume_scale_weak.upgrade(),
+                volume_label_weak.upgrade(),
+                mute_switch_weak.upgrade(),
+                device_list_weak.upgrade(),
+                popover_weak.upgrade(),
+            ) {
+                let device_changed = audio_info_service
+                    .lock()
+                    .map(|stored| stored.device_id != info.device_id)
+                    .unwrap_or(false);
+                if let Ok(mut stored) = audio_info_service.lock() {
+                    *stored = info.clone();
+                }
+                Self::update_audio(&icon, &label, &scale, &vol_label, &mute, info);
+                if device_changed && popover.is_visible() {
+                    Self::update_device_list(&device_list);
                 }
             }
-            glib::ControlFlow::Continue
         });
 
-        // Media info polling with a much slower update frequency
-        // Update less frequently to avoid system resource consumption
+        // Follow the shared media service
         let media_title_weak = media_title.downgrade();
         let media_artist_weak = media_artist.downgrade();
         let player_name_weak = player_name.downgrade();
         let play_button_weak = play_button.downgrade();
-        let media_info_clone = media_info.clone();
-
-        // Use a timer to track when we last updated to avoid too frequent updates
-        let last_update = Rc::new(RefCell::new(std::time::Instant::now()));
-        let popover_weak = popover.downgrade();
-
-        glib::timeout_add_local(Duration::from_millis(3000), move || {
-            // Skip update if popover isn't visible and it hasn't been very long since the last update
-            let should_update = if let Some(popover) = popover_weak.upgrade() {
-                // Always update if popover is visible
-                if popover.is_visible() {
-                    true
-                } else {
-                    // If popover is not visible, only update if it's been at least 10 seconds
-                    let elapsed = last_update.borrow().elapsed().as_secs();
-                    elapsed >= 10
-                }
-            } else {
-                // Default to normal update if we can't check popover
-                true
-            };
-
-            if should_update {
-                if let (Some(title), Some(artist), Some(player), Some(play_btn)) = (
-                    media_title_weak.upgrade(),
-                    media_artist_weak.upgrade(),
-                    player_name_weak.upgrade(),
-                    play_button_weak.upgrade(),
-                ) {
-                    *last_update.borrow_mut() = std::time::Instant::now();
-                    Self::update_media(
-                        &title,
-                        &artist,
-                        &player,
-                        &play_btn,
-                        media_info_clone.clone(),
-                    );
-                }
+        let media = media::service();
+        media.track_popover(&lifecycle, &popover);
+        media.subscribe(&lifecycle, move |info| {
+            if let (Some(title), Some(artist), Some(player), Some(play_btn)) = (
+                media_title_weak.upgrade(),
+                media_artist_weak.upgrade(),
+                player_name_weak.upgrade(),
+                play_button_weak.upgrade(),
+            ) {
+                Self::update_media(&title, &artist, &player, &play_btn, info.as_ref());
             }
-            glib::ControlFlow::Continue
         });
 
-        // Additionally try to set up file system monitoring for faster updates
-        if let Ok(audio_rx) = Self::setup_audio_monitor() {
-            info!("Audio monitoring initialized with file system watcher");
-
-            let icon_weak2 = icon.downgrade();
-            let label_weak2 = label.downgrade();
-            let volume_scale_weak2 = volume_scale.downgrade();
-            let volume_label_weak2 = volume_label.downgrade();
-            let mute_switch_weak2 = mute_switch.downgrade();
-            let device_list_weak2 = device_list.downgrade();
-            let audio_info_clone2 = audio_info.clone();
-            let volume_updating2 = volume_updating.clone();
-            let mute_updating2 = mute_updating.clone();
-
-            // Check for audio updates from file system monitor
-            glib::timeout_add_local(Duration::from_millis(50), move || {
-                // Check if we have any audio updates
-                while let Ok(()) = audio_rx.try_recv() {
-                    // Only update if we're not currently updating from UI controls
-                    if !*volume_updating2.borrow() && !*mute_updating2.borrow() {
-                        if let (
-                            Some(icon),
-                            Some(label),
-                            Some(scale),
-                            Some(vol_label),
-                            Some(mute),
-                            Some(device_list),
-                        ) = (
-                            icon_weak2.upgrade(),
-                            label_weak2.upgrade(),
-                            volume_scale_weak2.upgrade(),
-                            volume_label_weak2.upgrade(),
-                            mute_switch_weak2.upgrade(),
-                            device_list_weak2.upgrade(),
-                        ) {
-                            Self::update_audio(
-                                &icon,
-                                &label,
-                                &scale,
-                                &vol_label,
-                                &mute,
-                                &device_list,
-                                audio_info_clone2.clone(),
-                            );
-                        }
-                    }
-                }
-                glib::ControlFlow::Continue
-            });
-        } else {
-            info!("File system monitoring not available, using polling only");
-        }
-
         // Show popover on click
         let popover_ref = popover.clone();
         button.connect_clicked(move |_| {
@@ -642,93 +476,23 @@ impl Sound {
             gtk4::EventControllerScroll::new(gtk4::EventControllerScrollFlags::VERTICAL);
         let audio_info_scroll = audio_info.clone();
         controller.connect_scroll(move |_, _, dy| {
-            if let Ok(info) = audio_info_scroll.lock() {
+            if let Ok(mut info) = audio_info_scroll.lock() {
                 let current_volume = info.volume as f64;
                 let new_volume = (current_volume - dy * 5.0).clamp(0.0, 100.0) as u32;
+                // Keep track locally so quick scrolls add up
+                info.volume = new_volume;
                 Self::set_volume(new_volume);
+                Self::refresh_soon();
             }
             glib::Propagation::Stop
         });
         button.add_controller(controller);
 
-        Ok(Self { button, popover })
-    }
-
-    fn setup_audio_monitor() -> Result<mpsc::Receiver<()>> {
-        let (tx, rx) = mpsc::channel();
-
-        thread::spawn(move || {
-            // Create channel for watcher events
-            let (watch_tx, watch_rx) = mpsc::channel();
-
-            // Try to find PipeWire/PulseAudio runtime directories to monitor
-            let mut paths_to_watch = Vec::new();
-
-            // Check for PipeWire runtime
-            if let Ok(runtime_dir) = std::env::var("XDG_RUNTIME_DIR") {
-                let pipewire_path = PathBuf::from(&runtime_dir).join("pipewire-0");
-                if pipewire_path.exists() {
-                    paths_to_watch.push(pipewire_path);
-                }
-
-                // Also check for PulseAudio
-                let pulse_path = PathBuf::from(&runtime_dir).join("pulse");
-                if pulse_path.exists() {
-                    paths_to_watch.push(pulse_path);
-                }
-            }
-
-            // If no specific audio paths found, monitor /dev/snd for ALSA changes
-            let dev_snd = PathBuf::from("/dev/snd");
-            if dev_snd.exists() && paths_to_watch.is_empty() {
-                paths_to_watch.push(dev_snd);
-            }
-
-            if paths_to_watch.is_empty() {
-                warn!("No audio paths found to monitor");
-                return;
-            }
-
-            // Create a watcher
-            let mut watcher = match RecommendedWatcher::new(
-                move |res: Result<Event, notify::Error>| {
-                    if let Ok(event) = res {
-                        let _ = watch_tx.send(event);
-                    }
-                },
-                Config::default(),
-            ) {
-                Ok(w) => w,
-                Err(e) => {
-                    warn!("Failed to create file watcher: {}", e);
-                    return;
-                }
-            };
-
-            // Watch all audio paths
-            for path in &paths_to_watch {
-                if let Err(e) = watcher.watch(path, RecursiveMode::NonRecursive) {
-                    warn!("Failed to watch audio path {:?}: {}", path, e);
-                } else {
-                    info!("Watching audio path: {:?}", path);
-                }
-            }
-
-            // Process file change events
-            while let Ok(event) = watch_rx.recv() {
-                match event.kind {
-                    EventKind::Modify(_) | EventKind::Create(_) | EventKind::Remove(_) => {
-                        // Audio system state might have changed, notify the UI
-                        let _ = tx.send(());
-                    }
-                    _ => {}
-                }
-            }
-
-            warn!("Audio monitor thread exiting");
-        });
-
-        Ok(rx)
+        Ok(Self {
+            button,
+            popover,
+            lifecycle,
+        })
     }
 
     fn update_audio(
@@ -737,51 +501,42 @@ impl Sound {
         scale: &Scale,
         volume_label: &Label,
         mute_switch: &Switch,
-        device_list: &ListBox,
-        audio_info: Arc<Mutex<AudioInfo>>,
+        info: &AudioInfo,
     ) {
-        if let Some(info) = Self::get_audio_info() {
-            // Update stored info
-            let mut should_update_ui = false;
-            if let Ok(mut stored_info) = audio_info.lock() {
-                // Only update UI if values actually changed
-                if stored_info.volume != info.volume
-                    || stored_info.muted != info.muted
-                    || stored_info.device_name != info.device_name
-                    || stored_info.device_id != info.device_id
-                {
-                    should_update_ui = true;
-                }
-                *stored_info = info.clone();
-            }
+        // Update icon
+        Self::update_icon(icon, info.volume, info.muted);
 
-            if should_update_ui {
-                // Update icon
-                Self::update_icon(icon, info.volume, info.muted);
+        // Update label
+        if info.muted {
+            label.set_text("Muted");
+        } else {
+            label.set_text(&format!("{}%", info.volume));
+        }
 
-                // Update label
-                if info.muted {
-                    label.set_text("Muted");
-                } else {
-                    label.set_text(&format!("{}%", info.volume));
-                }
+        // Update popover controls
+        scale.set_value(info.volume as f64);
+        volume_label.set_text(&format!("{}%", info.volume));
+        mute_switch.set_active(info.muted);
+        scale.set_sensitive(!info.muted);
+    }
 
-                // Update popover controls
-                scale.set_value(info.volume as f64);
-                volume_label.set_text(&format!("{}%", info.volume));
-                mute_switch.set_active(info.muted);
-                scale.set_sensitive(!info.muted);
+    /// Read the output devices without blocking and show them in the list
+    fn update_device_list(device_list: &ListBox) {
+        let device_list_weak = device_list.downgrade();
+        glib::MainContext::default().spawn_local(async move {
+            let devices = Self::read_audio_devices().await;
+            if let Some(device_list) = device_list_weak.upgrade() {
+                Self::fill_device_list(&device_list, devices);
             }
-        }
+        });
     }
 
-    fn update_device_list(device_list: &ListBox) {
+    fn fill_device_list(device_list: &ListBox, devices: Vec<AudioDevice>) {
         // Clear existing items
         while let Some(child) = device_list.first_child() {
             device_list.remove(&child);
         }
 
-        let devices = Self::get_audio_devices();
         let toggle_buttons: Rc<RefCell<Vec<ToggleButton>>> = Rc::new(RefCell::new(Vec::new()));
 
         for device in devices {
@@ -836,6 +591,7 @@ impl Sound {
                         }
                     }
                     Self::set_default_device(&device_id);
+                    Self::refresh_soon();
                 }
             });
 
@@ -858,11 +614,11 @@ impl Sound {
         icon.set_from_icon_name(Some(icon_name));
     }
 
-    fn get_audio_devices() -> Vec<AudioDevice> {
+    async fn read_audio_devices() -> Vec<AudioDevice> {
         let mut devices = Vec::new();
 
         // Try wpctl first (WirePlumber/PipeWire)
-        if let Ok(output) = Command::new("wpctl").args(&["status"]).output() {
+        if let Ok(output) = Cmd::new("wpctl").arg("status").run().await {
             if output.status.success() {
                 let status_str = String::from_utf8_lossy(&output.stdout);
                 let mut in_sinks_section = false;
@@ -919,16 +675,17 @@ impl Sound {
         }
 
         // Try pactl as fallback (PulseAudio/older PipeWire)
-        if let Ok(output) = Command::new("pactl")
-            .args(&["list", "sinks", "short"])
-            .output()
+        if let Ok(output) = Cmd::new("pactl")
+            .args(["list", "sinks", "short"])
+            .run()
+            .await
         {
             if output.status.success() {
                 let output_str = String::from_utf8_lossy(&output.stdout);
 
                 // Get default sink
                 let default_sink = if let Ok(default_output) =
-                    Command::new("pactl").args(&["get-default-sink"]).output()
+                    Cmd::new("pactl").arg("get-default-sink").run().await
                 {
                     String::from_utf8_lossy(&default_output.stdout)
                         .trim()
@@ -938,8 +695,7 @@ impl Sound {
                 };
 
                 // Get detailed sink info
-                if let Ok(detailed_output) = Command::new("pactl").args(&["list", "sinks"]).output()
-                {
+                if let Ok(detailed_output) = Cmd::new("pactl").args(["list", "sinks"]).run().await {
                     let detailed_str = String::from_utf8_lossy(&detailed_output.stdout);
                     let mut current_sink_name = String::new();
                     let mut current_description = String::new();
@@ -994,234 +750,66 @@ impl Sound {
         devices
     }
 
-    fn get_audio_info() -> Option<AudioInfo> {
-        // Try wpctl first (WirePlumber/PipeWire) - using @DEFAULT_AUDIO_SINK@
-        if let Ok(volume_output) = Command::new("wpctl")
-            .args(&["get-volume", "@DEFAULT_AUDIO_SINK@"])
-            .output()
-        {
-            if volume_output.status.success() && !volume_output.stdout.is_empty() {
-                let volume_str = String::from_utf8_lossy(&volume_output.stdout);
-
-                // Parse volume (format: "Volume: 0.50 [MUTED]" or "Volume: 0.50")
-                let muted = volume_str.contains("[MUTED]");
-                let volume = if let Some(vol_str) = volume_str.split(':').nth(1) {
-                    if let Some(vol_val) = vol_str.split_whitespace().next() {
-                        (vol_val.parse::<f32>().unwrap_or(0.0) * 100.0) as u32
-                    } else {
-                        0
-                    }
-                } else {
-                    0
-                };
-
-                // Get device name and ID from wpctl status
-                let (device_name, device_id) = if let Ok(status_output) =
-                    Command::new("wpctl").arg("status").output()
-                {
-                    let status_str = String::from_utf8_lossy(&status_output.stdout);
-                    let mut in_sinks_section = false;
-                    let mut found_device_name = "Unknown".to_string();
-                    let mut found_device_id = String::new();
-
-                    for line in status_str.lines() {
-                        if line.contains("Sinks:") {
-                            in_sinks_section = true;
-                            continue;
-                        }
-                        if in_sinks_section
-                            && (line.starts_with(" ├─")
-                                || line.starts_with(" └─")
-                                || line.starts_with(" │"))
-                        {
-                            if line.contains("*") {
-                                // Parse format: " │  *   60. Family 17h/19h/1ah HD Audio Controller Analog Stereo [vol: 0.17]"
-                                // Find the part after the number and dot
-                                if let Some(dot_pos) = line.find('.') {
-                                    // Get ID
-                                    let prefix = &line[..dot_pos];
-                                    found_device_id =
-                                        prefix.split_whitespace().last().unwrap_or("").to_string();
-
-                                    let after_dot = &line[dot_pos + 1..].trim();
-                                    // Find where [vol: starts
-                                    if let Some(vol_pos) = after_dot.find("[vol:") {
-                                        found_device_name = after_dot[..vol_pos].trim().to_string();
-                                    } else if let Some(bracket_pos) = after_dot.find('[') {
-                                        found_device_name =
-                                            after_dot[..bracket_pos].trim().to_string();
-                                    } else {
-                                        found_device_name = after_dot.to_string();
-                                    }
-                                    break;
-                                }
-                            }
-                        }
-                        if in_sinks_section && !line.starts_with(" ") && !line.trim().is_empty() {
-                            break;
-                        }
-                    }
-
-                    (found_device_name, found_device_id)
-                } else {
-                    ("Unknown".to_string(), String::new())
-                };
-
-                return Some(AudioInfo {
-                    volume,
-                    muted,
-                    device_name,
-                    device_id,
-                });
-            }
-        }
-
-        // Try pactl as fallback (PulseAudio/older PipeWire)
-        if let Ok(output) = Command::new("pactl")
-            .args(&["get-sink-volume", "@DEFAULT_SINK@"])
-            .output()
-        {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            let volume = output_str
-                .split('/')
-                .nth(1)
-                .and_then(|s| s.trim().trim_end_matches('%').parse::<u32>().ok())
-                .unwrap_or(0);
-
-            let muted = if let Ok(mute_output) = Command::new("pactl")
-                .args(&["get-sink-mute", "@DEFAULT_SINK@"])
-                .output()
-            {
-                String::from_utf8_lossy(&mute_output.stdout).contains("yes")
-            } else {
-                false
-            };
-
-            // Get device name and ID
-            let (device_name, device_id) = if let Ok(device_output) =
-                Command::new("pactl").args(&["get-default-sink"]).output()
-            {
-                let sink_name = String::from_utf8_lossy(&device_output.stdout)
-                    .trim()
-                    .to_string();
-
-                // Get human-readable name
-                if let Ok(desc_output) = Command::new("pactl").args(&["list", "sinks"]).output() {
-                    let desc_str = String::from_utf8_lossy(&desc_output.stdout);
-                    let mut found_sink = false;
-                    for desc_line in desc_str.lines() {
-                        if desc_line.contains(&format!("Name: {}", sink_name)) {
-                            found_sink = true;
-                        }
-                        if found_sink && desc_line.trim().starts_with("Description:") {
-                            let description = desc_line
-                                .split(':')
-                                .nth(1)
-                                .unwrap_or("Unknown")
-                                .trim()
-                                .to_string();
-                            return Some(AudioInfo {
-                                volume,
-                                muted,
-                                device_name: description,
-                                device_id: sink_name.clone(),
-                            });
-                        }
-                    }
-                }
-                (sink_name.clone(), sink_name)
-            } else {
-                ("Unknown".to_string(), String::new())
-            };
-
-            return Some(AudioInfo {
-                volume,
-                muted,
-                device_name,
-                device_id,
-            });
-        }
-
-        // Fallback to amixer (ALSA)
-        if let Ok(output) = Command::new("amixer").args(&["get", "Master"]).output() {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines() {
-                if line.contains("Playback") && line.contains('%') {
-                    let volume = line
-                        .split('[')
-                        .nth(1)
-                        .and_then(|s| s.split('%').next())
-                        .and_then(|s| s.parse::<u32>().ok())
-                        .unwrap_or(0);
-
-                    let muted = line.contains("[off]");
-
-                    return Some(AudioInfo {
-                        volume,
-                        muted,
-                        device_name: "Master".to_string(),
-                        device_id: "master".to_string(),
-                    });
-                }
-            }
-        }
-
-        None
+    /// Poll the audio service once the command just started has had time to apply
+    fn refresh_soon() {
+        glib::timeout_add_local_once(Duration::from_millis(200), || {
+            audio::service().refresh();
+        });
     }
 
     fn set_default_device(device_id: &str) {
         // Try wpctl first (WirePlumber/PipeWire)
-        let _ = Command::new("wpctl")
-            .args(&["set-default", device_id])
-            .spawn();
+        Cmd::new("wpctl")
+            .args(["set-default", device_id])
+            .spawn(|_| {});
 
         // Fallback to pactl (PulseAudio)
-        let _ = Command::new("pactl")
-            .args(&["set-default-sink", device_id])
-            .spawn();
+        Cmd::new("pactl")
+            .args(["set-default-sink", device_id])
+            .spawn(|_| {});
     }
 
     fn set_volume(volume: u32) {
         // Try wpctl first (matches your niri config)
         let volume_float = (volume as f32 / 100.0).to_string();
-        let _ = Command::new("wpctl")
-            .args(&["set-volume", "@DEFAULT_AUDIO_SINK@", &volume_float])
-            .spawn();
+        Cmd::new("wpctl")
+            .args(["set-volume", "@DEFAULT_AUDIO_SINK@", &volume_float])
+            .spawn(|_| {});
 
         // Fallback to pactl
-        let _ = Command::new("pactl")
-            .args(&["set-sink-volume", "@DEFAULT_SINK@", &format!("{}%", volume)])
-            .spawn();
+        Cmd::new("pactl")
+            .args(["set-sink-volume", "@DEFAULT_SINK@", &format!("{}%", volume)])
+            .spawn(|_| {});
 
         // Fallback to amixer
-        let _ = Command::new("amixer")
-            .args(&["set", "Master", &format!("{}%", volume)])
-            .spawn();
+        Cmd::new("amixer")
+            .args(["set", "Master", &format!("{}%", volume)])
+            .spawn(|_| {});
     }
 
     fn toggle_mute(mute: bool) {
         // Try wpctl first (matches your niri config)
-        let _ = Command::new("wpctl")
-            .args(&[
+        Cmd::new("wpctl")
+            .args([
                 "set-mute",
                 "@DEFAULT_AUDIO_SINK@",
                 if mute { "1" } else { "0" },
             ])
-            .spawn();
+            .spawn(|_| {});
 
         // Fallback to pactl
-        let _ = Command::new("pactl")
-            .args(&[
+        Cmd::new("pactl")
+            .args([
                 "set-sink-mute",
                 "@DEFAULT_SINK@",
                 if mute { "1" } else { "0" },
             ])
-            .spawn();
+            .spawn(|_| {});
 
         // Fallback to amixer
-        let _ = Command::new("amixer")
-            .args(&["set", "Master", if mute { "mute" } else { "unmute" }])
-            .spawn();
+        Cmd::new("amixer")
+            .args(["set", "Master", if mute { "mute" } else { "unmute" }])
+            .spawn(|_| {});
     }
 
     fn open_sound_settings() {
@@ -1247,161 +835,58 @@ impl Sound {
         artist: &Label,
         player_name: &Label,
         play_button: &Button,
-        media_info: Arc<Mutex<MediaInfo>>,
+        info: Option<&MediaInfo>,
     ) {
-        if let Some(new_info) = Self::get_media_info() {
-            let mut should_update_ui = false;
-
-            if let Ok(mut stored_info) = media_info.lock() {
-                // Only update UI if values changed
-                if stored_info.title != new_info.title
-                    || stored_info.artist != new_info.artist
-                    || stored_info.player_name != new_info.player_name
-                    || stored_info.status != new_info.status
-                {
-                    should_update_ui = true;
-                }
-                *stored_info = new_info.clone();
-            }
-
-            if should_update_ui {
-                // Update title and artist
-                if new_info.title.is_empty() {
-                    title.set_text("No media playing");
-                    artist.set_text("");
-                } else {
-                    title.set_text(&new_info.title);
-                    artist.set_text(&new_info.artist);
-                }
-
-                // Update player name if available
-                if new_info.player_name.is_empty() {
-                    player_name.set_text("");
-                } else {
-                    let display_name = Self::format_player_name(&new_info.player_name);
-                    player_name.set_text(&display_name);
-                }
-
-                // Update play/pause button icon
-                match new_info.status.as_str() {
-                    "Playing" => play_button.set_icon_name("media-playback-pause-symbolic"),
-                    _ => play_button.set_icon_name("media-playback-start-symbolic"),
-                }
-            }
-        } else {
+        let Some(info) = info else {
             // No media playing
             title.set_text("No media playing");
             artist.set_text("");
             player_name.set_text("");
             play_button.set_icon_name("media-playback-start-symbolic");
-
-            // Clear stored info
-            if let Ok(mut stored_info) = media_info.lock() {
-                *stored_info = MediaInfo::default();
-            }
-        }
-    }
-
-    fn get_media_info() -> Option<MediaInfo> {
-        use std::sync::atomic::{AtomicBool, Ordering};
-
-        // Static flag to avoid repeated checks for playerctl availability
-        static PLAYERCTL_AVAILABLE: AtomicBool = AtomicBool::new(true);
-
-        if !PLAYERCTL_AVAILABLE.load(Ordering::Relaxed) {
-            return None;
-        }
-
-        // Use a single playerctl command with a combined format to get all info at once
-        // This reduces the process spawns from 3 to 1, which is a major performance improvement
-        let format_str = "{{status}}\\n{{playerName}}\\n{{artist}}\\n{{title}}\\n{{album}}";
-
-        // Set a timeout to prevent hanging on playerctl command
-        let mut cmd = Command::new("playerctl");
-        cmd.args(&["metadata", "--format", format_str]);
-
-        // Use an Option to store the result and avoid blocking on wait()
-        let output_result = match cmd.output() {
-            Ok(output) => {
-                if !output.status.success() {
-                    return None; // No players available
-                }
-                output
-            }
-            Err(_) => {
-                // Mark playerctl as unavailable to avoid future attempts
-                PLAYERCTL_AVAILABLE.store(false, Ordering::Relaxed);
-                return None;
-            }
+            return;
         };
 
-        // Process the output
-        let metadata_str = String::from_utf8_lossy(&output_result.stdout).to_string();
-        let parts: Vec<&str> = metadata_str.lines().collect();
-
-        // Extract the parts
-        let status = if parts.len() > 0 {
-            match parts[0] {
-                "Playing" | "Paused" | "Stopped" => parts[0].to_string(),
-                _ => "Stopped".to_string(),
-            }
+        // Update title and artist
+        if info.title.is_empty() {
+            title.set_text("No media playing");
+            artist.set_text("");
         } else {
-            "Stopped".to_string()
-        };
-
-        // If stopped, return default info to avoid further processing
-        if status == "Stopped" {
-            return Some(MediaInfo::default());
+            title.set_text(&info.title);
+            artist.set_text(&info.artist);
         }
 
-        let player_name = if parts.len() > 1 {
-            parts[1].to_string()
-        } else {
-            String::new()
-        };
-        let artist = if parts.len() > 2 {
-            parts[2].to_string()
-        } else {
-            String::new()
-        };
-        let title = if parts.len() > 3 {
-            parts[3].to_string()
-        } else {
-            String::new()
-        };
-        let album = if parts.len() > 4 {
-            parts[4].to_string()
+        // Update player name if available
+        if info.player_name.is_empty() {
+            player_name.set_text("");
         } else {
-            String::new()
-        };
+            let display_name = Self::format_player_name(&info.player_name);
+            player_name.set_text(&display_name);
+        }
 
-        Some(MediaInfo {
-            player_name,
-            status,
-            artist,
-            title,
-            album,
-            art_url: None,
-        })
+        // Update play/pause button icon
+        match info.status.as_str() {
+            "Playing" => play_button.set_icon_name("media-playback-pause-symbolic"),
+            _ => play_button.set_icon_name("media-playback-start-symbolic"),
+        }
     }
 
     fn media_play_pause() {
-        // Spawn a thread to avoid blocking the UI
-        thread::spawn(|| {
-            let _ = Command::new("playerctl").args(&["play-pause"]).spawn();
-        });
+        Self::playerctl("play-pause");
     }
 
     fn media_next() {
-        thread::spawn(|| {
-            let _ = Command::new("playerctl").args(&["next"]).spawn();
-        });
+        Self::playerctl("next");
     }
 
     fn media_previous() {
-        thread::spawn(|| {
-            let _ = Command::new("playerctl").args(&["previous"]).spawn();
-        });
+        Self::playerctl("previous");
+    }
+
+    /// Send a command to the active player, then read its new state
+    fn playerctl(command: &str) {
+        Cmd::new("playerctl")
+            .arg(command)
+            .spawn(|_| media::service().refresh());
     }
 
     fn format_player_name(name: &str) -> String {
@@ -1418,44 +903,21 @@ impl Sound {
     }
 
     fn command_exists(cmd: &str) -> bool {
-        // Simple approach that doesn't use caching but avoids repeated checks
-        // through static flags - less than ideal but works with existing Rust version
-
-        // For playerctl specifically
-        if cmd == "playerctl" {
-            use std::sync::atomic::{AtomicBool, Ordering};
-            static PLAYERCTL_CHECKED: AtomicBool = AtomicBool::new(false);
-            static PLAYERCTL_AVAILABLE: AtomicBool = AtomicBool::new(false);
-
-            if PLAYERCTL_CHECKED.load(Ordering::Relaxed) {
-                return PLAYERCTL_AVAILABLE.load(Ordering::Relaxed);
-            }
-
-            let result = Command::new("which")
-                .arg(cmd)
-                .output()
-                .map(|output| output.status.success())
-                .unwrap_or(false);
-
-            PLAYERCTL_AVAILABLE.store(result, Ordering::Relaxed);
-            PLAYERCTL_CHECKED.store(true, Ordering::Relaxed);
-
-            return result;
-        }
-
-        // For other commands, just check normally
-        Command::new("which")
-            .arg(cmd)
-            .output()
-            .map(|output| output.status.success())
-            .unwrap_or(false)
+        command::in_path(cmd)
     }
 
     pub fn widget(&self) -> &Button {
         &self.button
     }
+}
 
-    pub fn popover(&self) -> Option<&Popover> {
+// Implementation of Widget trait
+impl WidgetTrait for Sound {
+    fn popover(&self) -> Option<&Popover> {
         Some(&self.popover)
     }
+
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
 }
@@ -0,0 +1,76 @@
+use gtk4::prelude::*;
+use gtk4::{Box, Button, Image, Label, Orientation, Popover};
+
+/// A warning icon that stays hidden until there is a problem to report,
+/// such as a config file that failed to load
+#[derive(Clone)]
+pub struct StatusIndicator {
+    button: Button,
+    message_label: Label,
+}
+
+impl StatusIndicator {
+    pub fn new(css_class: &str, title: &str) -> Self {
+        let button = Button::new();
+        button.add_css_class("status-indicator");
+        button.add_css_class(css_class);
+        button.set_child(Some(&Image::from_icon_name("dialog-warning-symbolic")));
+        button.set_visible(false);
+
+        // Popover with the full problem description
+        let popover = Popover::new();
+        popover.set_parent(&button);
+        popover.add_css_class("status-indicator-popover");
+        popover.set_autohide(true);
+
+        let popover_box = Box::new(Orientation::Vertical, 5);
+        popover_box.set_margin_top(10);
+        popover_box.set_margin_bottom(10);
+        popover_box.set_margin_start(10);
+        popover_box.set_margin_end(10);
+
+        let title_label = Label::new(Some(title));
+        title_label.add_css_class("status-indicator-title");
+        title_label.set_halign(gtk4::Align::Start);
+        popover_box.append(&title_label);
+
+        let message_label = Label::new(None);
+        message_label.add_css_class("status-indicator-message");
+        message_label.set_halign(gtk4::Align::Start);
+        message_label.set_wrap(true);
+        message_label.set_max_width_chars(60);
+        message_label.set_selectable(true);
+        popover_box.append(&message_label);
+
+        popover.set_child(Some(&popover_box));
+
+        button.connect_clicked(move |_| {
+            popover.popup();
+        });
+
+        Self {
+            button,
+            message_label,
+        }
+    }
+
+    /// Show the indicator with the given problem, or hide it when there is none
+    pub fn set_problem(&self, problem: Option<&str>) {
+        match problem {
+            Some(message) => {
+                self.message_label.set_text(message);
+                self.button.set_tooltip_text(Some(message));
+                self.button.set_visible(true);
+            }
+            None => {
+                self.message_label.set_text("");
+                self.button.set_tooltip_text(None);
+                self.button.set_visible(false);
+            }
+        }
+    }
+
+    pub fn widget(&self) -> &Button {
+        &self.button
+    }
+}
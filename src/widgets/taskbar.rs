@@ -0,0 +1,188 @@
+use anyhow::Result;
+use gtk4::prelude::*;
+use gtk4::{Box, Button, Image, Label, Orientation, Overlay};
+use std::collections::HashMap;
+use tracing::debug;
+
+use crate::config::TaskbarConfig;
+use crate::niri_ipc::{NiriEvent, WindowInfo};
+use crate::niri_state::{self, NiriState};
+use crate::widgets::{Lifecycle, Widget as WidgetTrait, Workspaces};
+
+/// One button per open window, or per app when grouping is enabled
+pub struct Taskbar {
+    container: Box,
+    // Unsubscribes from the niri state
+    lifecycle: Lifecycle,
+}
+
+/// Windows shown by one taskbar button, in workspace order
+struct TaskbarEntry {
+    app_id: Option<String>,
+    windows: Vec<WindowInfo>,
+}
+
+impl TaskbarEntry {
+    /// The window that clicks act on: the focused one, or else the first
+    fn target(&self) -> &WindowInfo {
+        self.windows
+            .iter()
+            .find(|window| window.is_focused)
+            .unwrap_or(&self.windows[0])
+    }
+
+    /// The window a left click focuses; repeated clicks cycle through a group
+    fn next_to_focus(&self) -> &WindowInfo {
+        match self.windows.iter().position(|window| window.is_focused) {
+            Some(idx) => &self.windows[(idx + 1) % self.windows.len()],
+            None => &self.windows[0],
+        }
+    }
+}
+
+impl Taskbar {
+    pub fn new(output: Option<String>, config: &TaskbarConfig) -> Result<Self> {
+        let container = Box::new(Orientation::Horizontal, 2);
+        container.add_css_class("taskbar");
+
+        let lifecycle = Lifecycle::new();
+        let group_by_app = config.group_by_app;
+
+        let container_weak = container.downgrade();
+        let output_subscribe = output.clone();
+        niri_state::subscribe(&lifecycle, move |niri, event| {
+            let relevant = matches!(
+                event,
+                NiriEvent::WindowsChanged { .. }
+                    | NiriEvent::WindowOpenedOrChanged { .. }
+                    | NiriEvent::WindowClosed { .. }
+                    | NiriEvent::WindowFocusChanged { .. }
+                    | NiriEvent::WindowUrgencyChanged { .. }
+                    | NiriEvent::WorkspacesChanged { .. }
+                    | NiriEvent::ConnectionChanged { .. }
+            );
+            if !relevant {
+                return;
+            }
+            if let Some(container) = container_weak.upgrade() {
+                Self::update(&container, niri, output_subscribe.as_deref(), group_by_app);
+            }
+        });
+        niri_state::with(|niri| Self::update(&container, niri, output.as_deref(), group_by_app));
+
+        Ok(Self {
+            container,
+            lifecycle,
+        })
+    }
+
+    /// Windows on this output's workspaces, ordered by workspace and then by
+    /// window id, optionally grouped by app
+    fn entries(niri: &NiriState, output: Option<&str>, group_by_app: bool) -> Vec<TaskbarEntry> {
+        let mut workspaces = niri.workspaces_on_output(output);
+        workspaces.sort_by(|a, b| a.output.cmp(&b.output).then(a.idx.cmp(&b.idx)));
+
+        let windows = workspaces
+            .iter()
+            .flat_map(|workspace| niri.windows_on_workspace(workspace.id));
+
+        let mut entries: Vec<TaskbarEntry> = Vec::new();
+        let mut app_to_entry: HashMap<String, usize> = HashMap::new();
+        for window in windows {
+            // Windows without an app_id can't be grouped with anything
+            let group = window.app_id.clone().filter(|_| group_by_app);
+            match group.as_ref().and_then(|app_id| app_to_entry.get(app_id)) {
+                Some(&idx) => entries[idx].windows.push(window),
+                None => {
+                    if let Some(app_id) = group {
+                        app_to_entry.insert(app_id, entries.len());
+                    }
+                    entries.push(TaskbarEntry {
+                        app_id: window.app_id.clone(),
+                        windows: vec![window],
+                    });
+                }
+            }
+        }
+        entries
+    }
+
+    fn update(container: &Box, niri: &NiriState, output: Option<&str>, group_by_app: bool) {
+        let entries = Self::entries(niri, output, group_by_app);
+        debug!("Taskbar showing {} entries", entries.len());
+
+        // Window counts are small, so rebuild rather than diff
+        while let Some(child) = container.first_child() {
+            container.remove(&child);
+        }
+        for entry in &entries {
+            container.append(&Self::create_button(entry));
+        }
+    }
+
+    fn create_button(entry: &TaskbarEntry) -> Button {
+        let button = Button::new();
+        button.add_css_class("taskbar-item");
+
+        if entry.windows.iter().any(|window| window.is_focused) {
+            button.add_css_class("focused");
+        }
+        if entry.windows.iter().any(|window| window.is_urgent) {
+            button.add_css_class("urgent");
+        }
+
+        let icon = match &entry.app_id {
+            Some(app_id) => Image::from_icon_name(&Workspaces::get_app_icon(app_id)),
+            None => Image::from_icon_name("application-x-executable-symbolic"),
+        };
+
+        // Grouped windows show how many windows the button stands for
+        if entry.windows.len() > 1 {
+            let overlay = Overlay::new();
+            overlay.set_child(Some(&icon));
+            let count = Label::new(Some(&entry.windows.len().to_string()));
+            count.add_css_class("taskbar-count");
+            count.set_halign(gtk4::Align::End);
+            count.set_valign(gtk4::Align::End);
+            overlay.add_overlay(&count);
+            button.set_child(Some(&overlay));
+        } else {
+            button.set_child(Some(&icon));
+        }
+
+        let tooltip = entry
+            .windows
+            .iter()
+            .map(|window| window.title.as_str())
+            .collect::<Vec<_>>()
+            .join("\n");
+        button.set_tooltip_text(Some(&tooltip));
+
+        // Left click - focus the window, cycling through a group
+        let focus_id = entry.next_to_focus().id;
+        button.connect_clicked(move |_| {
+            Workspaces::focus_window(focus_id);
+        });
+
+        // Middle click - close the window
+        let close_id = entry.target().id;
+        let middle_click = gtk4::GestureClick::new();
+        middle_click.set_button(2);
+        middle_click.connect_released(move |_, _, _, _| {
+            Workspaces::close_window(close_id);
+        });
+        button.add_controller(middle_click);
+
+        button
+    }
+
+    pub fn widget(&self) -> &Box {
+        &self.container
+    }
+}
+
+impl WidgetTrait for Taskbar {
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
+}
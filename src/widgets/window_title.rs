@@ -0,0 +1,351 @@
+use anyhow::Result;
+use gtk4::glib::WeakRef;
+use gtk4::prelude::*;
+use gtk4::{ApplicationWindow, Box, Button, Image, Label, Orientation, Popover, Separator};
+use gtk4_layer_shell::LayerShell;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use tracing::{info, warn};
+
+use crate::niri_ipc::{self, Action, NiriEvent, WindowInfo, WorkspaceReference};
+use crate::niri_state::{self, NiriState};
+use crate::widgets::{Lifecycle, Widget as WidgetTrait, Workspaces};
+
+/// Titles longer than this are ellipsized
+const MAX_TITLE_CHARS: i32 = 60;
+
+/// Shows the title and app icon of the active window on the panel's output
+pub struct WindowTitle {
+    button: Button,
+    popover: Popover,
+    lifecycle: Lifecycle,
+}
+
+impl WindowTitle {
+    pub fn new(
+        output: Option<String>,
+        window_weak: WeakRef<ApplicationWindow>,
+        active_popovers: Rc<RefCell<i32>>,
+    ) -> Result<Self> {
+        let lifecycle = Lifecycle::new();
+        let button = Button::new();
+        button.add_css_class("window-title");
+
+        let container = Box::new(Orientation::Horizontal, 6);
+
+        let icon = Image::new();
+        icon.add_css_class("window-title-icon");
+
+        let label = Label::new(None);
+        label.add_css_class("window-title-label");
+        label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        label.set_max_width_chars(MAX_TITLE_CHARS);
+        label.set_single_line_mode(true);
+
+        container.append(&icon);
+        container.append(&label);
+        button.set_child(Some(&container));
+
+        // Create popover for window actions
+        let popover = Popover::new();
+        popover.set_parent(&button);
+        popover.add_css_class("window-title-popover");
+        popover.set_autohide(true);
+
+        // The window the widget currently shows
+        let window_id: Rc<Cell<Option<u64>>> = Rc::new(Cell::new(None));
+
+        // Handle popover show/hide for keyboard mode. The actions are filled
+        // in on show, so opening the popover over IPC gets current data too.
+        let window_weak_show = window_weak.clone();
+        let active_popovers_show = active_popovers.clone();
+        let window_id_show = window_id.clone();
+        popover.connect_show(move |popover| {
+            *active_popovers_show.borrow_mut() += 1;
+            if let Some(window) = window_weak_show.upgrade() {
+                window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::OnDemand);
+                info!("Window title popover shown - keyboard mode set to OnDemand");
+            }
+            niri_state::with(|niri| {
+                match window_id_show.get().and_then(|id| niri.window(id)) {
+                    Some(window) => Self::update_popover(popover, window, niri),
+                    None => Self::show_no_window(popover),
+                }
+            });
+        });
+
+        let window_weak_hide = window_weak.clone();
+        let active_popovers_hide = active_popovers.clone();
+        popover.connect_hide(move |_| {
+            *active_popovers_hide.borrow_mut() -= 1;
+            if *active_popovers_hide.borrow() == 0 {
+                if let Some(window) = window_weak_hide.upgrade() {
+                    window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::None);
+                    info!("Window title popover hidden - keyboard mode set to None");
+                }
+            }
+        });
+
+        // Follow focus and title changes from the shared niri state
+        let output_update = output.clone();
+        let button_weak = button.downgrade();
+        let icon_weak = icon.downgrade();
+        let label_weak = label.downgrade();
+        let window_id_update = window_id.clone();
+        niri_state::subscribe(&lifecycle, move |niri, event| {
+            let relevant = matches!(
+                event,
+                NiriEvent::WindowFocusChanged { .. }
+                    | NiriEvent::WorkspacesChanged { .. }
+                    | NiriEvent::WorkspaceActivated { .. }
+                    | NiriEvent::WorkspaceActiveWindowChanged { .. }
+                    | NiriEvent::WindowOpenedOrChanged { .. }
+                    | NiriEvent::WindowClosed { .. }
+                    | NiriEvent::WindowsChanged { .. }
+                    | NiriEvent::ConnectionChanged { .. }
+            );
+            if !relevant {
+                return;
+            }
+            if let (Some(button), Some(icon), Some(label)) =
+                (button_weak.upgrade(), icon_weak.upgrade(), label_weak.upgrade())
+            {
+                Self::update(
+                    &button,
+                    &icon,
+                    &label,
+                    &window_id_update,
+                    niri,
+                    output_update.as_deref(),
+                );
+            }
+        });
+        niri_state::with(|niri| {
+            Self::update(&button, &icon, &label, &window_id, niri, output.as_deref())
+        });
+
+        // Middle click closes the window
+        let middle_click = gtk4::GestureClick::new();
+        middle_click.set_button(2);
+        let window_id_close = window_id.clone();
+        middle_click.connect_released(move |_, _, _, _| {
+            if let Some(id) = window_id_close.get() {
+                Workspaces::close_window(id);
+            }
+        });
+        button.add_controller(middle_click);
+
+        // Handle Escape key
+        let escape_controller = gtk4::EventControllerKey::new();
+        let popover_weak_escape = popover.downgrade();
+        escape_controller.connect_key_pressed(move |_, key, _, _| {
+            if key == gtk4::gdk::Key::Escape {
+                if let Some(popover) = popover_weak_escape.upgrade() {
+                    popover.popdown();
+                }
+                gtk4::glib::Propagation::Stop
+            } else {
+                gtk4::glib::Propagation::Proceed
+            }
+        });
+        popover.add_controller(escape_controller);
+
+        // Left click shows the window actions
+        let popover_ref = popover.clone();
+        let window_id_popover = window_id.clone();
+        button.connect_clicked(move |_| {
+            if window_id_popover.get().is_some() {
+                popover_ref.popup();
+            }
+        });
+
+        Ok(Self {
+            button,
+            popover,
+            lifecycle,
+        })
+    }
+
+    fn update(
+        button: &Button,
+        icon: &Image,
+        label: &Label,
+        window_id: &Cell<Option<u64>>,
+        niri: &NiriState,
+        output: Option<&str>,
+    ) {
+        match niri.active_window_on_output(output) {
+            Some(window) => {
+                window_id.set(Some(window.id));
+                match &window.app_id {
+                    Some(app_id) => icon.set_from_icon_name(Some(&Workspaces::get_app_icon(app_id))),
+                    None => icon.set_from_icon_name(Some("application-x-executable-symbolic")),
+                }
+                label.set_text(&window.title);
+                button.set_tooltip_text(Some(&window.title));
+                button.set_visible(true);
+            }
+            None => {
+                // No window, e.g. an empty workspace
+                window_id.set(None);
+                label.set_text("");
+                button.set_tooltip_text(None);
+                button.set_visible(false);
+            }
+        }
+    }
+
+    /// Shown when the popover is opened over IPC with no active window
+    fn show_no_window(popover: &Popover) {
+        let label = Label::new(Some("No active window"));
+        label.add_css_class("window-title-popover-empty");
+        label.set_margin_top(10);
+        label.set_margin_bottom(10);
+        label.set_margin_start(10);
+        label.set_margin_end(10);
+        popover.set_child(Some(&label));
+    }
+
+    fn update_popover(popover: &Popover, window: &WindowInfo, niri: &NiriState) {
+        let popover_box = Box::new(Orientation::Vertical, 5);
+        popover_box.set_margin_top(10);
+        popover_box.set_margin_bottom(10);
+        popover_box.set_margin_start(10);
+        popover_box.set_margin_end(10);
+        popover_box.set_size_request(250, -1);
+
+        let title_label = Label::new(Some(&window.title));
+        title_label.add_css_class("window-title-popover-title");
+        title_label.set_halign(gtk4::Align::Start);
+        title_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+        title_label.set_max_width_chars(40);
+        popover_box.append(&title_label);
+
+        if let Some(app_id) = &window.app_id {
+            let app_label = Label::new(Some(app_id));
+            app_label.add_css_class("window-title-popover-app");
+            app_label.set_halign(gtk4::Align::Start);
+            popover_box.append(&app_label);
+        }
+
+        popover_box.append(&Separator::new(Orientation::Horizontal));
+
+        let id = window.id;
+        let floating_label = if window.is_floating {
+            "Move to Tiling"
+        } else {
+            "Move to Floating"
+        };
+        popover_box.append(&Self::action_button(
+            popover,
+            "window-restore-symbolic",
+            floating_label,
+            Action::ToggleWindowFloating { id: Some(id) },
+        ));
+        popover_box.append(&Self::action_button(
+            popover,
+            "view-fullscreen-symbolic",
+            "Toggle Fullscreen",
+            Action::FullscreenWindow { id: Some(id) },
+        ));
+
+        // Move to another workspace on the window's output
+        let output = window
+            .workspace_id
+            .and_then(|id| niri.workspace(id))
+            .map(|workspace| workspace.output.as_str());
+        let workspaces: Vec<_> = niri
+            .workspaces_on_output(output)
+            .into_iter()
+            .filter(|workspace| Some(workspace.id) != window.workspace_id)
+            .collect();
+        if !workspaces.is_empty() {
+            let move_label = Label::new(Some("Move to Workspace"));
+            move_label.add_css_class("window-title-popover-section");
+            move_label.set_halign(gtk4::Align::Start);
+            move_label.set_margin_top(5);
+            popover_box.append(&move_label);
+
+            let workspace_box = Box::new(Orientation::Horizontal, 5);
+            for workspace in workspaces {
+                let label = workspace
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| workspace.idx.to_string());
+                let workspace_button = Button::with_label(&label);
+                workspace_button.add_css_class("window-title-workspace");
+                let popover_weak = popover.downgrade();
+                let workspace_id = workspace.id;
+                workspace_button.connect_clicked(move |_| {
+                    Self::run_action(Action::MoveWindowToWorkspace {
+                        window_id: Some(id),
+                        reference: WorkspaceReference::Id(workspace_id),
+                        focus: true,
+                    });
+                    if let Some(popover) = popover_weak.upgrade() {
+                        popover.popdown();
+                    }
+                });
+                workspace_box.append(&workspace_button);
+            }
+            popover_box.append(&workspace_box);
+        }
+
+        popover_box.append(&Separator::new(Orientation::Horizontal));
+
+        let close_button = Self::action_button(
+            popover,
+            "window-close-symbolic",
+            "Close Window",
+            Action::CloseWindow { id: Some(id) },
+        );
+        close_button.add_css_class("destructive-action");
+        popover_box.append(&close_button);
+
+        popover.set_child(Some(&popover_box));
+    }
+
+    /// A flat button that runs a niri action and closes the popover
+    fn action_button(popover: &Popover, icon_name: &str, label: &str, action: Action) -> Button {
+        let button = Button::new();
+        button.add_css_class("flat");
+        button.add_css_class("window-title-action");
+
+        let button_box = Box::new(Orientation::Horizontal, 10);
+        button_box.append(&Image::from_icon_name(icon_name));
+        let label = Label::new(Some(label));
+        label.set_halign(gtk4::Align::Start);
+        button_box.append(&label);
+        button.set_child(Some(&button_box));
+
+        let popover_weak = popover.downgrade();
+        button.connect_clicked(move |_| {
+            Self::run_action(action.clone());
+            if let Some(popover) = popover_weak.upgrade() {
+                popover.popdown();
+            }
+        });
+
+        button
+    }
+
+    fn run_action(action: Action) {
+        if let Err(e) = niri_ipc::action(action) {
+            warn!("Failed to run window action: {:#}", e);
+        }
+    }
+
+    pub fn widget(&self) -> &Button {
+        &self.button
+    }
+}
+
+impl WidgetTrait for WindowTitle {
+    fn popover(&self) -> Option<&Popover> {
+        Some(&self.popover)
+    }
+
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
+}
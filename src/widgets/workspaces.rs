@@ -1,70 +1,58 @@
 use anyhow::Result;
 use gtk4::prelude::*;
 use gtk4::{Box, Button, Image, Label, ListBox, ListBoxRow, Orientation, Popover};
-use serde_json::Value;
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::process::{Child, Command};
 use std::rc::Rc;
-use tracing::{debug, error, info, warn};
+use tracing::{debug, info, warn};
 
-use crate::niri_ipc::{self, NiriEvent, WindowInfo, WorkspaceInfo};
+use crate::niri_ipc::{self, Action, NiriEvent, WindowInfo, WorkspaceInfo, WorkspaceReference};
+use crate::niri_state::{self, NiriState};
+use crate::widgets::{Lifecycle, Widget as WidgetTrait};
 
 pub struct Workspaces {
     container: Box,
-    // Shared state for workspaces and windows
+    // Buttons for the workspaces shown on this output
     state: Rc<RefCell<WorkspacesState>>,
-    // Keep the child process alive
-    _event_stream_child: Option<Child>,
-    // Keep the GLib source ID for cleanup
-    _event_source_id: Option<gtk4::glib::SourceId>,
+    // Unsubscribes from the niri state
+    lifecycle: Lifecycle,
 }
 
 #[derive(Default)]
 struct WorkspacesState {
-    workspaces: Vec<WorkspaceInfo>,
-    windows: Vec<WindowInfo>,
+    // Output (connector name) whose workspaces are shown, or all outputs if None
+    output: Option<String>,
     workspace_id_to_button: HashMap<u64, gtk4::glib::WeakRef<Button>>,
 }
 
 impl Workspaces {
-    pub fn new() -> Result<Self> {
+    pub fn new(output: Option<String>) -> Result<Self> {
         let container = Box::new(Orientation::Horizontal, 5);
         container.add_css_class("workspaces");
         
-        let state = Rc::new(RefCell::new(WorkspacesState::default()));
-        
-        // Get initial workspace info
-        let (initial_workspaces, initial_windows) = Self::get_initial_state()?;
-        
-        // Update state with initial data
-        {
-            let mut state_mut = state.borrow_mut();
-            state_mut.workspaces = initial_workspaces;
-            state_mut.windows = initial_windows;
-        }
+        let state = Rc::new(RefCell::new(WorkspacesState {
+            output,
+            ..Default::default()
+        }));
         
+        // Follow the shared niri state; subscribing loads it if this is the first widget
+        let lifecycle = Lifecycle::new();
+        Self::subscribe_to_niri(&lifecycle, container.clone(), state.clone());
+
         // Create workspace buttons
-        Self::update_workspace_ui(&container, state.clone());
-        
-        // Set up event stream
-        let (event_source_id, event_stream_child) = Self::setup_event_stream(container.clone(), state.clone())?;
+        niri_state::with(|niri| Self::update_workspace_ui(&container, state.clone(), niri));
         
         Ok(Self { 
             container, 
             state,
-            _event_stream_child: Some(event_stream_child),
-            _event_source_id: Some(event_source_id),
+            lifecycle,
         })
     }
 
     fn create_workspace_button(workspace: &WorkspaceInfo, state: Rc<RefCell<WorkspacesState>>) -> Button {
         let button = Button::with_label(&workspace.id.to_string());
         button.add_css_class("workspace");
-
-        if workspace.is_focused {
-            button.add_css_class("active");
-        }
+        Self::update_button_state(&button, workspace);
 
         // Store workspace info in widget name (safer than set_data)
         button.set_widget_name(&format!("{}:{}", workspace.id, workspace.idx));
@@ -77,17 +65,18 @@ impl Workspaces {
             workspace.idx, workspace.id
         );
 
-        // Left click - switch workspace
+        // Left click - switch workspace. By id, since an index is resolved on
+        // the focused output rather than this one.
+        let workspace_id = workspace.id;
         let workspace_idx = workspace.idx;
         button.connect_clicked(move |_| {
-            Self::switch_workspace(workspace_idx);
+            Self::switch_workspace(workspace_id, workspace_idx);
         });
 
         // Right click - show window picker
         let gesture = gtk4::GestureClick::new();
         gesture.set_button(3); // Right mouse button
 
-        let workspace_id = workspace.id;
         let workspace_idx_for_popover = workspace.idx;
         gesture.connect_released(move |gesture, _, x, y| {
             let widget = gesture.widget();
@@ -132,8 +121,8 @@ impl Workspaces {
         title_label.set_halign(gtk4::Align::Start);
         popover_box.append(&title_label);
 
-        // Get current windows for this workspace using niri msg windows
-        let windows = Self::get_windows_for_workspace(workspace_id);
+        // Get current windows for this workspace from the shared niri state
+        let windows = niri_state::with(|niri| niri.windows_on_workspace(workspace_id));
         info!("Found {} windows for workspace {}", windows.len(), workspace_id);
 
         if windows.is_empty() {
@@ -236,7 +225,7 @@ impl Workspaces {
         row
     }
 
-    fn get_app_icon(app_id: &str) -> String {
+    pub(crate) fn get_app_icon(app_id: &str) -> String {
         // Common app_id to icon mappings
         let icon = match app_id.to_lowercase().as_str() {
             "firefox" | "firefox-esr" => "firefox",
@@ -258,10 +247,30 @@ impl Workspaces {
         format!("{}-symbolic", icon)
     }
 
-    fn update_workspace_ui(container: &Box, state: Rc<RefCell<WorkspacesState>>) {
-        // Clone and sort workspaces by ID
-        let mut workspaces = state.borrow().workspaces.clone();
-        workspaces.sort_by_key(|w| w.id);
+    /// Mark the workspace that is active on this output, and the one holding focus
+    fn update_button_state(button: &Button, workspace: &WorkspaceInfo) {
+        if workspace.is_active {
+            button.add_css_class("active");
+        } else {
+            button.remove_css_class("active");
+        }
+
+        if workspace.is_focused {
+            button.add_css_class("focused");
+        } else {
+            button.remove_css_class("focused");
+        }
+
+        if workspace.is_urgent {
+            button.add_css_class("urgent");
+        } else {
+            button.remove_css_class("urgent");
+        }
+    }
+
+    fn update_workspace_ui(container: &Box, state: Rc<RefCell<WorkspacesState>>, niri: &NiriState) {
+        // Only show workspaces on this panel's output
+        let workspaces = niri.workspaces_on_output(state.borrow().output.as_deref());
 
         // Get current buttons
         let mut current_buttons = Vec::new();
@@ -273,30 +282,32 @@ impl Workspaces {
             child = widget.next_sibling();
         }
 
-        // Update existing buttons or add new ones
+        // Update existing buttons or add new ones. A button's handlers are
+        // bound to one workspace, so a position that now holds a different
+        // workspace gets a new button.
         for (idx, workspace) in workspaces.iter().enumerate() {
-            if let Some(button) = current_buttons.get(idx) {
-                if let Some(button) = button.downcast_ref::<Button>() {
+            match current_buttons.get(idx).and_then(|b| b.downcast_ref::<Button>()) {
+                Some(button) if Self::button_workspace_id(button) == Some(workspace.id) => {
                     // Update label
                     button.set_label(&workspace.idx.to_string());
 
                     // Update active state
-                    if workspace.is_focused {
-                        button.add_css_class("active");
-                    } else {
-                        button.remove_css_class("active");
-                    }
+                    Self::update_button_state(button, workspace);
 
                     // Update stored data
                     button.set_widget_name(&format!("{}:{}", workspace.id, workspace.idx));
-                    
-                    // Update button reference in state
-                    state.borrow_mut().workspace_id_to_button.insert(workspace.id, button.downgrade());
                 }
-            } else {
-                // Add new button if needed
-                let button = Self::create_workspace_button(workspace, state.clone());
-                container.append(&button);
+                Some(old_button) => {
+                    let button = Self::create_workspace_button(workspace, state.clone());
+                    button.insert_before(container, Some(old_button));
+                    Self::forget_button(&state, old_button);
+                    container.remove(old_button);
+                }
+                None => {
+                    // Add new button if needed
+                    let button = Self::create_workspace_button(workspace, state.clone());
+                    container.append(&button);
+                }
             }
         }
 
@@ -305,277 +316,96 @@ impl Workspaces {
             if let Some(button) = current_buttons.pop() {
                 // Remove from state if it exists
                 if let Some(button) = button.downcast_ref::<Button>() {
-                    let name = button.widget_name();
-                    if let Some(id_str) = name.split(':').next() {
-                        if let Ok(id) = id_str.parse::<u64>() {
-                            state.borrow_mut().workspace_id_to_button.remove(&id);
-                        }
-                    }
+                    Self::forget_button(&state, button);
                 }
                 container.remove(&button);
             }
         }
     }
 
-    fn get_initial_state() -> Result<(Vec<WorkspaceInfo>, Vec<WindowInfo>)> {
-        let mut workspaces = Vec::new();
-        let mut windows = Vec::new();
-
-        // Get workspace information first
-        if let Ok(output) = Command::new("niri")
-            .args(&["msg", "-j", "workspaces"])
-            .output()
-        {
-            if let Ok(json) = serde_json::from_slice::<Value>(&output.stdout) {
-                if let Some(workspaces_array) = json.as_array() {
-                    // Create workspace info with proper IDs
-                    for (idx, workspace_json) in workspaces_array.iter().enumerate() {
-                        // Extract the actual workspace ID from the Niri JSON
-                        let workspace_id = match workspace_json["id"].as_u64() {
-                            Some(id) => id,
-                            None => {
-                                warn!("Missing id for workspace at index {}, using fallback", idx);
-                                (idx + 1) as u64 // Fallback to index+1 if id is missing
-                            }
-                        };
-                        
-                        // Get the idx field from the JSON (display number)
-                        let idx_from_json = workspace_json["idx"].as_u64().unwrap_or_else(|| {
-                            warn!("Missing idx for workspace ID {}, using index+1", workspace_id);
-                            (idx + 1) as u64
-                        });
-                        
-                        workspaces.push(WorkspaceInfo {
-                            id: workspace_id,
-                            idx: idx_from_json as u32,
-                            name: workspace_json["name"].as_str().map(ToString::to_string),
-                            output: workspace_json["output"]
-                                .as_str()
-                                .unwrap_or("eDP-1")
-                                .to_string(),
-                            is_urgent: workspace_json["is_urgent"].as_bool().unwrap_or(false),
-                            is_active: workspace_json["is_active"].as_bool().unwrap_or(false),
-                            is_focused: workspace_json["is_focused"].as_bool().unwrap_or(false),
-                            active_window_id: workspace_json["active_window_id"].as_u64(),
-                        });
-                    }
-                    
-                    // Sort workspaces by ID
-                    workspaces.sort_by_key(|w| w.id);
-                }
-            }
-        } else {
-            // Fallback: create default empty workspaces (1-4)
-            warn!("Could not get workspace info from niri, using defaults");
-            for i in 1..=4 {
-                let workspace_id = i as u64;
-                let display_idx = i;
-                workspaces.push(WorkspaceInfo {
-                    id: workspace_id,
-                    idx: display_idx,
-                    name: None,
-                    output: "eDP-1".to_string(),
-                    is_urgent: false,
-                    is_active: i == 1,
-                    is_focused: i == 1,
-                    active_window_id: None,
-                });
-            }
-            // Sort workspaces by ID (in fallback case this is already sorted,
-            // but we include it for consistency)
-            workspaces.sort_by_key(|w| w.id);
-        }
-
-        // Get all windows
-        if let Ok(output) = Command::new("niri").args(&["msg", "-j", "windows"]).output() {
-            if let Ok(json) = serde_json::from_slice::<Value>(&output.stdout) {
-                if let Some(windows_array) = json.as_array() {
-                    for window_json in windows_array {
-                        if let (Some(id), Some(title), Some(workspace_id)) = (
-                            window_json["id"].as_u64(),
-                            window_json["title"].as_str(),
-                            window_json["workspace_id"].as_u64(),
-                        ) {
-                            windows.push(WindowInfo {
-                                id,
-                                title: title.to_string(),
-                                app_id: window_json["app_id"].as_str().map(ToString::to_string),
-                                pid: window_json["pid"].as_u64().unwrap_or(0),
-                                workspace_id,
-                                is_focused: window_json["is_focused"].as_bool().unwrap_or(false),
-                                is_floating: window_json["is_floating"].as_bool().unwrap_or(false),
-                                is_urgent: window_json["is_urgent"].as_bool().unwrap_or(false),
-                            });
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok((workspaces, windows))
+    /// The workspace id stored in a button's name
+    fn button_workspace_id(button: &Button) -> Option<u64> {
+        button.widget_name().split(':').next()?.parse().ok()
     }
 
-    fn get_windows_for_workspace(workspace_id: u64) -> Vec<WindowInfo> {
-        let mut windows = Vec::new();
-        
-        if let Ok(output) = Command::new("niri").args(&["msg", "-j", "windows"]).output() {
-            if let Ok(json) = serde_json::from_slice::<Value>(&output.stdout) {
-                if let Some(windows_array) = json.as_array() {
-                    for window_json in windows_array {
-                        if let (Some(id), Some(title), Some(wid)) = (
-                            window_json["id"].as_u64(),
-                            window_json["title"].as_str(),
-                            window_json["workspace_id"].as_u64(),
-                        ) {
-                            // Only include windows for the requested workspace
-                            if wid == workspace_id {
-                                windows.push(WindowInfo {
-                                    id,
-                                    title: title.to_string(),
-                                    app_id: window_json["app_id"].as_str().map(ToString::to_string),
-                                    pid: window_json["pid"].as_u64().unwrap_or(0),
-                                    workspace_id,
-                                    is_focused: window_json["is_focused"].as_bool().unwrap_or(false),
-                                    is_floating: window_json["is_floating"].as_bool().unwrap_or(false),
-                                    is_urgent: window_json["is_urgent"].as_bool().unwrap_or(false),
-                                });
-                            }
-                        }
-                    }
-                }
+    /// Drop a button from the id map, unless its workspace has a newer button
+    fn forget_button(state: &Rc<RefCell<WorkspacesState>>, button: &Button) {
+        if let Some(id) = Self::button_workspace_id(button) {
+            let mut state = state.borrow_mut();
+            let current = state
+                .workspace_id_to_button
+                .get(&id)
+                .and_then(|button_weak| button_weak.upgrade());
+            if current.as_ref() == Some(button) {
+                state.workspace_id_to_button.remove(&id);
             }
         }
-        
-        windows
     }
-    
-    fn setup_event_stream(
-        container: Box, 
-        state: Rc<RefCell<WorkspacesState>>
-    ) -> Result<(gtk4::glib::SourceId, Child)> {
-        // Set up event stream
+
+    fn subscribe_to_niri(
+        lifecycle: &Lifecycle,
+        container: Box,
+        state: Rc<RefCell<WorkspacesState>>,
+    ) {
         let container_weak = container.downgrade();
-        let callback = move |event: NiriEvent| {
+        niri_state::subscribe(lifecycle, move |niri, event| {
             match event {
-                NiriEvent::WorkspacesChanged { mut workspaces } => {
-                    debug!("Workspaces changed: {} workspaces", workspaces.len());
-                    // Update workspace state
-                    state.borrow_mut().workspaces = workspaces;
-                    
-                    // Update UI
+                // Rebuild when the workspaces change, or after (re)connecting
+                NiriEvent::WorkspacesChanged { .. } | NiriEvent::ConnectionChanged { .. } => {
+                    debug!("Workspaces changed: {} workspaces", niri.workspaces().len());
                     if let Some(container) = container_weak.upgrade() {
-                        Self::update_workspace_ui(&container, state.clone());
+                        Self::update_workspace_ui(&container, state.clone(), niri);
                     }
                 },
-                NiriEvent::WindowsChanged { windows } => {
-                    debug!("Windows changed: {} windows", windows.len());
-                    // Update window state
-                    state.borrow_mut().windows = windows;
-                },
-                NiriEvent::WorkspaceActivated { id, focused } => {
-                    debug!("Workspace activated: {} (focused: {})", id, focused);
-                    
-                    // Update active state in workspace info
-                    {
-                        let mut state_mut = state.borrow_mut();
-                        for workspace in &mut state_mut.workspaces {
-                            workspace.is_active = workspace.id == id;
-                            if workspace.id == id {
-                                workspace.is_focused = focused;
-                            } else if focused {
-                                workspace.is_focused = false;
-                            }
-                        }
-                        
-                        // Update button active state directly without full UI refresh
-                        if focused {
-                            // If this workspace is focused, update button states
-                            for (ws_id, button_weak) in &state_mut.workspace_id_to_button {
-                                if let Some(button) = button_weak.upgrade() {
-                                    if *ws_id == id {
-                                        button.add_css_class("active");
-                                    } else {
-                                        button.remove_css_class("active");
-                                    }
-                                }
-                            }
+                NiriEvent::WorkspaceActivated { .. } | NiriEvent::WorkspaceUrgencyChanged { .. } => {
+                    debug!("Workspace state changed: {:?}", event);
+
+                    // Update button states directly without full UI refresh
+                    let state = state.borrow();
+                    for workspace in niri.workspaces() {
+                        if let Some(button) = state
+                            .workspace_id_to_button
+                            .get(&workspace.id)
+                            .and_then(|button_weak| button_weak.upgrade())
+                        {
+                            Self::update_button_state(&button, workspace);
                         }
                     }
                 },
-                NiriEvent::WindowFocusChanged { id: _ } => {
-                    // We don't need to handle this directly for the workspaces widget
-                    // since we get workspace activation events
-                },
                 _ => {}
             }
-        };
-        
-        niri_ipc::attach_event_stream(callback)
+        });
     }
 
-    fn switch_workspace(idx: u32) {
-        match Command::new("niri")
-            .args(&["msg", "action", "focus-workspace", &idx.to_string()])
-            .output()
-        {
-            Ok(output) => {
-                if !output.status.success() {
-                    warn!("Failed to switch workspace: {:?}", output);
-                } else {
-                    info!("Switched to workspace {}", idx);
-                }
-            }
-            Err(e) => {
-                warn!("Failed to execute niri command: {}", e);
-            }
+    fn switch_workspace(id: u64, idx: u32) {
+        let reference = WorkspaceReference::Id(id);
+        match niri_ipc::action(Action::FocusWorkspace { reference }) {
+            Ok(()) => info!("Switched to workspace {}", idx),
+            Err(e) => warn!("Failed to switch workspace: {:#}", e),
         }
     }
 
-    fn focus_window(window_id: u64) {
-        match Command::new("niri")
-            .args(&[
-                "msg",
-                "action",
-                "focus-window",
-                "--id",
-                &window_id.to_string(),
-            ])
-            .output()
-        {
-            Ok(output) => {
-                if !output.status.success() {
-                    warn!("Failed to focus window: {:?}", output);
-                }
-            }
-            Err(e) => {
-                warn!("Failed to execute niri command: {}", e);
-            }
+    pub(crate) fn focus_window(window_id: u64) {
+        if let Err(e) = niri_ipc::action(Action::FocusWindow { id: window_id }) {
+            warn!("Failed to focus window: {:#}", e);
         }
     }
 
-    fn close_window(window_id: u64) {
-        match Command::new("niri")
-            .args(&[
-                "msg",
-                "action",
-                "close-window",
-                "--id",
-                &window_id.to_string(),
-            ])
-            .output()
-        {
-            Ok(output) => {
-                if !output.status.success() {
-                    warn!("Failed to close window: {:?}", output);
-                }
-            }
-            Err(e) => {
-                warn!("Failed to execute niri command: {}", e);
-            }
+    pub(crate) fn close_window(window_id: u64) {
+        if let Err(e) = niri_ipc::action(Action::CloseWindow {
+            id: Some(window_id),
+        }) {
+            warn!("Failed to close window: {:#}", e);
         }
     }
 
     pub fn widget(&self) -> &Box {
         &self.container
     }
+}
+
+impl WidgetTrait for Workspaces {
+    fn shutdown(&self) {
+        self.lifecycle.shutdown();
+    }
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+use niri_panel::command::{self, Cmd};
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Whether a live (not zombie) process runs with exactly these arguments
+fn is_running(args: &[&str]) -> bool {
+    let expected: String = args.iter().map(|arg| format!("{}\0", arg)).collect();
+    fs::read_dir("/proc").unwrap().flatten().any(|entry| {
+        let dir = entry.path();
+        let alive = fs::read_to_string(dir.join("stat")).is_ok_and(|stat| {
+            stat.rsplit(')')
+                .next()
+                .is_some_and(|rest| !rest.starts_with(" Z"))
+        });
+        alive && fs::read(dir.join("cmdline")).is_ok_and(|cmdline| cmdline == expected.as_bytes())
+    })
+}
+
+#[test]
+fn runs_a_command() {
+    let output = Cmd::new("echo").args(["a", "b"]).run_blocking().unwrap();
+    assert!(output.status.success());
+    assert_eq!(command::stdout(Ok(output)).as_deref(), Some("a b\n"));
+}
+
+#[test]
+fn kills_a_command_that_times_out() {
+    // An unusual duration, so the process can be told apart from others
+    let args = ["sleep", "5.271828"];
+    let started = Instant::now();
+    let error = Cmd::new(args[0])
+        .arg(args[1])
+        .timeout(Duration::from_millis(200))
+        .run_blocking()
+        .unwrap_err();
+
+    assert!(error.to_string().contains("timed out"), "{}", error);
+    assert!(started.elapsed() < Duration::from_secs(2));
+
+    let deadline = Instant::now() + Duration::from_secs(2);
+    while is_running(&args) {
+        assert!(
+            Instant::now() < deadline,
+            "sleep still runs after timing out"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn reports_a_missing_program() {
+    let error = Cmd::new("niri-panel-no-such-program")
+        .run_blocking()
+        .unwrap_err();
+    assert!(
+        error.downcast_ref::<std::io::Error>().is_some(),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn finds_programs_on_the_path() {
+    assert!(command::in_path("sh"));
+    assert!(!command::in_path("niri-panel-no-such-program"));
+
+    let sh = command::find_in_path("sh").expect("sh is on the path");
+    assert!(sh.is_absolute() && sh.ends_with("sh"));
+    assert_eq!(command::find_in_path("niri-panel-no-such-program"), None);
+}
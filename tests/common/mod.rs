@@ -0,0 +1,153 @@
+//! A fake niri compositor listening on a temporary socket, for tests that
+//! exercise the panel's niri client without a running compositor
+
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Read a file from `tests/fixtures/niri`
+pub fn fixture(name: &str) -> String {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/niri")
+        .join(name);
+    fs::read_to_string(&path).unwrap_or_else(|e| panic!("failed to read {:?}: {}", path, e))
+}
+
+/// Recorded event stream, one JSON event per line
+pub fn recorded_events() -> Vec<String> {
+    fixture("event-stream.jsonl")
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Answers `Workspaces`, `Windows`, `KeyboardLayouts` and `Action` requests
+/// from the recorded fixtures, and replays the recorded event stream to
+/// `EventStream` requests before closing the connection. Other requests get
+/// an error reply.
+pub struct MockNiri {
+    dir: PathBuf,
+    socket_path: PathBuf,
+    actions: Arc<Mutex<Vec<Value>>>,
+}
+
+impl MockNiri {
+    pub fn start() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "niri-panel-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::create_dir_all(&dir).expect("failed to create socket directory");
+        let socket_path = dir.join("niri.sock");
+        let listener = UnixListener::bind(&socket_path).expect("failed to bind mock niri socket");
+
+        let workspaces: Value = serde_json::from_str(&fixture("workspaces.json")).unwrap();
+        let windows: Value = serde_json::from_str(&fixture("windows.json")).unwrap();
+        let events = recorded_events();
+        let actions = Arc::new(Mutex::new(Vec::new()));
+
+        let server_actions = actions.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { break };
+                let workspaces = workspaces.clone();
+                let windows = windows.clone();
+                let events = events.clone();
+                let actions = server_actions.clone();
+                thread::spawn(move || {
+                    serve(stream, &workspaces, &windows, &events, &actions);
+                });
+            }
+        });
+
+        Self {
+            dir,
+            socket_path,
+            actions,
+        }
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// Actions received so far, as sent over the socket
+    pub fn actions(&self) -> Vec<Value> {
+        self.actions.lock().unwrap().clone()
+    }
+}
+
+impl Drop for MockNiri {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn serve(
+    stream: UnixStream,
+    workspaces: &Value,
+    windows: &Value,
+    events: &[String],
+    actions: &Mutex<Vec<Value>>,
+) {
+    let mut writer = stream.try_clone().unwrap();
+    let reader = BufReader::new(stream);
+
+    for line in reader.lines() {
+        let Ok(line) = line else { return };
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                reply(&mut writer, json!({ "Err": format!("error parsing request: {}", e) }));
+                continue;
+            }
+        };
+
+        match &request {
+            Value::String(name) if name == "Workspaces" => {
+                reply(&mut writer, json!({ "Ok": { "Workspaces": workspaces } }));
+            }
+            Value::String(name) if name == "Windows" => {
+                reply(&mut writer, json!({ "Ok": { "Windows": windows } }));
+            }
+            Value::String(name) if name == "KeyboardLayouts" => {
+                reply(
+                    &mut writer,
+                    json!({ "Ok": { "KeyboardLayouts": {
+                        "names": ["English (US)", "German"],
+                        "current_idx": 0
+                    } } }),
+                );
+            }
+            Value::String(name) if name == "EventStream" => {
+                reply(&mut writer, json!({ "Ok": "Handled" }));
+                for event in events {
+                    if writeln!(writer, "{}", event).is_err() {
+                        return;
+                    }
+                }
+                // Closing the connection ends the stream, like niri exiting
+                return;
+            }
+            Value::Object(map) if map.contains_key("Action") => {
+                actions.lock().unwrap().push(map["Action"].clone());
+                reply(&mut writer, json!({ "Ok": "Handled" }));
+            }
+            other => {
+                reply(&mut writer, json!({ "Err": format!("unsupported request: {}", other) }));
+            }
+        }
+    }
+}
+
+fn reply(writer: &mut UnixStream, reply: Value) {
+    let _ = writeln!(writer, "{}", reply);
+}
@@ -0,0 +1,165 @@
+use niri_panel::config::PanelConfig;
+
+/// A config with every required key; tables can be appended
+const CONFIG: &str = r#"height = 32
+show_launcher = true
+show_places = true
+show_servers = true
+show_search = true
+show_workspaces = true
+show_clock = true
+show_battery = true
+show_network = true
+show_sound = true
+show_bluetooth = true
+show_power = true
+show_git = false
+show_secrets = false
+clock_format = "%H:%M"
+launcher_icon = "view-app-grid-symbolic"
+
+[git]
+repositories = []
+services = []
+"#;
+
+/// [`CONFIG`] with the line setting `key` replaced
+fn with_line(key: &str, line: &str) -> String {
+    let prefix = format!("{} = ", key);
+    assert!(
+        CONFIG.lines().any(|l| l.starts_with(&prefix)),
+        "config has no `{}` line",
+        key
+    );
+    CONFIG
+        .lines()
+        .map(|l| if l.starts_with(&prefix) { line } else { l })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn a_complete_config_is_clean() {
+    let check = PanelConfig::check(CONFIG);
+    assert!(check.config.is_some());
+    assert!(check.errors.is_empty(), "errors: {:?}", check.errors);
+    assert!(check.warnings.is_empty(), "warnings: {:?}", check.warnings);
+}
+
+#[test]
+fn reports_problems_as_errors_or_warnings() {
+    let cases = [
+        // Unknown keys are tolerated, with their full path
+        (
+            format!("colour = \"red\"\n{}", CONFIG),
+            None,
+            Some("unknown key `colour`"),
+        ),
+        (
+            format!("{}\n[auto_hide]\nhot_zone = 2\ndelay = 100\n", CONFIG),
+            None,
+            Some("unknown key `auto_hide.delay`"),
+        ),
+        // Values that parse but can't be used
+        (
+            with_line("clock_format", "clock_format = \"%H:%Q\""),
+            Some("clock_format `%H:%Q` is not a valid strftime format"),
+            None,
+        ),
+        (
+            with_line("height", "height = 0"),
+            Some("height must be positive, got 0"),
+            None,
+        ),
+        (
+            format!("{}\n[[custom]]\nname = \"weather\"\nexec = \" \"\n", CONFIG),
+            Some("custom widget `weather` has an empty exec"),
+            None,
+        ),
+        // Unknown layout names are skipped, so they only warn
+        (
+            format!(
+                "{}\n[layout]\nleft = [\"workspaces\", \"clocks\"]\n",
+                CONFIG
+            ),
+            None,
+            Some("unknown widget `clocks` in layout.left"),
+        ),
+        (
+            format!("{}\n[layout]\nright = [\"custom/weather\"]\n", CONFIG),
+            None,
+            Some("unknown widget `custom/weather` in layout.right"),
+        ),
+    ];
+
+    for (content, error, warning) in cases {
+        let check = PanelConfig::check(&content);
+        match error {
+            Some(error) => assert!(
+                check.errors.iter().any(|e| e == error),
+                "expected error {:?}, got {:?}",
+                error,
+                check.errors
+            ),
+            None => assert!(check.errors.is_empty(), "errors: {:?}", check.errors),
+        }
+        match warning {
+            Some(warning) => assert!(
+                check.warnings.iter().any(|w| w == warning),
+                "expected warning {:?}, got {:?}",
+                warning,
+                check.warnings
+            ),
+            None => assert!(check.warnings.is_empty(), "warnings: {:?}", check.warnings),
+        }
+    }
+}
+
+#[test]
+fn layout_names_of_defined_custom_widgets_and_plugins_are_known() {
+    let content = format!(
+        "{}\n[[custom]]\nname = \"weather\"\nexec = \"curl wttr.in\"\n\n\
+         [[plugin]]\nname = \"vpn\"\nexec = \"vpn-plugin\"\n\n\
+         [layout]\nright = [\"custom/weather\", \"plugin/vpn\", \"clock\"]\n",
+        CONFIG
+    );
+    let check = PanelConfig::check(&content);
+    assert!(check.errors.is_empty(), "errors: {:?}", check.errors);
+    assert!(check.warnings.is_empty(), "warnings: {:?}", check.warnings);
+}
+
+#[test]
+fn type_errors_point_at_the_line_and_column() {
+    let content = with_line("height", "height = \"tall\"");
+    let line = content
+        .lines()
+        .position(|l| l.starts_with("height = "))
+        .unwrap()
+        + 1;
+
+    let check = PanelConfig::check(&content);
+    assert!(check.config.is_none());
+    assert_eq!(check.errors.len(), 1);
+    let prefix = format!("line {}, column 10: ", line);
+    assert!(
+        check.errors[0].starts_with(&prefix),
+        "expected {:?}, got {:?}",
+        prefix,
+        check.errors[0]
+    );
+}
+
+#[test]
+fn columns_count_characters_not_bytes() {
+    let content = format!("{}\n[layout]\nleft = [\"ünïcödé\", 3]\n", CONFIG);
+    let line = content.lines().count();
+
+    let check = PanelConfig::check(&content);
+    let prefix = format!("line {}, column 20: ", line);
+    assert!(
+        check.errors[0].starts_with(&prefix),
+        "expected {:?}, got {:?}",
+        prefix,
+        check.errors[0]
+    );
+}
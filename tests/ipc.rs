@@ -0,0 +1,122 @@
+use niri_panel::ipc::{Command, ErrorCode, Request, Response, PROTOCOL_VERSION};
+use serde_json::json;
+
+fn error_code(line: &str) -> ErrorCode {
+    Request::parse(line)
+        .expect_err("request should be rejected")
+        .code
+}
+
+#[test]
+fn parses_json_requests() {
+    let request =
+        Request::parse(r#"{"version": 1, "command": "show", "widget": "sound"}"#).unwrap();
+    assert_eq!(request.version, PROTOCOL_VERSION);
+    assert!(matches!(request.command, Command::Show { widget } if widget == "sound"));
+
+    let request = Request::parse(r#"{"version": 1, "command": "hide-all"}"#).unwrap();
+    assert!(matches!(request.command, Command::HideAll));
+}
+
+#[test]
+fn every_command_round_trips_through_json() {
+    let widget = || "clock".to_string();
+    let commands = [
+        Command::Show { widget: widget() },
+        Command::Hide { widget: widget() },
+        Command::Toggle { widget: widget() },
+        Command::HideAll,
+        Command::Status,
+        Command::List,
+        Command::Get { widget: widget() },
+        Command::Subscribe,
+        Command::Quit,
+        Command::Reveal,
+    ];
+
+    for command in commands {
+        let line = serde_json::to_string(&Request::new(command.clone())).unwrap();
+        let parsed =
+            Request::parse(&line).unwrap_or_else(|e| panic!("failed to parse {}: {}", line, e));
+        assert_eq!(
+            serde_json::to_value(&parsed.command).unwrap(),
+            serde_json::to_value(&command).unwrap()
+        );
+    }
+}
+
+#[test]
+fn accepts_plain_text_requests_from_older_clients() {
+    let request = Request::parse("toggle network\n").unwrap();
+    assert_eq!(request.version, PROTOCOL_VERSION);
+    assert!(matches!(request.command, Command::Toggle { widget } if widget == "network"));
+
+    assert!(matches!(
+        Request::parse("hide-all").unwrap().command,
+        Command::HideAll
+    ));
+    assert!(matches!(
+        Request::parse("reveal").unwrap().command,
+        Command::Reveal
+    ));
+}
+
+#[test]
+fn classifies_bad_requests() {
+    let cases = [
+        // JSON
+        ("{not json", ErrorCode::InvalidRequest),
+        (r#"{"command": "status"}"#, ErrorCode::InvalidRequest),
+        (
+            r#"{"version": 2, "command": "status"}"#,
+            ErrorCode::UnsupportedVersion,
+        ),
+        (r#"{"version": 1}"#, ErrorCode::InvalidRequest),
+        (
+            r#"{"version": 1, "command": "dance"}"#,
+            ErrorCode::UnknownCommand,
+        ),
+        // A known command with missing or mistyped arguments
+        (
+            r#"{"version": 1, "command": "show"}"#,
+            ErrorCode::InvalidRequest,
+        ),
+        (
+            r#"{"version": 1, "command": "get", "widget": 3}"#,
+            ErrorCode::InvalidRequest,
+        ),
+        // Plain text
+        ("", ErrorCode::InvalidRequest),
+        ("dance", ErrorCode::UnknownCommand),
+        ("show", ErrorCode::InvalidRequest),
+    ];
+
+    for (line, code) in cases {
+        assert_eq!(error_code(line), code, "for {:?}", line);
+    }
+}
+
+#[test]
+fn responses_carry_data_or_an_error() {
+    let response: Response =
+        serde_json::from_str(r#"{"version": 1, "ok": true, "data": {"visible": true}}"#).unwrap();
+    assert_eq!(
+        response.into_result().unwrap(),
+        Some(json!({ "visible": true }))
+    );
+
+    let response: Response = serde_json::from_str(
+        r#"{"version": 1, "ok": false, "error": {"code": "unknown-widget", "message": "unknown widget 'x'"}}"#,
+    )
+    .unwrap();
+    let error = response.into_result().unwrap_err();
+    assert_eq!(error.code, ErrorCode::UnknownWidget);
+    assert_eq!(error.message, "unknown widget 'x'");
+
+    // A failure without details is still a failure
+    let response: Response = serde_json::from_str(r#"{"version": 1, "ok": false}"#).unwrap();
+    assert_eq!(
+        response.into_result().unwrap_err().code,
+        ErrorCode::Internal
+    );
+}
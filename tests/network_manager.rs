@@ -0,0 +1,678 @@
+use gtk4::gio::{self, DBusCallFlags, DBusConnection, DBusConnectionFlags, DBusNodeInfo};
+use gtk4::glib::variant::ObjectPath;
+use gtk4::glib::{self, ToVariant, Variant};
+use niri_panel::network_manager::{self as nm, NetworkManager, Snapshot};
+use niri_panel::services::network::{self, ConnectionType};
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const ACTIVE_WIFI: &str = "/org/freedesktop/NetworkManager/ActiveConnection/1";
+const ACTIVE_VPN: &str = "/org/freedesktop/NetworkManager/ActiveConnection/2";
+const DEVICE: &str = "/org/freedesktop/NetworkManager/Devices/1";
+const AP_HOME: &str = "/org/freedesktop/NetworkManager/AccessPoint/1";
+const AP_HOME_FAR: &str = "/org/freedesktop/NetworkManager/AccessPoint/2";
+const AP_CAFE: &str = "/org/freedesktop/NetworkManager/AccessPoint/3";
+const IP4_CONFIG: &str = "/org/freedesktop/NetworkManager/IP4Config/1";
+const IP6_CONFIG: &str = "/org/freedesktop/NetworkManager/IP6Config/1";
+const SAVED_HOME: &str = "/org/freedesktop/NetworkManager/Settings/1";
+const SAVED_WORK: &str = "/org/freedesktop/NetworkManager/Settings/2";
+
+/// NetworkManager on a private dbus-daemon: a laptop on the `home` WiFi with
+/// the `work` VPN up, and a `cafe` network in range. Method calls are
+/// recorded rather than acted on.
+struct FakeNetworkManager {
+    daemon: Child,
+    address: String,
+    config: PathBuf,
+    calls: Arc<Mutex<Vec<String>>>,
+    // The fake's own connection, which owns NetworkManager's name
+    connection: DBusConnection,
+}
+
+impl FakeNetworkManager {
+    /// `None` when dbus-daemon isn't installed
+    fn start() -> Option<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let config = std::env::temp_dir().join(format!(
+            "niri-panel-test-bus-{}-{}.conf",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::SeqCst)
+        ));
+        fs::write(
+            &config,
+            r#"<busconfig>
+  <type>session</type>
+  <listen>unix:tmpdir=/tmp</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>"#,
+        )
+        .expect("failed to write bus config");
+
+        let mut daemon = Command::new("dbus-daemon")
+            .arg(format!("--config-file={}", config.display()))
+            .args(["--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .ok()?;
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .expect("dbus-daemon printed no address");
+        let address = address.trim().to_string();
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (ready, is_ready) = mpsc::channel();
+        let service_address = address.clone();
+        let service_calls = calls.clone();
+        thread::spawn(move || {
+            let context = glib::MainContext::new();
+            context
+                .with_thread_default(|| {
+                    let connection = connect(&service_address);
+                    serve(&connection, service_calls);
+                    ready.send(connection.clone()).unwrap();
+                    glib::MainLoop::new(Some(&context), false).run();
+                })
+                .unwrap();
+        });
+        let connection = is_ready
+            .recv_timeout(Duration::from_secs(5))
+            .expect("fake NetworkManager did not start");
+
+        Some(Self {
+            daemon,
+            address,
+            config,
+            calls,
+            connection,
+        })
+    }
+
+    fn client(&self) -> NetworkManager {
+        NetworkManager::connect_to(&self.address).expect("failed to connect to the test bus")
+    }
+
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Send a signal as NetworkManager
+    fn emit(&self, path: &str, interface: &str, member: &str, parameters: Variant) {
+        self.connection
+            .emit_signal(None, path, interface, member, Some(&parameters))
+            .unwrap();
+    }
+
+    /// Report a changed property of an object
+    fn emit_property(&self, path: &str, interface: &str, name: &str, value: Variant) {
+        let changed = HashMap::from([(name.to_string(), value)]);
+        self.emit(
+            path,
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+            (interface, changed, Vec::<String>::new()).to_variant(),
+        );
+    }
+}
+
+impl Drop for FakeNetworkManager {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+        let _ = fs::remove_file(&self.config);
+    }
+}
+
+fn connect(address: &str) -> DBusConnection {
+    DBusConnection::for_address_sync(
+        address,
+        DBusConnectionFlags::AUTHENTICATION_CLIENT | DBusConnectionFlags::MESSAGE_BUS_CONNECTION,
+        None,
+        gio::Cancellable::NONE,
+    )
+    .expect("failed to connect to the test bus")
+}
+
+fn path(path: &str) -> Variant {
+    ObjectPath::try_from(path).unwrap().to_variant()
+}
+
+fn paths(paths: &[&str]) -> Variant {
+    paths
+        .iter()
+        .map(|path| ObjectPath::try_from(*path).unwrap())
+        .collect::<Vec<_>>()
+        .to_variant()
+}
+
+fn addresses(addresses: &[&str]) -> Variant {
+    addresses
+        .iter()
+        .map(|address| HashMap::from([("address".to_string(), address.to_variant())]))
+        .collect::<Vec<HashMap<String, Variant>>>()
+        .to_variant()
+}
+
+fn access_point(
+    ssid: &str,
+    strength: u8,
+    rsn_flags: u32,
+    bssid: &str,
+) -> Vec<(&'static str, Variant)> {
+    vec![
+        ("Ssid", ssid.as_bytes().to_vec().to_variant()),
+        ("Strength", strength.to_variant()),
+        ("Flags", 0u32.to_variant()),
+        ("WpaFlags", 0u32.to_variant()),
+        ("RsnFlags", rsn_flags.to_variant()),
+        ("HwAddress", bssid.to_variant()),
+    ]
+}
+
+/// Every object's properties, by path and interface
+fn objects() -> Vec<(&'static str, &'static str, Vec<(&'static str, Variant)>)> {
+    vec![
+        (
+            nm::MANAGER_PATH,
+            nm::MANAGER_INTERFACE,
+            vec![
+                ("WirelessEnabled", true.to_variant()),
+                // The VPN carries the default route
+                ("PrimaryConnection", path(ACTIVE_VPN)),
+                ("ActiveConnections", paths(&[ACTIVE_WIFI, ACTIVE_VPN])),
+                ("Devices", paths(&[DEVICE])),
+            ],
+        ),
+        (nm::SETTINGS_PATH, nm::SETTINGS_INTERFACE, vec![]),
+        (SAVED_HOME, nm::CONNECTION_INTERFACE, vec![]),
+        (SAVED_WORK, nm::CONNECTION_INTERFACE, vec![]),
+        (
+            ACTIVE_WIFI,
+            nm::ACTIVE_INTERFACE,
+            vec![
+                ("Id", "home".to_variant()),
+                ("Uuid", "uuid-home".to_variant()),
+                ("Type", "802-11-wireless".to_variant()),
+                ("State", 2u32.to_variant()),
+                ("Vpn", false.to_variant()),
+                ("Devices", paths(&[DEVICE])),
+                ("Ip4Config", path(IP4_CONFIG)),
+                ("Ip6Config", path(IP6_CONFIG)),
+            ],
+        ),
+        (
+            ACTIVE_VPN,
+            nm::ACTIVE_INTERFACE,
+            vec![
+                ("Id", "work".to_variant()),
+                ("Uuid", "uuid-work".to_variant()),
+                ("Type", "vpn".to_variant()),
+                ("State", 2u32.to_variant()),
+                ("Vpn", true.to_variant()),
+                ("Devices", paths(&[DEVICE])),
+                ("Ip4Config", path("/")),
+                ("Ip6Config", path("/")),
+            ],
+        ),
+        (
+            DEVICE,
+            nm::DEVICE_INTERFACE,
+            vec![
+                ("Interface", "wlan0".to_variant()),
+                ("DeviceType", 2u32.to_variant()),
+                ("ActiveConnection", path(ACTIVE_WIFI)),
+            ],
+        ),
+        (
+            DEVICE,
+            nm::WIRELESS_INTERFACE,
+            vec![
+                ("ActiveAccessPoint", path(AP_HOME)),
+                ("AccessPoints", paths(&[AP_HOME_FAR, AP_CAFE, AP_HOME])),
+                ("LastScan", 1000i64.to_variant()),
+            ],
+        ),
+        (
+            AP_HOME,
+            nm::ACCESS_POINT_INTERFACE,
+            access_point("home", 72, 0x188, "00:11:22:33:44:01"),
+        ),
+        (
+            AP_HOME_FAR,
+            nm::ACCESS_POINT_INTERFACE,
+            access_point("home", 30, 0x188, "00:11:22:33:44:02"),
+        ),
+        (
+            AP_CAFE,
+            nm::ACCESS_POINT_INTERFACE,
+            access_point("cafe", 55, 0, "00:11:22:33:44:03"),
+        ),
+        (
+            IP4_CONFIG,
+            nm::IP4_CONFIG_INTERFACE,
+            vec![("AddressData", addresses(&["192.168.1.20"]))],
+        ),
+        (
+            IP6_CONFIG,
+            nm::IP6_CONFIG_INTERFACE,
+            vec![("AddressData", addresses(&["fe80::20", "2001:db8::20"]))],
+        ),
+    ]
+}
+
+fn methods(interface: &str) -> &'static str {
+    match interface {
+        nm::MANAGER_INTERFACE => {
+            r#"<method name="ActivateConnection">
+                 <arg direction="in" type="o"/><arg direction="in" type="o"/><arg direction="in" type="o"/>
+                 <arg direction="out" type="o"/>
+               </method>
+               <method name="AddAndActivateConnection">
+                 <arg direction="in" type="a{sa{sv}}"/><arg direction="in" type="o"/><arg direction="in" type="o"/>
+                 <arg direction="out" type="o"/><arg direction="out" type="o"/>
+               </method>
+               <method name="DeactivateConnection"><arg direction="in" type="o"/></method>"#
+        }
+        nm::SETTINGS_INTERFACE => {
+            r#"<method name="ListConnections"><arg direction="out" type="ao"/></method>"#
+        }
+        nm::CONNECTION_INTERFACE => {
+            r#"<method name="GetSettings"><arg direction="out" type="a{sa{sv}}"/></method>"#
+        }
+        nm::WIRELESS_INTERFACE => {
+            r#"<method name="RequestScan"><arg direction="in" type="a{sv}"/></method>"#
+        }
+        _ => "",
+    }
+}
+
+fn saved_settings(path: &str) -> HashMap<String, HashMap<String, Variant>> {
+    let setting = |entries: &[(&str, Variant)]| {
+        entries
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect::<HashMap<String, Variant>>()
+    };
+    if path == SAVED_HOME {
+        HashMap::from([
+            (
+                "connection".to_string(),
+                setting(&[
+                    ("id", "home".to_variant()),
+                    ("uuid", "uuid-home".to_variant()),
+                    ("type", "802-11-wireless".to_variant()),
+                ]),
+            ),
+            (
+                "802-11-wireless".to_string(),
+                setting(&[("ssid", b"home".to_vec().to_variant())]),
+            ),
+        ])
+    } else {
+        HashMap::from([(
+            "connection".to_string(),
+            setting(&[
+                ("id", "work".to_variant()),
+                ("uuid", "uuid-work".to_variant()),
+                ("type", "vpn".to_variant()),
+            ]),
+        )])
+    }
+}
+
+/// Register the objects, answer method calls and take NetworkManager's name
+fn serve(connection: &DBusConnection, calls: Arc<Mutex<Vec<String>>>) {
+    for (object_path, interface, properties) in objects() {
+        let property_xml: String = properties
+            .iter()
+            .map(|(name, value)| {
+                let access = if *name == "WirelessEnabled" {
+                    "readwrite"
+                } else {
+                    "read"
+                };
+                format!(
+                    r#"<property name="{}" type="{}" access="{}"/>"#,
+                    name,
+                    value.type_(),
+                    access
+                )
+            })
+            .collect();
+        let xml = format!(
+            r#"<node><interface name="{}">{}{}</interface></node>"#,
+            interface,
+            property_xml,
+            methods(interface)
+        );
+        let info = DBusNodeInfo::for_xml(&xml)
+            .unwrap()
+            .lookup_interface(interface)
+            .unwrap();
+
+        let properties: HashMap<String, Variant> = properties
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect();
+        let method_calls = calls.clone();
+        let property_calls = calls.clone();
+        connection
+            .register_object(
+                object_path,
+                &info,
+                move |connection, _, object_path, _, method, parameters, invocation| {
+                    let argument = |index: usize| {
+                        parameters
+                            .child_value(index)
+                            .str()
+                            .unwrap_or_default()
+                            .to_string()
+                    };
+                    let reply = match method {
+                        "ListConnections" => Some(
+                            (vec![
+                                ObjectPath::try_from(SAVED_HOME).unwrap(),
+                                ObjectPath::try_from(SAVED_WORK).unwrap(),
+                            ],)
+                                .to_variant(),
+                        ),
+                        "GetSettings" => Some((saved_settings(object_path),).to_variant()),
+                        "ActivateConnection" => {
+                            method_calls.lock().unwrap().push(format!(
+                                "ActivateConnection {} {} {}",
+                                argument(0),
+                                argument(1),
+                                argument(2)
+                            ));
+                            Some((ObjectPath::try_from(ACTIVE_VPN).unwrap(),).to_variant())
+                        }
+                        "AddAndActivateConnection" => {
+                            let settings: HashMap<String, HashMap<String, Variant>> =
+                                parameters.child_value(0).get().unwrap();
+                            let psk = settings
+                                .get("802-11-wireless-security")
+                                .and_then(|security| security.get("psk"))
+                                .and_then(|psk| psk.get::<String>())
+                                .unwrap_or_default();
+                            method_calls.lock().unwrap().push(format!(
+                                "AddAndActivateConnection {} {} psk={}",
+                                argument(1),
+                                argument(2),
+                                psk
+                            ));
+                            Some(
+                                (
+                                    ObjectPath::try_from(SAVED_HOME).unwrap(),
+                                    ObjectPath::try_from(ACTIVE_WIFI).unwrap(),
+                                )
+                                    .to_variant(),
+                            )
+                        }
+                        "DeactivateConnection" => {
+                            method_calls
+                                .lock()
+                                .unwrap()
+                                .push(format!("DeactivateConnection {}", argument(0)));
+                            let changed = HashMap::from([(
+                                "ActiveConnections".to_string(),
+                                paths(&[ACTIVE_WIFI]),
+                            )]);
+                            connection
+                                .emit_signal(
+                                    None,
+                                    nm::MANAGER_PATH,
+                                    "org.freedesktop.DBus.Properties",
+                                    "PropertiesChanged",
+                                    Some(
+                                        &(nm::MANAGER_INTERFACE, changed, Vec::<String>::new())
+                                            .to_variant(),
+                                    ),
+                                )
+                                .unwrap();
+                            None
+                        }
+                        "RequestScan" => {
+                            method_calls
+                                .lock()
+                                .unwrap()
+                                .push(format!("RequestScan {}", object_path));
+                            None
+                        }
+                        _ => {
+                            invocation.return_dbus_error(
+                                "org.freedesktop.DBus.Error.UnknownMethod",
+                                method,
+                            );
+                            return;
+                        }
+                    };
+                    invocation.return_value(reply.as_ref());
+                },
+                move |_, _, _, _, name| properties[name].clone(),
+                move |_, _, _, _, name, value| {
+                    property_calls.lock().unwrap().push(format!(
+                        "Set {} {}",
+                        name,
+                        value.get::<bool>().unwrap()
+                    ));
+                    true
+                },
+            )
+            .unwrap();
+    }
+
+    connection
+        .call_sync(
+            Some("org.freedesktop.DBus"),
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "RequestName",
+            Some(&(nm::BUS_NAME, 0u32).to_variant()),
+            None,
+            DBusCallFlags::NONE,
+            5_000,
+            gio::Cancellable::NONE,
+        )
+        .unwrap();
+}
+
+/// Run a request on a main context of its own, as the panel does on its main loop
+fn block_on<T>(context: &glib::MainContext, future: impl std::future::Future<Output = T>) -> T {
+    context
+        .with_thread_default(|| context.block_on(future))
+        .unwrap()
+}
+
+#[test]
+fn a_missing_network_manager_reads_as_disconnected() {
+    let state = network::network_state(&Snapshot::default());
+    assert!(!state.info.connected);
+    assert_eq!(state.info.connection_type, ConnectionType::Disconnected);
+    assert!(state.vpn_connections.is_empty());
+    assert!(state.wifi.is_none());
+}
+
+#[test]
+fn reads_devices_connections_and_access_points() {
+    let Some(fake) = FakeNetworkManager::start() else {
+        eprintln!("dbus-daemon is not installed, skipping");
+        return;
+    };
+    let snapshot = fake.client().read().unwrap();
+
+    assert!(snapshot.wireless_enabled);
+    assert_eq!(snapshot.primary_connection.as_deref(), Some(ACTIVE_VPN));
+    assert_eq!(snapshot.active_connections.len(), 2);
+    assert!(snapshot.active_connections[1].vpn);
+    assert_eq!(
+        snapshot.active_connections[0].ip4_addresses,
+        vec!["192.168.1.20"]
+    );
+
+    let device = &snapshot.devices[0];
+    assert_eq!(device.interface, "wlan0");
+    assert!(device.wifi);
+    assert_eq!(device.active_access_point.as_deref(), Some(AP_HOME));
+    assert_eq!(device.access_points.len(), 3);
+    assert_eq!(device.last_scan, 1000);
+
+    let cafe = &device.access_points[1];
+    assert_eq!(cafe.ssid, "cafe");
+    assert_eq!(cafe.strength, 55);
+    assert!(!cafe.secured);
+    assert!(device.access_points[0].secured);
+
+    assert_eq!(snapshot.connections.len(), 2);
+    assert_eq!(snapshot.connections[0].ssid.as_deref(), Some("home"));
+    assert_eq!(snapshot.connections[1].kind, "vpn");
+}
+
+#[test]
+fn shows_the_connection_underneath_a_vpn() {
+    let Some(fake) = FakeNetworkManager::start() else {
+        eprintln!("dbus-daemon is not installed, skipping");
+        return;
+    };
+    let state = network::network_state(&fake.client().read().unwrap());
+
+    let info = &state.info;
+    assert!(info.connected);
+    assert_eq!(info.connection_type, ConnectionType::Wifi);
+    assert_eq!(info.interface, "wlan0");
+    assert_eq!(info.ssid.as_deref(), Some("home"));
+    assert_eq!(info.signal_strength, Some(72));
+    assert_eq!(info.ip_address.as_deref(), Some("192.168.1.20"));
+    // Link-local addresses are skipped
+    assert_eq!(info.ipv6_address.as_deref(), Some("2001:db8::20"));
+    assert!(info.vpn_active);
+    assert_eq!(info.vpn_name.as_deref(), Some("work"));
+
+    assert_eq!(state.vpn_connections.len(), 1);
+    assert!(state.vpn_connections[0].active);
+    assert_eq!(
+        state.vpn_connections[0].active_connection.as_deref(),
+        Some(ACTIVE_VPN)
+    );
+
+    // One entry per SSID, the connected network first
+    let wifi = state.wifi.unwrap();
+    let ssids: Vec<&str> = wifi
+        .networks
+        .iter()
+        .map(|network| network.ssid.as_str())
+        .collect();
+    assert_eq!(ssids, vec!["home", "cafe"]);
+    assert!(wifi.networks[0].connected);
+    assert_eq!(wifi.networks[0].signal, 72);
+    assert_eq!(wifi.networks[0].access_point, AP_HOME);
+    assert_eq!(
+        wifi.networks[0].saved_connection.as_deref(),
+        Some(SAVED_HOME)
+    );
+    assert_eq!(wifi.networks[1].saved_connection, None);
+}
+
+#[test]
+fn sends_requests_and_follows_signals() {
+    let Some(fake) = FakeNetworkManager::start() else {
+        eprintln!("dbus-daemon is not installed, skipping");
+        return;
+    };
+    let context = glib::MainContext::new();
+    let client = fake.client();
+
+    let changes = Rc::new(Cell::new(0));
+    let counted = changes.clone();
+    let subscription = context
+        .with_thread_default(|| client.subscribe(move || counted.set(counted.get() + 1)))
+        .unwrap();
+
+    block_on(&context, client.request_scan(DEVICE)).unwrap();
+    block_on(&context, client.activate(SAVED_WORK, None, None)).unwrap();
+    block_on(
+        &context,
+        client.connect_wifi(DEVICE, AP_CAFE, Some("secret")),
+    )
+    .unwrap();
+    block_on(&context, client.set_wireless_enabled(false)).unwrap();
+    block_on(&context, client.deactivate(ACTIVE_VPN)).unwrap();
+
+    assert_eq!(
+        fake.calls(),
+        vec![
+            format!("RequestScan {}", DEVICE),
+            format!("ActivateConnection {} / /", SAVED_WORK),
+            format!("AddAndActivateConnection {} {} psk=secret", DEVICE, AP_CAFE),
+            "Set WirelessEnabled false".to_string(),
+            format!("DeactivateConnection {}", ACTIVE_VPN),
+        ]
+    );
+
+    // The deactivation's PropertiesChanged signal reaches the subscriber
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while changes.get() == 0 && Instant::now() < deadline {
+        context.iteration(false);
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert!(changes.get() > 0, "no change signal arrived");
+    subscription.unsubscribe();
+}
+
+#[test]
+fn follows_new_networks_but_not_signal_strength() {
+    let Some(fake) = FakeNetworkManager::start() else {
+        eprintln!("dbus-daemon is not installed, skipping");
+        return;
+    };
+    let context = glib::MainContext::new();
+    let client = fake.client();
+
+    let changes = Rc::new(Cell::new(0));
+    let counted = changes.clone();
+    let subscription = context
+        .with_thread_default(|| client.subscribe(move || counted.set(counted.get() + 1)))
+        .unwrap();
+    // Let the match rules reach the bus before anything is sent
+    block_on(&context, client.request_scan(DEVICE)).unwrap();
+
+    fake.emit_property(
+        AP_CAFE,
+        nm::ACCESS_POINT_INTERFACE,
+        "Strength",
+        80u8.to_variant(),
+    );
+    fake.emit(
+        DEVICE,
+        nm::WIRELESS_INTERFACE,
+        "AccessPointAdded",
+        (ObjectPath::try_from(AP_CAFE).unwrap(),).to_variant(),
+    );
+
+    // Signals arrive in order, so the strength change was skipped by the
+    // time the new access point is seen
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while changes.get() == 0 && Instant::now() < deadline {
+        context.iteration(false);
+        thread::sleep(Duration::from_millis(10));
+    }
+    for _ in 0..10 {
+        context.iteration(false);
+        thread::sleep(Duration::from_millis(10));
+    }
+    assert_eq!(changes.get(), 1);
+    subscription.unsubscribe();
+}
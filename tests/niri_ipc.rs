@@ -0,0 +1,251 @@
+mod common;
+
+use common::{recorded_events, MockNiri};
+use niri_panel::niri_ipc::{
+    self, Action, LayoutSwitchTarget, LogicalOutput, NiriEvent, NiriSocket, OutputInfo,
+    WorkspaceReference,
+};
+use niri_panel::niri_state::NiriState;
+use serde_json::json;
+
+#[test]
+fn parses_every_recorded_event() {
+    for line in recorded_events() {
+        let json = serde_json::from_str(&line).expect("recorded event is not JSON");
+        let event = niri_ipc::parse_event(json);
+        assert!(event.is_some(), "failed to parse recorded event: {}", line);
+    }
+}
+
+#[test]
+fn unknown_events_are_kept_as_unknown() {
+    let event = niri_ipc::parse_event(json!({ "ScreenshotCaptured": { "path": null } }));
+    assert!(matches!(event, Some(NiriEvent::Unknown(_))));
+}
+
+#[test]
+fn known_events_with_a_new_shape_are_rejected() {
+    // `id` became a string: the parser must not silently misread it
+    let event = niri_ipc::parse_event(json!({ "WorkspaceActivated": { "id": "1", "focused": true } }));
+    assert!(event.is_none());
+}
+
+#[test]
+fn focus_can_be_cleared() {
+    let event = niri_ipc::parse_event(json!({ "WindowFocusChanged": { "id": null } }));
+    assert!(matches!(event, Some(NiriEvent::WindowFocusChanged { id: None })));
+}
+
+#[test]
+fn layout_changes_without_a_tile_size_only_skip_that_window() {
+    let event = niri_ipc::parse_event(json!({ "WindowLayoutsChanged": { "changes": [
+        [10, { "tile_size": [1920.0, 1080.0] }],
+        [11, { "pos_in_scrolling_layout": [1, 1] }],
+        [12, { "tile_size": [800.0, 600.0] }],
+    ] } }));
+    let Some(NiriEvent::WindowLayoutsChanged { tile_sizes }) = event else {
+        panic!("failed to parse layout changes: {:?}", event);
+    };
+    assert_eq!(tile_sizes, vec![(10, (1920.0, 1080.0)), (12, (800.0, 600.0))]);
+}
+
+#[test]
+fn state_keeps_outputs_by_name() {
+    let output = |name: &str, width| OutputInfo {
+        name: name.to_string(),
+        make: String::new(),
+        model: String::new(),
+        logical: Some(LogicalOutput {
+            x: 0,
+            y: 0,
+            width,
+            height: 1080,
+            scale: 1.0,
+        }),
+    };
+
+    let mut state = NiriState::default();
+    state.apply(&NiriEvent::OutputsChanged {
+        outputs: vec![output("HDMI-A-1", 1920), output("DP-1", 2560)],
+    });
+    assert_eq!(state.output("DP-1").and_then(|o| o.logical).map(|l| l.width), Some(2560));
+
+    // A new mode replaces the old size
+    state.apply(&NiriEvent::OutputsChanged {
+        outputs: vec![output("DP-1", 1280)],
+    });
+    assert_eq!(state.output("DP-1").and_then(|o| o.logical).map(|l| l.width), Some(1280));
+    assert!(state.output("HDMI-A-1").is_none());
+}
+
+#[test]
+fn queries_workspaces_sorted_by_id() {
+    let niri = MockNiri::start();
+    let workspaces = NiriSocket::connect_to(niri.socket_path())
+        .unwrap()
+        .workspaces()
+        .unwrap();
+
+    let ids: Vec<u64> = workspaces.iter().map(|w| w.id).collect();
+    assert_eq!(ids, vec![1, 2, 3, 4]);
+    assert_eq!(workspaces[0].name.as_deref(), Some("web"));
+    assert_eq!(workspaces[0].output, "DP-1");
+    assert!(workspaces[0].is_focused);
+}
+
+#[test]
+fn queries_windows_with_missing_optional_fields() {
+    let niri = MockNiri::start();
+    let windows = NiriSocket::connect_to(niri.socket_path())
+        .unwrap()
+        .windows()
+        .unwrap();
+
+    assert_eq!(windows.len(), 3);
+    let music = windows.iter().find(|w| w.id == 12).unwrap();
+    assert_eq!(music.app_id, None);
+    assert_eq!(music.pid, 0);
+    assert_eq!(music.workspace_id, Some(3));
+    assert!(music.is_floating);
+    assert_eq!(music.tile_size, Some((800.0, 600.0)));
+}
+
+#[test]
+fn sends_typed_actions() {
+    let niri = MockNiri::start();
+    NiriSocket::connect_to(niri.socket_path())
+        .unwrap()
+        .action(Action::FocusWorkspace {
+            reference: WorkspaceReference::Index(2),
+        })
+        .unwrap();
+    NiriSocket::connect_to(niri.socket_path())
+        .unwrap()
+        .action(Action::CloseWindow { id: Some(11) })
+        .unwrap();
+    NiriSocket::connect_to(niri.socket_path())
+        .unwrap()
+        .action(Action::ToggleOverview {})
+        .unwrap();
+    NiriSocket::connect_to(niri.socket_path())
+        .unwrap()
+        .action(Action::MoveWindowToWorkspace {
+            window_id: Some(13),
+            reference: WorkspaceReference::Id(3),
+            focus: false,
+        })
+        .unwrap();
+    NiriSocket::connect_to(niri.socket_path())
+        .unwrap()
+        .action(Action::SwitchLayout {
+            layout: LayoutSwitchTarget::Next,
+        })
+        .unwrap();
+
+    assert_eq!(
+        niri.actions(),
+        vec![
+            json!({ "FocusWorkspace": { "reference": { "Index": 2 } } }),
+            json!({ "CloseWindow": { "id": 11 } }),
+            json!({ "ToggleOverview": {} }),
+            json!({ "MoveWindowToWorkspace": {
+                "window_id": 13,
+                "reference": { "Id": 3 },
+                "focus": false
+            } }),
+            json!({ "SwitchLayout": { "layout": "Next" } }),
+        ]
+    );
+}
+
+#[test]
+fn reports_errors_from_niri() {
+    let niri = MockNiri::start();
+    let error = NiriSocket::connect_to(niri.socket_path())
+        .unwrap()
+        .outputs()
+        .unwrap_err();
+    assert!(error.to_string().contains("unsupported request"), "{}", error);
+}
+
+#[test]
+fn fails_cleanly_without_a_socket() {
+    let niri = MockNiri::start();
+    let missing = niri.socket_path().with_file_name("missing.sock");
+    assert!(NiriSocket::connect_to(&missing).is_err());
+}
+
+/// Replay the recorded stream through the socket into a fresh state
+fn replay_event_stream() -> NiriState {
+    let niri = MockNiri::start();
+    let (events, _handle) = NiriSocket::connect_to(niri.socket_path())
+        .unwrap()
+        .into_event_stream()
+        .unwrap();
+
+    let mut state = NiriState::default();
+    // The mock closes the stream after the last event, which ends the iterator
+    for event in events {
+        state.apply(&event);
+    }
+    state
+}
+
+#[test]
+fn event_stream_keeps_one_active_workspace_per_output() {
+    let state = replay_event_stream();
+
+    let dp1 = state.workspaces_on_output(Some("DP-1"));
+    assert_eq!(dp1.iter().map(|w| w.id).collect::<Vec<_>>(), vec![1, 2]);
+    assert!(!dp1[0].is_active && !dp1[0].is_focused);
+    assert!(dp1[1].is_active && dp1[1].is_focused);
+    assert_eq!(dp1[1].active_window_id, Some(13));
+
+    // Activating a workspace on DP-1 leaves HDMI-A-1's active workspace alone
+    let hdmi = state.workspaces_on_output(Some("HDMI-A-1"));
+    assert_eq!(hdmi.iter().map(|w| w.id).collect::<Vec<_>>(), vec![3, 4]);
+    assert!(hdmi[0].is_active && !hdmi[0].is_focused);
+    assert!(hdmi[0].is_urgent);
+
+    assert_eq!(state.workspaces_on_output(None).len(), 4);
+}
+
+#[test]
+fn event_stream_tracks_windows_incrementally() {
+    let state = replay_event_stream();
+
+    let ids: Vec<u64> = state.windows().map(|w| w.id).collect();
+    assert_eq!(ids, vec![10, 12, 13]);
+    assert_eq!(state.focused_window().map(|w| w.id), Some(13));
+    assert!(state.window(12).unwrap().is_urgent);
+
+    let on_second: Vec<u64> = state.windows_on_workspace(2).iter().map(|w| w.id).collect();
+    assert_eq!(on_second, vec![13]);
+
+    // Windows from the event stream carry no layout until niri reports one
+    assert_eq!(state.window(10).unwrap().tile_size, None);
+    assert_eq!(state.window(13).unwrap().tile_size, Some((1280.0, 1400.0)));
+}
+
+#[test]
+fn each_output_has_its_own_active_window() {
+    let state = replay_event_stream();
+
+    let active = |output| state.active_window_on_output(output).map(|w| w.id);
+    assert_eq!(active(Some("DP-1")), Some(13));
+    // Not focused, but still shown on its own monitor
+    assert_eq!(active(Some("HDMI-A-1")), Some(12));
+    assert_eq!(active(Some("eDP-1")), None);
+    assert_eq!(active(None), Some(13));
+    assert_eq!(state.focused_output(), Some("DP-1"));
+}
+
+#[test]
+fn event_stream_tracks_keyboard_layout_and_overview() {
+    let state = replay_event_stream();
+
+    let layouts = state.keyboard_layouts().unwrap();
+    assert_eq!(layouts.names, vec!["English (US)", "German"]);
+    assert_eq!(layouts.current_idx, 1);
+    assert!(state.is_overview_open());
+}
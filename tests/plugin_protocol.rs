@@ -0,0 +1,81 @@
+use niri_panel::plugin::{self, Element, ListItem, PluginEvent, PluginMessage};
+
+#[test]
+fn parses_bar_messages_with_defaults() {
+    let message = plugin::parse_message(r#"{"type": "bar", "text": "3 updates"}"#).unwrap();
+    assert_eq!(
+        message,
+        PluginMessage::Bar {
+            text: "3 updates".to_string(),
+            icon: None,
+            tooltip: None,
+            class: Vec::new(),
+        }
+    );
+}
+
+#[test]
+fn parses_every_popover_element() {
+    let message = plugin::parse_message(
+        r#"{"type": "popover", "elements": [
+            {"type": "list", "id": "hosts", "items": [{"id": "a", "label": "Host A", "detail": "up"}]},
+            {"type": "button", "id": "refresh", "label": "Refresh", "icon": "view-refresh-symbolic"},
+            {"type": "slider", "id": "level", "value": 40},
+            {"type": "toggle", "id": "enabled", "label": "Enabled", "active": true},
+            {"type": "entry", "id": "filter", "placeholder": "Filter"}
+        ]}"#,
+    )
+    .unwrap();
+
+    let PluginMessage::Popover { elements } = message else {
+        panic!("expected a popover message");
+    };
+    assert_eq!(
+        elements.iter().map(Element::id).collect::<Vec<_>>(),
+        vec!["hosts", "refresh", "level", "enabled", "filter"]
+    );
+    assert_eq!(
+        elements[0],
+        Element::List {
+            id: "hosts".to_string(),
+            items: vec![ListItem {
+                id: "a".to_string(),
+                label: "Host A".to_string(),
+                detail: Some("up".to_string()),
+                icon: None,
+            }],
+        }
+    );
+    assert_eq!(
+        elements[2],
+        Element::Slider {
+            id: "level".to_string(),
+            label: None,
+            value: 40.0,
+            min: 0.0,
+            max: 100.0,
+            step: 1.0,
+        }
+    );
+}
+
+#[test]
+fn rejects_unknown_messages() {
+    assert!(plugin::parse_message(r#"{"type": "notification", "text": "hi"}"#).is_err());
+    assert!(plugin::parse_message("not json").is_err());
+}
+
+#[test]
+fn encodes_events_as_tagged_json() {
+    assert_eq!(
+        plugin::encode_event(&PluginEvent::Selected {
+            id: "hosts".to_string(),
+            item: "a".to_string(),
+        }),
+        r#"{"event":"selected","id":"hosts","item":"a"}"#
+    );
+    assert_eq!(
+        plugin::encode_event(&PluginEvent::PopoverOpened),
+        r#"{"event":"popover-opened"}"#
+    );
+}
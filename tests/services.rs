@@ -0,0 +1,185 @@
+use gtk4::glib;
+use niri_panel::services::{Service, ServiceSpec};
+use niri_panel::widgets::Lifecycle;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const FAST: Duration = Duration::from_millis(10);
+const SLOW: Duration = Duration::from_secs(60);
+
+fn spec<T>(fetch: fn() -> T, interval: Duration, idle_interval: Duration) -> ServiceSpec<T> {
+    ServiceSpec {
+        name: "test",
+        fetch,
+        interval,
+        idle_interval,
+        monitor: None,
+        on_change: None,
+    }
+}
+
+/// Run `test` with a fresh main context as the thread default, standing in
+/// for the panel's main loop
+fn with_main_context(test: impl FnOnce(&glib::MainContext)) {
+    let context = glib::MainContext::new();
+    context.with_thread_default(|| test(&context)).unwrap();
+}
+
+/// Iterate the main context until `done` returns true, or fail after a while
+fn run_until(context: &glib::MainContext, done: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !done() {
+        assert!(
+            Instant::now() < deadline,
+            "timed out waiting for the service"
+        );
+        if !context.iteration(false) {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+/// Iterate the main context for a fixed time
+fn run_for(context: &glib::MainContext, duration: Duration) {
+    let deadline = Instant::now() + duration;
+    run_until(context, || Instant::now() >= deadline);
+}
+
+/// Record every value the service hands to a subscriber
+fn record<T: Clone + PartialEq + Send + 'static>(
+    service: &Service<T>,
+    lifecycle: &Lifecycle,
+) -> Rc<RefCell<Vec<T>>> {
+    let values = Rc::new(RefCell::new(Vec::new()));
+    let values_subscriber = values.clone();
+    service.subscribe(lifecycle, move |value| {
+        values_subscriber.borrow_mut().push(value.clone())
+    });
+    values
+}
+
+static CHANGING_VALUE: AtomicU32 = AtomicU32::new(1);
+static CHANGING_POLLS: AtomicU32 = AtomicU32::new(0);
+
+fn fetch_changing() -> u32 {
+    CHANGING_POLLS.fetch_add(1, Ordering::SeqCst);
+    CHANGING_VALUE.load(Ordering::SeqCst)
+}
+
+#[test]
+fn subscribers_hear_only_about_changes() {
+    with_main_context(|context| {
+        let service = Service::new(spec(fetch_changing, FAST, FAST));
+        let lifecycle = Lifecycle::new();
+        let values = record(&service, &lifecycle);
+        let polls = || CHANGING_POLLS.load(Ordering::SeqCst);
+
+        run_until(context, || polls() >= 3);
+        assert_eq!(*values.borrow(), vec![1]);
+
+        CHANGING_VALUE.store(2, Ordering::SeqCst);
+        let after_change = polls();
+        run_until(context, || polls() >= after_change + 3);
+        assert_eq!(*values.borrow(), vec![1, 2]);
+
+        // A late subscriber gets the current value right away
+        let late = record(&service, &lifecycle);
+        assert_eq!(*late.borrow(), vec![2]);
+
+        lifecycle.shutdown();
+    });
+}
+
+static INTERVAL_POLLS: AtomicU32 = AtomicU32::new(0);
+
+fn fetch_counting() -> u32 {
+    INTERVAL_POLLS.fetch_add(1, Ordering::SeqCst)
+}
+
+#[test]
+fn polls_fast_only_while_shown() {
+    with_main_context(|context| {
+        let service = Service::new(spec(fetch_counting, FAST, SLOW));
+        let lifecycle = Lifecycle::new();
+        let _values = record(&service, &lifecycle);
+        let polls = || INTERVAL_POLLS.load(Ordering::SeqCst);
+
+        // Idle: one poll on start, then nothing for a minute
+        run_until(context, || service.get().is_some());
+        run_for(context, FAST * 10);
+        assert_eq!(polls(), 1);
+
+        // Shown: a poll right away, then one per fast interval
+        let set_shown = service.track_shown(&lifecycle);
+        set_shown(true);
+        run_until(context, || polls() >= 5);
+
+        // Hidden again: at most the poll already under way finishes
+        set_shown(false);
+        let when_hidden = polls();
+        run_for(context, FAST * 10);
+        assert!(
+            polls() <= when_hidden + 1,
+            "still polling fast after hiding"
+        );
+
+        lifecycle.shutdown();
+    });
+}
+
+static STALE_ROUND: AtomicU32 = AtomicU32::new(1);
+
+fn fetch_slowly() -> u32 {
+    let round = STALE_ROUND.load(Ordering::SeqCst);
+    thread::sleep(Duration::from_millis(50));
+    round
+}
+
+#[test]
+fn results_from_before_a_restart_are_dropped() {
+    with_main_context(|context| {
+        let service = Service::new(spec(fetch_slowly, SLOW, SLOW));
+
+        // Stop while the first poll is still running
+        let first = Lifecycle::new();
+        let _ = record(&service, &first);
+        first.shutdown();
+
+        STALE_ROUND.store(2, Ordering::SeqCst);
+        let second = Lifecycle::new();
+        let values = record(&service, &second);
+        run_until(context, || !values.borrow().is_empty());
+        // Give the first poll's result time to arrive as well
+        run_for(context, Duration::from_millis(100));
+
+        assert_eq!(*values.borrow(), vec![2]);
+        assert_eq!(service.get(), Some(2));
+
+        second.shutdown();
+    });
+}
+
+fn fetch_constant() -> u32 {
+    42
+}
+
+#[test]
+fn value_is_cleared_when_the_last_subscriber_leaves() {
+    with_main_context(|context| {
+        let service = Service::new(spec(fetch_constant, SLOW, SLOW));
+        let first = Lifecycle::new();
+        let second = Lifecycle::new();
+        let _ = record(&service, &first);
+        let _ = record(&service, &second);
+
+        run_until(context, || service.get().is_some());
+        first.shutdown();
+        assert_eq!(service.get(), Some(42));
+
+        second.shutdown();
+        assert_eq!(service.get(), None);
+    });
+}
@@ -0,0 +1,22 @@
+use niri_panel::services::system;
+
+const STAT: &str = "cpu  100 0 50 800 50 0 0 0 0 0
+cpu0 50 0 25 400 25 0 0 0 0 0
+intr 12345
+";
+
+#[test]
+fn parses_busy_and_total_time_from_the_cpu_line() {
+    // idle and iowait are not busy
+    assert_eq!(system::parse_cpu_times(STAT), Some((150, 1000)));
+    assert_eq!(system::parse_cpu_times("intr 12345\n"), None);
+    assert_eq!(system::parse_cpu_times("cpu  1 2\n"), None);
+}
+
+#[test]
+fn measures_usage_since_the_previous_reading() {
+    assert_eq!(system::cpu_usage(Some((150, 1000)), (400, 1500)), 50.0);
+    // Without a previous reading, the average since boot
+    assert_eq!(system::cpu_usage(None, (250, 1000)), 25.0);
+    assert_eq!(system::cpu_usage(Some((150, 1000)), (150, 1000)), 0.0);
+}